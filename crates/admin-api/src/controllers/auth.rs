@@ -1,43 +1,42 @@
 use std::collections::{HashMap, HashSet};
 
 use argon2::{password_hash::{rand_core::OsRng, SaltString}, Argon2, PasswordHash, PasswordVerifier, PasswordHasher};
-use axum::{debug_handler, extract::State, middleware, response::IntoResponse, routing::{get, post}, Extension, Json, Router};
-use bubo::{controllers::middlewares::auth::{self, create_token, AuthUser}, server::AppState, 
-utils::{error::{BuboError, BuboResult, BusinessErrorCode, SystemErrorCode}, redis, time::now_utc_primitive, validator::JsonValid}, 
-views::auth::AuthUserResponse};
+use axum::{debug_handler, extract::State, response::IntoResponse, Extension, Json};
+use bubo::{controllers::{middlewares::auth::{create_token, AuthUser}, routing::{get, post, refresh, secured, Routes}}, server::AppState, 
+utils::{error::{BuboError, BuboResult, BusinessErrorCode, SystemErrorCode}, prometheus, time::now_utc_primitive, validator::JsonValid}, 
+views::{auth::{AuthUserResponse, TokenResponse}, response::ApiResponse}};
+use schemars::JsonSchema;
 use sea_orm::{ActiveModelTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter, QuerySelect, Set};
 use serde::{Deserialize, Serialize};
 use admin_migration::sea_orm::ColumnTrait;
-use serde_json::json;
 use sea_orm::QueryOrder;
 use validator::Validate;
 
 use crate::models::_entities::{admin_menu, admin_role, admin_role_menu, admin_user, admin_user_role, 
     prelude::{AdminMenu, AdminRoleMenu, AdminUser, AdminUserRole, AdminRole}};
 
-pub(crate) fn init_routes(state: AppState) -> Router {
-    Router::new()
-        //登录
-        .route("/auth/login/account", post(account_login_handler))
-        .route("/auth/refresh-token",post(refresh_token_handler)
-            .route_layer(middleware::from_fn_with_state(state.clone(),auth::refresh))
-        )
-        .route("/auth/logout",post(logout_handler)
-            .route_layer(middleware::from_fn_with_state(state.clone(),auth::auth))
-        )
-        .route("/auth/user-info",get(user_info_handler)
-            .route_layer(middleware::from_fn_with_state(state.clone(),auth::auth))
-        )
-        .route("/auth/user-routes", get(user_routes_handler)
-            .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth))
-        )
-        .route("/auth/change-pwd", post(change_password_handler)
-            .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth))
-        )
-        .with_state(state)
+pub(crate) fn routes() -> Routes {
+    Routes::new()
+    //登录
+    .route("/auth/login/account", post(account_login_handler).rate_limit("login")
+        .summary("帐号登录").json::<LoginUserParams>().raw_response::<TokenResponse>()
+    )
+    .route("/auth/refresh-token", refresh(post(refresh_token_handler))
+        .summary("刷新令牌").raw_response::<TokenResponse>()
+    )
+    .route("/auth/logout", secured(post(logout_handler)).summary("退出登录"))
+    .route("/auth/user-info", secured(get(user_info_handler))
+        .summary("获取用户信息").response::<AuthUserResponse>()
+    )
+    .route("/auth/user-routes", secured(get(user_routes_handler))
+        .summary("获取用户路由").response::<Vec<Route>>()
+    )
+    .route("/auth/change-pwd", secured(post(change_password_handler))
+        .summary("修改密码").json::<ChangePasswordParams>()
+    )
 }
 
-#[derive(Debug, Deserialize, Validate)]
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
 pub(crate) struct LoginUserParams {
     #[validate(length(min = 3, max = 20))]
     username: String,
@@ -45,13 +44,13 @@ pub(crate) struct LoginUserParams {
     password: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub(crate) struct ChangePasswordParams {
     pub old_password: String,
     pub new_password: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 struct Route {
     pub path: String,
     pub name: String,
@@ -84,25 +83,22 @@ pub(crate) async fn account_login_handler(
                 Err(_) => false,
             };
             if !is_valid {
-                return Err(BuboError::business_error(BusinessErrorCode::UserOrPasswordNotMatch, "用户名或密码错误"));
+                prometheus::record_login(false);
+                return Err(BuboError::from(BusinessErrorCode::UserOrPasswordNotMatch));
             }
 
             let (roles, permissions, menu_ids) = get_user_roles_and_permissions(&state.db, admin_user.id).await?;
-            let auth_user = AuthUser::new(admin_user.id, admin_user.username, admin_user.nick_name, admin_user.is_admin, 0, 
+            let mut auth_user = AuthUser::new(admin_user.id, admin_user.username, admin_user.nick_name, admin_user.is_admin, 0, 
                 0, roles, permissions, menu_ids);
-            let (access_token, refresh_token, token_type, expires_in) = create_token(&state, auth_user).await?;
-
-            let result = json!({
-                "status":  true,
-                "access_token": access_token,
-                "refresh_token": refresh_token,
-                "token_type": token_type, 
-                "expires_in": expires_in,
-            });
-            Ok(Json(result))
+            if let Some(locale) = admin_user.locale {
+                auth_user = auth_user.with_locale(locale);
+            }
+            let token = create_token(&state, auth_user).await?;
+            Ok(Json(TokenResponse::new(token)))
         }
         None => {
-            Err(BuboError::business_error(BusinessErrorCode::UserOrPasswordNotMatch, "用户名或密码错误"))
+            prometheus::record_login(false);
+            Err(BuboError::from(BusinessErrorCode::UserOrPasswordNotMatch))
         }
     }
 }
@@ -116,16 +112,8 @@ pub(crate) async fn refresh_token_handler(
     Extension(auth_user): Extension<AuthUser>
 ) -> BuboResult<impl IntoResponse> {
     // let mut new_auth_user = auth_user.clone();
-    let (access_token, refresh_token, token_type, expires_in) = create_token(&state, auth_user).await?;
-    
-    let result = json!({
-        "status":  true,
-        "access_token": access_token,
-        "refresh_token": refresh_token,
-        "token_type": token_type,
-        "expires_in": expires_in,
-    });
-    Ok(Json(result))
+    let token = create_token(&state, auth_user).await?;
+    Ok(Json(TokenResponse::new(token)))
 }
 
 ///
@@ -136,12 +124,8 @@ pub(crate) async fn logout_handler(
     State(state): State<AppState>, 
     Extension(auth_user): Extension<AuthUser>
 ) -> BuboResult<impl IntoResponse> {
-    let key = redis::gen_key(state.app_name, "adminlogin", auth_user.id);
-    redis::del(&state.redis, key.as_str()).await?;
-    let result = json!({
-        "status":  true,
-    });
-    Ok(Json(result))
+    state.sessions.remove(auth_user.id).await?;
+    Ok(ApiResponse::empty())
 }
 
 ///
@@ -151,11 +135,7 @@ pub(crate) async fn logout_handler(
 pub(crate) async fn user_info_handler(
     Extension(auth_user): Extension<AuthUser>
 ) -> BuboResult<impl IntoResponse> {
-    let result = json!({
-        "status":  true,
-        "data": AuthUserResponse::new(auth_user),
-    });
-    Ok(Json(result))
+    Ok(ApiResponse::ok(AuthUserResponse::new(auth_user)))
 }
 
 ///
@@ -201,11 +181,7 @@ pub(crate) async fn user_routes_handler(
         }
     }
     
-    let result = json!({
-        "status":  true,
-        "data": datas,
-    });
-    Ok(Json(result))
+    Ok(ApiResponse::ok(datas))
 }
 
 ///
@@ -247,7 +223,7 @@ pub(crate) async fn change_password_handler(
                 Err(_) => false,
             };
             if !is_valid {
-                return Err(BuboError::business_error(BusinessErrorCode::PasswordNotMatch, "旧密码错误"));
+                return Err(BuboError::business_error(BusinessErrorCode::PasswordNotMatch, "auth.old_password_not_match"));
             }
             
             let salt = SaltString::generate(&mut OsRng);
@@ -269,10 +245,7 @@ pub(crate) async fn change_password_handler(
         }
     }
 
-    let result = json!({
-        "status": true,
-    });
-    Ok(Json(result))
+    Ok(ApiResponse::empty())
 }
 
 // 获取用户的角色和权限
@@ -0,0 +1,55 @@
+use axum::{debug_handler, extract::{Multipart, State}, response::IntoResponse};
+use bubo::{controllers::routing::{post, secured, Routes}, server::AppState, storage::upload::{save_multipart, UploadPolicy, UploadedFile}, utils::error::BuboResult, views::response::ApiResponse};
+
+// 头像大小上限
+const AVATAR_MAX_SIZE: u64 = 2 * 1024 * 1024;
+
+pub(crate) fn routes() -> Routes {
+    // 文件上传
+    Routes::new()
+    .route("/file/avatar", secured(post(upload_avatar))
+        .with_layer(|router, state| router.layer(avatar_policy(state).body_limit()))
+        .summary("上传头像").multipart().response::<UploadedFile>()
+    )
+    .route("/file/attachment", secured(post(upload_attachment))
+        .with_layer(|router, state| router.layer(attachment_policy(state).body_limit()))
+        .summary("上传附件").multipart().response::<Vec<UploadedFile>>()
+    )
+}
+
+fn avatar_policy(state: &AppState) -> UploadPolicy {
+    UploadPolicy::from_config(&state.config.storage, "avatars")
+        .max_size(AVATAR_MAX_SIZE.min(state.config.storage.max_upload_size))
+        .max_files(1)
+        .allowed_types(["image/png", "image/jpeg", "image/gif", "image/webp"])
+}
+
+fn attachment_policy(state: &AppState) -> UploadPolicy {
+    UploadPolicy::from_config(&state.config.storage, "attachments")
+}
+
+///
+/// 上传头像
+/// 
+#[debug_handler]
+pub(crate) async fn upload_avatar(
+    State(state): State<AppState>,
+    multipart: Multipart,
+) -> BuboResult<impl IntoResponse> {
+    let mut files = save_multipart(&state, multipart, &avatar_policy(&state)).await?;
+
+    Ok(ApiResponse::ok(files.pop()))
+}
+
+///
+/// 上传附件
+/// 
+#[debug_handler]
+pub(crate) async fn upload_attachment(
+    State(state): State<AppState>,
+    multipart: Multipart,
+) -> BuboResult<impl IntoResponse> {
+    let files = save_multipart(&state, multipart, &attachment_policy(&state)).await?;
+
+    Ok(ApiResponse::ok(files))
+}
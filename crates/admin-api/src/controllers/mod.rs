@@ -1,11 +1,12 @@
-use axum::Router;
-use bubo::server::AppState;
+use bubo::controllers::routing::Routes;
 
 mod auth;
+mod file;
 mod system;
 
-pub(crate) fn init_routes(state: AppState) -> Router {
-    Router::new()
-    .merge(auth::init_routes(state.clone()))
-    .merge(system::init_routes(state.clone()))
-}
\ No newline at end of file
+pub(crate) fn routes() -> Routes {
+    Routes::new()
+    .merge(auth::routes())
+    .merge(file::routes())
+    .merge(system::routes())
+}
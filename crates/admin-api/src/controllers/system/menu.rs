@@ -1,30 +1,24 @@
-use axum::{debug_handler, extract::State, middleware, response::IntoResponse, routing::{get, post}, Extension, Json, Router};
-use bubo::{controllers::{middlewares::auth::{self, AuthUser}, RemoveParams}, server::AppState, utils::error::BuboResult};
+use axum::{debug_handler, extract::State, response::IntoResponse, Extension, Json};
+use bubo::{controllers::{middlewares::auth::AuthUser, routing::{get, post, secured, Routes}, RemoveParams}, server::AppState, utils::error::BuboResult, views::response::ApiResponse};
 
-use serde_json::json;
 use crate::{models::{_entities::admin_menu, menu::{AddMenuParams, EditMenuParams}}, views::menu::MenuResponse};
 
 
-pub(crate) fn init_routes(state: AppState) -> Router {
+pub(crate) fn routes() -> Routes {
     // 菜单
-    Router::new()
-     .route("/system/menu/list", get(menu_list)
-     .route_layer(middleware::from_fn_with_state(state.clone(), auth::permission))
-     .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth))
+    Routes::new()
+    .route("/system/menu/list", secured(get(menu_list)).permission("system:menu:list")
+        .summary("菜单列表").response::<Vec<MenuResponse>>()
     )
-    .route("/system/menu/add", post(add_menu)
-        .route_layer(middleware::from_fn_with_state(state.clone(), auth::permission))
-        .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth))
+    .route("/system/menu/add", secured(post(add_menu)).permission("system:menu:add")
+        .summary("新增菜单").json::<AddMenuParams>().response::<MenuResponse>()
     )
-    .route("/system/menu/edit", post(edit_menu)
-        .route_layer(middleware::from_fn_with_state(state.clone(),auth::permission))
-        .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth))
+    .route("/system/menu/edit", secured(post(edit_menu)).permission("system:menu:edit")
+        .summary("编辑菜单").json::<EditMenuParams>().response::<MenuResponse>()
     )
-    .route("/system/menu/remove", post(remove_menu)
-        .route_layer(middleware::from_fn_with_state(state.clone(), auth::permission))
-        .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth))
+    .route("/system/menu/remove", secured(post(remove_menu)).permission("system:menu:remove")
+        .summary("删除菜单").json::<RemoveParams>()
     )
-    .with_state(state)
 }
 
 
@@ -41,12 +35,7 @@ pub(crate) async fn menu_list(
     // 转换返回对象
     let models: Vec<MenuResponse> = models.into_iter().map(|model| MenuResponse::new(model)).collect();
 
-    let result = json!({
-        "status": true,
-        "data": models,
-    });
-    
-    Ok(Json(result))
+    Ok(ApiResponse::ok(models))
 }
 
 ///
@@ -59,13 +48,9 @@ pub(crate) async fn add_menu(
     Json(params): Json<AddMenuParams>,
 ) -> BuboResult<impl IntoResponse> {
 
-    let model = admin_menu::Model::add(&state.db, params, auth_user.id).await?;
+    let model = admin_menu::Model::add(&state.db, &state.routes, params, auth_user.id).await?;
 
-    let result = json!({
-        "status": true,
-        "data": MenuResponse::new(model),
-    });
-    Ok(Json(result))
+    Ok(ApiResponse::ok(MenuResponse::new(model)))
 }
 
 ///
@@ -77,13 +62,9 @@ pub(crate) async fn edit_menu(
     Extension(auth_user): Extension<AuthUser>,
     Json(params): Json<EditMenuParams>,
 ) -> BuboResult<impl IntoResponse> {
-    let model = admin_menu::Model::edit(&state.db, params, auth_user.id).await?;
+    let model = admin_menu::Model::edit(&state.db, &state.routes, params, auth_user.id).await?;
     
-    let result = json!({
-        "status": true,
-        "data": MenuResponse::new(model),
-    });
-    Ok(Json(result))
+    Ok(ApiResponse::ok(MenuResponse::new(model)))
 }
 
 ///
@@ -98,8 +79,5 @@ pub(crate) async fn remove_menu(
     
     admin_menu::Model::remove(&state.db, params, auth_user.id).await?;
 
-    let result = json!({
-        "status": true,
-    });
-    Ok(Json(result))
+    Ok(ApiResponse::empty())
 }
@@ -1,13 +1,12 @@
-use axum::Router;
-use bubo::server::AppState;
+use bubo::controllers::routing::Routes;
 
 pub(crate) mod menu;
 pub(crate) mod role;
 pub(crate) mod user;
 
-pub(crate) fn init_routes(state: AppState) -> Router {
-    Router::new()
-    .merge(menu::init_routes(state.clone()))
-    .merge(role::init_routes(state.clone()))
-    .merge(user::init_routes(state.clone()))
-}
\ No newline at end of file
+pub(crate) fn routes() -> Routes {
+    Routes::new()
+    .merge(menu::routes())
+    .merge(role::routes())
+    .merge(user::routes())
+}
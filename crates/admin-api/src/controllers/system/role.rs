@@ -1,34 +1,27 @@
-use axum::{debug_handler, extract::{Query, State}, middleware, response::IntoResponse, routing::{get, post}, Extension, Json, Router};
-use bubo::{controllers::{middlewares::auth::{self, AuthUser}, RemoveParams}, server::AppState, utils::error::BuboResult};
-use serde_json::json;
+use axum::{debug_handler, extract::{Query, State}, response::IntoResponse, Extension, Json};
+use bubo::{controllers::{middlewares::auth::AuthUser, routing::{get, post, secured, Routes}, RemoveParams}, server::AppState, utils::error::BuboResult, views::response::{ApiResponse, PageResponse}};
 
 use crate::{models::{_entities::admin_role, role::{AddRoleParams, EditRoleParams, RolePageParams}}, views::role::RoleResponse};
 
 
-pub(crate) fn init_routes(state: AppState) -> Router {
+pub(crate) fn routes() -> Routes {
     // 角色
-    Router::new()
-    .route("/system/role/list", get(role_list)
-        .route_layer(middleware::from_fn_with_state(state.clone(), auth::permission))
-        .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth))
+    Routes::new()
+    .route("/system/role/list", secured(get(role_list)).permission("system:role:list")
+        .summary("角色列表").response::<Vec<RoleResponse>>()
     )
-    .route("/system/role/page", get(role_page)
-        .route_layer(middleware::from_fn_with_state(state.clone(), auth::permission))
-        .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth))
+    .route("/system/role/page", secured(get(role_page)).permission("system:role:page")
+        .summary("角色分页").query::<RolePageParams>().page::<RoleResponse>()
     )
-    .route("/system/role/add", post(add_role)
-        .route_layer(middleware::from_fn_with_state(state.clone(), auth::permission))
-        .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth))
+    .route("/system/role/add", secured(post(add_role)).permission("system:role:add")
+        .summary("新增角色").json::<AddRoleParams>().response::<RoleResponse>()
     )
-    .route("/system/role/edit", post(edit_role)
-        .route_layer(middleware::from_fn_with_state(state.clone(),auth::permission))
-        .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth))
+    .route("/system/role/edit", secured(post(edit_role)).permission("system:role:edit")
+        .summary("编辑角色").json::<EditRoleParams>().response::<RoleResponse>()
     )
-    .route("/system/role/remove", post(remove_role)
-        .route_layer(middleware::from_fn_with_state(state.clone(), auth::permission))
-        .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth))
+    .route("/system/role/remove", secured(post(remove_role)).permission("system:role:remove")
+        .summary("删除角色").json::<RemoveParams>()
     )
-    .with_state(state)
 }
 
 ///
@@ -44,12 +37,7 @@ pub(crate) async fn role_list(
     // 转换返回对象
     let datas: Vec<RoleResponse> = models.into_iter().map(|model| RoleResponse::new(model)).collect();
 
-    let result = json!({
-        "status":  true,
-        "data": datas,
-    });
-    
-    Ok(Json(result))
+    Ok(ApiResponse::ok(datas))
 }
 
 ///
@@ -62,18 +50,12 @@ pub(crate) async fn role_page(
     Query(params): Query<RolePageParams>
 ) -> BuboResult<impl IntoResponse> {
 
-    let (models, num_pages) = admin_role::Model::page(&state.db, params).await?;
+    let page = admin_role::Model::page(&state.db, params).await?;
 
     // 转换返回对象
-    let datas: Vec<RoleResponse> = models.into_iter().map(|model| RoleResponse::new(model)).collect();
+    let page = page.map(RoleResponse::new);
 
-    let result = json!({
-        "status":  true,
-        "data": datas,
-        "num_pages": num_pages,
-    });
-    
-    Ok(Json(result))
+    Ok(PageResponse::new(page))
 }
 
 ///
@@ -88,11 +70,7 @@ pub(crate) async fn add_role(
     
     let model = admin_role::Model::add(&state.db, params, auth_user.id).await?;
 
-    let result = json!({
-        "status": true,
-        "data": RoleResponse::new(model),
-    });
-    Ok(Json(result))
+    Ok(ApiResponse::ok(RoleResponse::new(model)))
 }
 
 ///
@@ -106,11 +84,7 @@ pub(crate) async fn edit_role(
 ) -> BuboResult<impl IntoResponse> {
     let model = admin_role::Model::edit(&state.db, params, auth_user.id).await?;
 
-    let result = json!({
-        "status":  true,
-        "data": RoleResponse::new(model),
-    });
-    Ok(Json(result))
+    Ok(ApiResponse::ok(RoleResponse::new(model)))
 }
 
 ///
@@ -125,8 +99,5 @@ pub(crate) async fn remove_role(
     
     admin_role::Model::remove(&state.db, params, auth_user.id).await?;
 
-    let result = json!({
-        "status":  true,
-    });
-    Ok(Json(result))
+    Ok(ApiResponse::empty())
 }
\ No newline at end of file
@@ -1,26 +1,21 @@
-use axum::{debug_handler, extract::{Query, State}, middleware, response::IntoResponse, routing::{get, post}, Extension, Json, Router};
-use bubo::{controllers::middlewares::auth::{self, AuthUser}, server::AppState, utils::error::BuboResult};
-use serde_json::json;
+use axum::{debug_handler, extract::{Query, State}, response::IntoResponse, Extension, Json};
+use bubo::{controllers::{middlewares::auth::AuthUser, routing::{get, post, secured, Routes}}, server::AppState, utils::error::BuboResult, views::response::{ApiResponse, PageResponse}};
 
 use crate::{models::{_entities::admin_user, user::{AddUserParams, EditUserParams, UserPageParams}}, views::user::AdminUserResponse};
 
 
-pub(crate) fn init_routes(state: AppState) -> Router {
+pub(crate) fn routes() -> Routes {
     // 用户
-    Router::new()
-    .route("/system/user/page", get(user_page)
-        .route_layer(middleware::from_fn_with_state(state.clone(), auth::permission))
-        .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth))
+    Routes::new()
+    .route("/system/user/page", secured(get(user_page)).permission("system:user:page")
+        .summary("用户分页").query::<UserPageParams>().page::<AdminUserResponse>()
     )
-    .route("/system/user/add", post(add_user)
-        .route_layer(middleware::from_fn_with_state(state.clone(), auth::permission))
-        .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth))
+    .route("/system/user/add", secured(post(add_user)).permission("system:user:add")
+        .summary("新增用户").json::<AddUserParams>().response::<AdminUserResponse>()
     )
-    .route("/system/user/edit", post(edit_user)
-        .route_layer(middleware::from_fn_with_state(state.clone(), auth::permission))
-        .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth))
+    .route("/system/user/edit", secured(post(edit_user)).permission("system:user:edit")
+        .summary("编辑用户").json::<EditUserParams>().response::<AdminUserResponse>()
     )
-    .with_state(state)
 }
 
 ///
@@ -33,18 +28,12 @@ pub(crate) async fn user_page(
     Query(params): Query<UserPageParams>
 ) -> BuboResult<impl IntoResponse> {
 
-    let (models, num_pages) = admin_user::Model::page(&state.db, params).await?;
+    let page = admin_user::Model::page(&state.db, params).await?;
 
     // 转换返回对象
-    let datas: Vec<AdminUserResponse> = models.into_iter().map(|model| AdminUserResponse::new(model)).collect();
+    let page = page.map(AdminUserResponse::new);
 
-    let result = json!({
-        "status":  true,
-        "data": datas,
-        "num_pages": num_pages,
-    });
-    
-    Ok(Json(result))
+    Ok(PageResponse::new(page))
 }
 
 ///
@@ -58,11 +47,7 @@ pub(crate) async fn add_user(
 ) -> BuboResult<impl IntoResponse> {
     let model = admin_user::Model::add(&state.db, params, auth_user.id).await?;
     
-    let result = json!({
-        "status":  true,
-        "data": AdminUserResponse::new(model),
-    });
-    Ok(Json(result))
+    Ok(ApiResponse::ok(AdminUserResponse::new(model)))
 }
 
 ///
@@ -77,10 +62,6 @@ pub(crate) async fn edit_user(
     
     let model = admin_user::Model::edit(&state.db, params, auth_user.id).await?;
 
-    let result = json!({
-        "status":  true,
-        "data": AdminUserResponse::new(model),
-    });
-    Ok(Json(result))
+    Ok(ApiResponse::ok(AdminUserResponse::new(model)))
 }
 
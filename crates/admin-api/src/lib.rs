@@ -1,20 +1,31 @@
 use admin_migration::Migrator;
-use axum::Router;
-use bubo::server::{AppState, Hooks};
+use async_trait::async_trait;
+use bubo::{controllers::routing::Routes, i18n::{self, I18n}, server::Hooks, utils::error::BuboResult};
+use sea_orm::DatabaseConnection;
 
 mod controllers;
 mod models;
 mod views;
 
-struct App;
+pub struct App;
 
+#[async_trait]
 impl Hooks for App {
     fn app_name() ->  &'static str {
         env!("CARGO_PKG_NAME")
     }
 
-    fn router(state: AppState) -> Router {
-        Router::new().nest("/admin", controllers::init_routes(state))
+    fn routes() -> Routes {
+        Routes::new().nest("/admin", controllers::routes())
+    }
+
+    fn i18n(i18n: &mut I18n) {
+        i18n.add_toml(i18n::ZH_CN, include_str!("../locales/zh-CN.toml")).expect("Invalid locale file");
+        i18n.add_toml(i18n::EN_US, include_str!("../locales/en-US.toml")).expect("Invalid locale file");
+    }
+
+    async fn create_admin(db: &DatabaseConnection, username: &str, password: &str) -> BuboResult<()> {
+        models::_entities::admin_user::Model::create_admin(db, username, password).await.map(|_| ())
     }
 
     fn clean_up() {
@@ -24,7 +35,3 @@ impl Hooks for App {
 pub async fn main() {
     bubo::main::<App, Migrator>().await;
 }
-
-
-
-
@@ -22,6 +22,7 @@ pub struct Model {
     pub created_at: TimeDateTime,
     pub updated_by: i64,
     pub updated_at: TimeDateTime,
+    pub locale: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -1,18 +1,20 @@
+use schemars::JsonSchema;
 use sea_orm::{ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter, Set};
 use serde::Deserialize;
 use tracing::info;
 use validator::Validate;
 use crate::fill_active_model;
 use super::{FillActiveModelTrait, _entities::{admin_menu, prelude::AdminMenu}};
-use bubo::{controllers::RemoveParams, utils::{database::{ColOrd, EntityExtension}, error::{BuboError, BuboResult, BusinessErrorCode}, serde::to_i64}};
+use bubo::{controllers::{routing::RouteRegistry, RemoveParams}, i18n, utils::{database::{ColOrd, EntityExtension}, error::{BuboError, BuboResult, BusinessErrorCode}, serde::to_i64}};
 
 
 fill_active_model!(admin_menu::ActiveModel);
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub(crate) struct AddMenuParams {
     pub name: String,
     #[serde(deserialize_with = "to_i64")]
+    #[schemars(with = "bubo::openapi::Id")]
     pub parent_id: i64,
     pub url: String,
     pub target: i16,
@@ -25,12 +27,14 @@ pub(crate) struct AddMenuParams {
     pub remark: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub(crate) struct EditMenuParams {
     #[serde(deserialize_with = "to_i64")]
+    #[schemars(with = "bubo::openapi::Id")]
     pub id: i64,
     pub name: String,
     #[serde(deserialize_with = "to_i64")]
+    #[schemars(with = "bubo::openapi::Id")]
     pub parent_id: i64,
     pub url: String,
     pub target: i16,
@@ -53,7 +57,8 @@ impl admin_menu::Model {
         Ok(models)
     }
 
-    pub(crate) async fn add(db: &DatabaseConnection, params: AddMenuParams, operator: i64) -> BuboResult<Self> {
+    pub(crate) async fn add(db: &DatabaseConnection, routes: &RouteRegistry, params: AddMenuParams, operator: i64) -> BuboResult<Self> {
+        check_permission(routes, &params.permission)?;
         // 创建菜单model
         let mut active_model = admin_menu::ActiveModel {
             name: Set(params.name.clone()),
@@ -75,9 +80,10 @@ impl admin_menu::Model {
         Ok(model)
     }
 
-    pub(crate) async fn edit(db: &DatabaseConnection, params: EditMenuParams, operator: i64) -> BuboResult<Self> {
+    pub(crate) async fn edit(db: &DatabaseConnection, routes: &RouteRegistry, params: EditMenuParams, operator: i64) -> BuboResult<Self> {
+        check_permission(routes, &params.permission)?;
         let menu = AdminMenu::find_by_id(params.id).one(db).await?
-        .ok_or(BuboError::business_error(BusinessErrorCode::NotFound, "菜单不存在"))?;
+        .ok_or(BuboError::business_error(BusinessErrorCode::NotFound, "menu.not_found"))?;
         //创建菜单更新model
         let mut active_model: admin_menu::ActiveModel = menu.into();
         active_model.name = Set(params.name.clone());
@@ -107,7 +113,7 @@ impl admin_menu::Model {
         .all(db)
         .await?;
         if admin_menu_vec.is_empty() {
-            return Err(BuboError::business_error(BusinessErrorCode::NotFound, "menu not found"));
+            return Err(BuboError::business_error(BusinessErrorCode::NotFound, "menu.not_found"));
         }
         // 遍历取出菜单id和名称
         let menu_vec: Vec<(i64, String)> = admin_menu_vec.iter().map(|x| (x.id, x.name.clone())).collect();
@@ -115,9 +121,20 @@ impl admin_menu::Model {
         // 删除菜单
         let result = AdminMenu::delete_many().filter(condition).exec(db).await?;
         if result.rows_affected == 0 {
-            return Err(BuboError::business_error(BusinessErrorCode::NotFound, "menu not found"));
+            return Err(BuboError::business_error(BusinessErrorCode::NotFound, "menu.not_found"));
         }
         info!("operator: {}, delete menu {:?}", operator, menu_vec);
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+///
+/// 菜单的权限标识必须是已注册路由的权限，目录等不需要权限的菜单为空
+///
+fn check_permission(routes: &RouteRegistry, permission: &str) -> BuboResult<()> {
+    if permission.is_empty() || routes.contains_permission(permission) {
+        Ok(())
+    } else {
+        Err(BuboError::business_error(BusinessErrorCode::ValidationError, i18n::t_args("menu.permission_not_found", &[("permission", &permission)])))
+    }
+}
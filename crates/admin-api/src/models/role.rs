@@ -1,16 +1,17 @@
 use std::collections::HashSet;
+use schemars::JsonSchema;
 use sea_orm::{ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter, Set, TransactionTrait};
 use serde::Deserialize;
 use tracing::info;
 use validator::Validate;
 use crate::fill_active_model;
 use super::{FillActiveModelTrait, _entities::{admin_role, admin_role_menu, prelude::{AdminRole, AdminRoleMenu}}};
-use bubo::{controllers::RemoveParams, utils::{database::{ColOrd, EntityExtension}, error::{BuboError, BuboResult, BusinessErrorCode}, serde::{to_i64, to_set_i64}, snowflake, time::now_utc_primitive}};
+use bubo::{controllers::RemoveParams, i18n, utils::{database::{ColOrd, EntityExtension, Page}, error::{BuboError, BuboResult, BusinessErrorCode}, serde::{to_i64, to_set_i64}, snowflake, time::now_utc_primitive}};
 
 
 fill_active_model!(admin_role::ActiveModel,admin_role_menu::ActiveModel);
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub(crate) struct AddRoleParams {
     pub name: String,
     pub code: String,
@@ -18,12 +19,14 @@ pub(crate) struct AddRoleParams {
     pub state: i16,
     pub remark: String,
     #[serde(deserialize_with = "to_set_i64")]
+    #[schemars(with = "HashSet<bubo::openapi::Id>")]
     pub menu_ids: HashSet<i64>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub(crate) struct EditRoleParams {
     #[serde(deserialize_with = "to_i64")]
+    #[schemars(with = "bubo::openapi::Id")]
     pub id: i64,
     pub name: String,
     pub code: String,
@@ -31,10 +34,11 @@ pub(crate) struct EditRoleParams {
     pub state: i16,
     pub remark: String,
     #[serde(deserialize_with = "to_set_i64")]
+    #[schemars(with = "HashSet<bubo::openapi::Id>")]
     pub menu_ids: HashSet<i64>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub(crate) struct RolePageParams {
     pub name: Option<String>,
     pub page: u16,
@@ -51,16 +55,14 @@ impl admin_role::Model {
         Ok(models)
     }
 
-    pub(crate) async fn page(db: &DatabaseConnection, params: RolePageParams) -> BuboResult<(Vec<Self>, u64)> {
+    pub(crate) async fn page(db: &DatabaseConnection, params: RolePageParams) -> BuboResult<Page<Self>> {
         //查询条件
         let condition = Condition::all()
         .add_option(params.name.map(|name| admin_role::Column::Name.starts_with(name.as_str())));
 
         let col_ord_vec = vec![ColOrd::new(admin_role::Column::Id, sea_orm::Order::Desc)];
 
-        let (models, num_pages) = AdminRole::fetch_page(db, params.page.into(), params.page_size.into(), condition,
-            col_ord_vec).await?;
-        Ok((models, num_pages))
+        AdminRole::fetch_page(db, params.page.into(), params.page_size.into(), condition, col_ord_vec).await
     }
 
     pub(crate) async fn add(db: &DatabaseConnection, params: AddRoleParams, operator: i64) -> BuboResult<Self> {
@@ -71,7 +73,7 @@ impl admin_role::Model {
         
         let count = AdminRole::count( &txn, condition).await?;
         if count > 0 {
-            return Err(BuboError::business_error(BusinessErrorCode::AlreadyExists, format!("Role code {} already exists", params.code)));
+            return Err(BuboError::business_error(BusinessErrorCode::AlreadyExists, i18n::t_args("role.code_exists", &[("code", &params.code)])));
         }
 
         // 创建角色model
@@ -104,7 +106,7 @@ impl admin_role::Model {
 
     pub(crate) async fn edit(db: &DatabaseConnection, params: EditRoleParams, operator: i64) -> BuboResult<Self> {
         let role = AdminRole::find_by_id(params.id).one(db).await?
-        .ok_or(BuboError::business_error(BusinessErrorCode::NotFound, "角色不存在"))?;
+        .ok_or(BuboError::business_error(BusinessErrorCode::NotFound, "role.not_found"))?;
         //判断角色编码是否唯一
         let condition = Condition::all().add(admin_role::Column::Code.eq(params.code.as_str()))
         .add(admin_role::Column::Id.ne(params.id))
@@ -127,7 +129,7 @@ impl admin_role::Model {
         
         let count = AdminRole::count(&txn, condition).await?;
         if count > 0 {
-            return Err(BuboError::business_error(BusinessErrorCode::AlreadyExists, format!("Role code {} already exists", params.code)));
+            return Err(BuboError::business_error(BusinessErrorCode::AlreadyExists, i18n::t_args("role.code_exists", &[("code", &params.code)])));
         }
 
         // 更新角色
@@ -156,7 +158,7 @@ impl admin_role::Model {
         .all(db)
         .await?;
         if admin_role_vec.is_empty() {
-            return Err(BuboError::business_error(BusinessErrorCode::NotFound, "要删除的角色不存在"));
+            return Err(BuboError::business_error(BusinessErrorCode::NotFound, "role.not_found"));
         }
         // 遍历取出角色id和名称
         let role_vec: Vec<(i64, String)> = admin_role_vec.iter().map(|x| (x.id, x.name.clone())).collect();
@@ -164,7 +166,7 @@ impl admin_role::Model {
         // 删除角色
         let result = AdminRole::delete_many().filter(expr).exec(db).await?;
         if result.rows_affected == 0 {
-            return Err(BuboError::business_error(BusinessErrorCode::NotFound, "要删除的角色不存在"));
+            return Err(BuboError::business_error(BusinessErrorCode::NotFound, "role.not_found"));
         }
         info!("operator:{}, delete role {:?}", operator, role_vec);
         Ok(())
@@ -1,8 +1,9 @@
 use std::collections::HashSet;
 
 use argon2::{password_hash::{rand_core::OsRng, SaltString}, Argon2, PasswordHasher};
-use bubo::utils::{database::ColOrd, error::{BuboError, BuboResult, BusinessErrorCode}, snowflake, time::now_utc_primitive};
+use bubo::{i18n, utils::{database::{ColOrd, Page}, sha256_hash, error::{BuboError, BuboResult, BusinessErrorCode}, snowflake, time::now_utc_primitive}};
 use num_enum::{IntoPrimitive, TryFromPrimitive};
+use schemars::JsonSchema;
 use sea_orm::{ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter, Set, TransactionTrait};
 use serde::Deserialize;
 use validator::Validate;
@@ -16,7 +17,7 @@ use bubo::utils::{serde::{to_i64, to_set_i64}, database::EntityExtension};
 
 fill_active_model!(admin_user::ActiveModel,admin_user_role::ActiveModel);
 
-#[derive(Debug, Deserialize, Default, Validate)]
+#[derive(Debug, Deserialize, Default, Validate, JsonSchema)]
 pub(crate) struct AddUserParams {
     #[validate(length(min = 3, max = 20))]
     username: String,
@@ -32,13 +33,19 @@ pub(crate) struct AddUserParams {
     password: String,
     #[validate(length(max = 100))]
     remark: String,
+    // 语言偏好，例如 en-US，为空时按 Accept-Language 选择
+    #[serde(default)]
+    #[validate(length(max = 20))]
+    locale: Option<String>,
     #[serde(deserialize_with = "to_set_i64")]
+    #[schemars(with = "HashSet<bubo::openapi::Id>")]
     role_ids: HashSet<i64>,
 }
 
-#[derive(Debug, Deserialize, Default, Validate)]
+#[derive(Debug, Deserialize, Default, Validate, JsonSchema)]
 pub(crate) struct EditUserParams {
     #[serde(deserialize_with = "to_i64")]
+    #[schemars(with = "bubo::openapi::Id")]
     pub id: i64,
     #[validate(length(min = 1, max = 20))]
     pub nick_name: String,
@@ -50,11 +57,16 @@ pub(crate) struct EditUserParams {
     pub gender: i16,
     #[validate(length(max = 100))]
     pub remark: String,
+    // 语言偏好，例如 en-US，不传时保留原来的设置，空字符串时清除，按 Accept-Language 选择
+    #[serde(default)]
+    #[validate(length(max = 20))]
+    pub locale: Option<String>,
     #[serde(deserialize_with = "to_set_i64")]
+    #[schemars(with = "HashSet<bubo::openapi::Id>")]
     pub role_ids: HashSet<i64>,
 }
 
-#[derive(Debug, Deserialize, Validate)]
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
 pub(crate) struct UserPageParams {
     username: Option<String>,
     #[validate(range(min=1))]
@@ -77,7 +89,7 @@ impl admin_user::Model {
         let txn = db.begin().await?;
         let count = AdminUser::count(&txn, condition).await?;
         if count > 0 {
-            return Err(BuboError::business_error(BusinessErrorCode::AlreadyExists, format!("User username {}", params.username)));
+            return Err(BuboError::business_error(BusinessErrorCode::AlreadyExists, i18n::t_args("user.username_exists", &[("username", &params.username)])));
         }
 
         let salt = SaltString::generate(&mut OsRng);
@@ -96,6 +108,7 @@ impl admin_user::Model {
             is_admin: Set(false),
             is_deleted: Set(false),
             remark: Set(params.remark.clone()),
+            locale: Set(params.locale.clone().filter(|locale| !locale.is_empty())),
             ..Default::default()
         };
         active_model.fill_insert(Some(operator));
@@ -113,6 +126,34 @@ impl admin_user::Model {
         Ok(model)
     }
 
+    ///
+    /// 命令行创建管理员，password 为明文，与前端一致先做 sha256 再保存 argon2 哈希
+    ///
+    pub(crate) async fn create_admin(db: &DatabaseConnection, username: &str, password: &str) -> BuboResult<Self> {
+        let condition = Condition::all().add(admin_user::Column::Username.eq(username));
+        if AdminUser::count(db, condition).await? > 0 {
+            return Err(BuboError::business_error(BusinessErrorCode::AlreadyExists, i18n::t_args("user.username_exists", &[("username", &username)])));
+        }
+
+        let salt = SaltString::generate(&mut OsRng);
+        let password_hash = Argon2::default().hash_password(sha256_hash(password).as_bytes(), &salt)?.to_string();
+        let mut active_model = admin_user::ActiveModel {
+            username: Set(username.to_owned()),
+            nick_name: Set(username.to_owned()),
+            password: Set(password_hash),
+            email: Set(String::new()),
+            phone_number: Set(String::new()),
+            gender: Set(0),
+            state: Set(AdminUserState::Normal as i16),
+            is_admin: Set(true),
+            is_deleted: Set(false),
+            remark: Set(String::new()),
+            ..Default::default()
+        };
+        active_model.fill_insert(None);
+        Ok(active_model.insert(db).await?)
+    }
+
     ///
     /// 编辑后台用户
     /// 
@@ -121,7 +162,7 @@ impl admin_user::Model {
         // 开始事务
         let txn = db.begin().await?;
         let user = AdminUser::find_by_id(params.id).one(&txn).await?
-            .ok_or(BuboError::business_error(BusinessErrorCode::NotFound, "用户不存在"))?;
+            .ok_or(BuboError::business_error(BusinessErrorCode::NotFound, "user.not_found"))?;
 
         let mut active_model : admin_user::ActiveModel = user.into();
         active_model.nick_name = Set(params.nick_name.clone());
@@ -129,6 +170,9 @@ impl admin_user::Model {
         active_model.phone_number = Set(params.phone_number.clone());
         active_model.gender = Set(params.gender);
         active_model.remark = Set(params.remark.clone());
+        if let Some(locale) = &params.locale {
+            active_model.locale = Set(Some(locale.clone()).filter(|locale| !locale.is_empty()));
+        }
         active_model.fill_update(Some(operator));
 
         // 创建用户角色model
@@ -151,18 +195,14 @@ impl admin_user::Model {
     ///
     /// 后台用户分页
     /// 
-    pub(crate) async fn page<'a, C: ConnectionTrait>(db: &'a C, params: UserPageParams) -> BuboResult<(Vec<admin_user::Model>, u64)> {
+    pub(crate) async fn page<'a, C: ConnectionTrait>(db: &'a C, params: UserPageParams) -> BuboResult<Page<admin_user::Model>> {
         params.validate()?;
         //查询条件
         let condition = Condition::all()
             .add_option(params.username.map(|username| admin_user::Column::Username.starts_with(username.as_str())))
             .add(admin_user::Column::IsAdmin.eq(false));
         let col_ord_vec = vec![ColOrd::new(admin_user::Column::Id, sea_orm::Order::Desc)];
-        let (models, num_pages) = AdminUser::fetch_page(db, params.page.into(), params.page_size.into(), condition, 
-        col_ord_vec).await?;
-        
-
-        Ok((models, num_pages))
+        AdminUser::fetch_page(db, params.page.into(), params.page_size.into(), condition, col_ord_vec).await
     }
 }
 
@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use serde::Serialize;
 use serde_with::{serde_as, DisplayFromStr};
 use time::OffsetDateTime;
@@ -5,9 +6,10 @@ use time::OffsetDateTime;
 use crate::models::_entities::admin_menu;
 
 #[serde_as]
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub(crate) struct MenuResponse {
     #[serde_as(as = "DisplayFromStr")]
+    #[schemars(with = "bubo::openapi::Id")]
     pub id: i64,
     pub name: String,
     pub parent_id: i64,
@@ -21,6 +23,7 @@ pub(crate) struct MenuResponse {
     pub display_order: i16,
     pub remark: String,
     #[serde(with = "time::serde::rfc3339")]
+    #[schemars(with = "bubo::openapi::DateTime")]
     pub created_at: OffsetDateTime,
 }
 
@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use serde::Serialize;
 use serde_with::{serde_as, DisplayFromStr};
 use time::OffsetDateTime;
@@ -5,9 +6,10 @@ use time::OffsetDateTime;
 use crate::models::_entities::admin_user;
 
 #[serde_as]
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub(crate) struct AdminUserResponse {
     #[serde_as(as = "DisplayFromStr")]
+    #[schemars(with = "bubo::openapi::Id")]
     pub id: i64,
     pub username: String,
     pub nick_name: String,
@@ -16,7 +18,9 @@ pub(crate) struct AdminUserResponse {
     pub gender: i16,
     pub state: i16,
     pub remark: String,
+    pub locale: Option<String>,
     #[serde(with = "time::serde::rfc3339")]
+    #[schemars(with = "bubo::openapi::DateTime")]
     pub created_at: OffsetDateTime,
 }
 
@@ -31,6 +35,7 @@ impl AdminUserResponse {
             gender: model.gender, 
             state: model.state, 
             remark: model.remark, 
+            locale: model.locale,
             created_at: model.created_at.assume_utc(),
         }
     }
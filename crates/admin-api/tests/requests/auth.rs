@@ -0,0 +1,127 @@
+use admin_api::App;
+use admin_migration::Migrator;
+use axum::{body::Body, http::{header::{ACCEPT_LANGUAGE, AUTHORIZATION, CONTENT_LANGUAGE, CONTENT_TYPE}, Request, StatusCode}};
+use bubo::{config::ErrorMode, testing::{self, TestApp}, utils::{error::BusinessErrorCode, sha256_hash}};
+use serde_json::{json, Value};
+
+#[tokio::test]
+async fn test_login() {
+    let mut app = TestApp::new::<App, Migrator>().await;
+    // 迁移中创建的管理员
+    let response = app.post_json("/admin/auth/login/account", &json!({"username": "admin", "password": sha256_hash("123456")})).await;
+    let body: Value = response.assert_ok().json();
+    assert_eq!(body["token_type"], "Bearer");
+
+    app.post_json("/admin/auth/login/account", &json!({"username": "admin", "password": sha256_hash("654321")}))
+        .await
+        .assert_error_code(BusinessErrorCode::UserOrPasswordNotMatch);
+
+    app.login_as(testing::auth_user(1, true, &[])).await;
+    let body: Value = app.get("/admin/auth/user-info").await.assert_ok().json();
+    assert_eq!(body["data"]["id"], "1");
+
+    // 退出后 token 失效
+    app.post_json("/admin/auth/logout", &json!({})).await.assert_ok();
+    app.get("/admin/auth/user-info").await.assert_error_code(BusinessErrorCode::Unauthorized);
+}
+
+#[tokio::test]
+async fn test_permission() {
+    let mut app = TestApp::new::<App, Migrator>().await;
+    app.login_as(testing::auth_user(2, false, &["system:role:list"])).await;
+    app.get("/admin/system/role/list").await.assert_ok();
+    app.get("/admin/system/role/page?page=1&page_size=10").await.assert_error_code(BusinessErrorCode::Forbidden);
+
+    app.login_as(testing::auth_user(1, true, &[])).await;
+    app.get("/admin/system/role/page?page=1&page_size=10").await.assert_ok();
+}
+
+#[tokio::test]
+async fn test_problem_json() {
+    let mut config = testing::test_config();
+    config.server.error_mode = ErrorMode::Problem;
+    let mut app = TestApp::with_config::<App, Migrator>(config).await;
+    app.login_as(testing::auth_user(2, false, &[])).await;
+
+    let response = app.get("/admin/system/role/page?page=1&page_size=10").await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    assert_eq!(response.headers[CONTENT_TYPE], "application/problem+json");
+    response.assert_error_code(BusinessErrorCode::Forbidden);
+    let body: Value = response.json();
+    assert_eq!(body["status"], 403);
+    assert_eq!(body["instance"], "/admin/system/role/page");
+    assert_eq!(body["request_id"].as_str(), response.headers["x-request-id"].to_str().ok());
+
+    app.logout();
+    assert_eq!(app.get("/admin/auth/user-info").await.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(app.get("/admin/not-found").await.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_i18n() {
+    let mut app = TestApp::new::<App, Migrator>().await;
+    let login = |body: Value| Request::post("/admin/auth/login/account")
+        .header(CONTENT_TYPE, "application/json")
+        .header(ACCEPT_LANGUAGE, "en-US,en;q=0.9,zh-CN;q=0.8")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+
+    let response = app.request(login(json!({"username": "admin", "password": sha256_hash("654321")}))).await;
+    assert_eq!(response.headers[CONTENT_LANGUAGE], "en-US");
+    let body: Value = response.assert_error_code(BusinessErrorCode::UserOrPasswordNotMatch).json();
+    assert_eq!(body["error_message"], "Incorrect username or password");
+
+    let response = app.request(login(json!({"username": "ad", "password": sha256_hash("123456")}))).await;
+    let body: Value = response.assert_error_code(BusinessErrorCode::ValidationError).json();
+    assert_eq!(body["error_message"], "Username length must be between 3 and 20");
+
+    // 没有 Accept-Language 时使用默认语言
+    let body: Value = app.post_json("/admin/auth/login/account", &json!({"username": "ad", "password": sha256_hash("123456")})).await.json();
+    assert_eq!(body["error_message"], "用户名 长度必须在 3 到 20 之间");
+
+    // 用户设置的语言优先于 Accept-Language
+    app.login_as(testing::auth_user(2, false, &[]).with_locale("en-US")).await;
+    let response = app.get("/admin/system/role/page?page=1&page_size=10").await;
+    assert_eq!(response.headers[CONTENT_LANGUAGE], "en-US");
+    let body: Value = response.assert_error_code(BusinessErrorCode::Forbidden).json();
+    assert_eq!(body["error_message"], "Access denied");
+}
+
+#[tokio::test]
+async fn test_login_locale() {
+    let mut app = TestApp::new::<App, Migrator>().await;
+    app.login_as(testing::auth_user(1, true, &[])).await;
+    app.post_json("/admin/system/user/add", &json!({
+        "username": "alice", "nick_name": "alice", "email": "alice@example.com", "phone_number": "13800000000",
+        "gender": 0, "password": sha256_hash("123456"), "remark": "", "locale": "en-US", "role_ids": [],
+    })).await.assert_ok();
+    app.logout();
+
+    // 登录时读取用户的语言偏好，之后的请求不需要 Accept-Language
+    let body: Value = app.post_json("/admin/auth/login/account", &json!({"username": "alice", "password": sha256_hash("123456")}))
+        .await.assert_ok().json();
+    let request = Request::get("/admin/system/role/page?page=1&page_size=10")
+        .header(AUTHORIZATION, format!("Bearer {}", body["access_token"].as_str().unwrap()))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.request(request).await;
+    assert_eq!(response.headers[CONTENT_LANGUAGE], "en-US");
+    let body: Value = response.assert_error_code(BusinessErrorCode::Forbidden).json();
+    assert_eq!(body["error_message"], "Access denied");
+
+    // 编辑时不传 locale 保留原来的设置，空字符串清除
+    app.login_as(testing::auth_user(1, true, &[])).await;
+    let page: Value = app.get("/admin/system/user/page?page=1&page_size=10").await.assert_ok().json();
+    let id = page["data"][0]["id"].as_str().unwrap().to_owned();
+    let mut user = json!({
+        "id": id, "nick_name": "alice", "email": "alice@example.com", "phone_number": "13800000000",
+        "gender": 0, "remark": "", "role_ids": [],
+    });
+    app.post_json("/admin/system/user/edit", &user).await.assert_ok();
+    let page: Value = app.get("/admin/system/user/page?page=1&page_size=10").await.json();
+    assert_eq!(page["data"][0]["locale"], "en-US");
+    user["locale"] = json!("");
+    app.post_json("/admin/system/user/edit", &user).await.assert_ok();
+    let page: Value = app.get("/admin/system/user/page?page=1&page_size=10").await.json();
+    assert_eq!(page["data"][0]["locale"], Value::Null);
+}
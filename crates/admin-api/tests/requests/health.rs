@@ -0,0 +1,32 @@
+use admin_api::App;
+use admin_migration::Migrator;
+use axum::http::StatusCode;
+use bubo::testing::{self, TestApp};
+use serde_json::Value;
+
+#[tokio::test]
+async fn test_health() {
+    let mut config = testing::test_config();
+    config.server.health_check_timeout = 1;
+    let app = TestApp::with_config::<App, Migrator>(config).await;
+
+    let response = app.get("/health/live").await;
+    assert_eq!(response.status, StatusCode::OK);
+    response.assert_ok();
+
+    // 测试中没有连接 redis
+    let response = app.get("/health/ready").await;
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+    let body: Value = response.json();
+    assert_eq!(body["shutting_down"], false);
+    assert_eq!(body["components"]["database"]["status"], true);
+    assert_eq!(body["components"]["migrations"]["pending"], 0);
+    assert_eq!(body["components"]["redis"]["status"], false);
+
+    // 摘流阶段就绪检查失败，存活检查和其他请求不受影响
+    app.state.shutdown.drain();
+    let body: Value = app.get("/health/ready").await.json();
+    assert_eq!(body["shutting_down"], true);
+    assert_eq!(app.get("/health/live").await.status, StatusCode::OK);
+    assert!(!app.state.shutdown.is_shutting_down());
+}
@@ -0,0 +1,22 @@
+use admin_api::App;
+use admin_migration::Migrator;
+use bubo::{testing::{self, TestApp}, utils::error::BusinessErrorCode};
+use serde_json::json;
+
+#[tokio::test]
+async fn test_log_level_permission() {
+    let mut app = TestApp::new::<App, Migrator>().await;
+    let route = app.state.routes.routes().iter().find(|route| route.path == "/system/log-level").unwrap();
+    assert_eq!(route.permission(), Some("system:log:level"));
+
+    app.get("/system/log-level").await.assert_error_code(BusinessErrorCode::Unauthorized);
+    app.login_as(testing::auth_user(2, false, &[])).await;
+    app.post_json("/system/log-level", &json!({"directive": "debug", "revert_after": 60})).await.assert_error_code(BusinessErrorCode::Forbidden);
+
+    // 测试中没有初始化日志
+    app.login_as(testing::auth_user(2, false, &["system:log:level"])).await;
+    app.get("/system/log-level").await.assert_error_code(BusinessErrorCode::NotFound);
+    app.post_json("/system/log-level", &json!({"directive": ""})).await.assert_error_code(BusinessErrorCode::ValidationError);
+    app.post_json("/system/log-level", &json!({"directive": "debug", "revert_after": u64::MAX})).await.assert_error_code(BusinessErrorCode::ValidationError);
+    app.post_json("/system/log-level", &json!({"directive": "debug", "revert_after": 86401})).await.assert_error_code(BusinessErrorCode::ValidationError);
+}
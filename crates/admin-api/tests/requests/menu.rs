@@ -0,0 +1,23 @@
+use admin_api::App;
+use admin_migration::Migrator;
+use bubo::{testing::{self, TestApp}, utils::error::BusinessErrorCode};
+use serde_json::{json, Value};
+
+fn menu(permission: &str) -> Value {
+    json!({
+        "name": "用户管理", "parent_id": "0", "url": "/system/user", "target": 0, "menu_type": 1,
+        "is_hidden": false, "is_refresh": false, "permission": permission, "icon": "", "display_order": 1, "remark": "",
+    })
+}
+
+#[tokio::test]
+async fn test_menu_permission() {
+    let mut app = TestApp::new::<App, Migrator>().await;
+    app.login_as(testing::auth_user(1, true, &[])).await;
+
+    app.post_json("/admin/system/menu/add", &menu("system:user:pages")).await.assert_error_code(BusinessErrorCode::ValidationError);
+    let body: Value = app.post_json("/admin/system/menu/add", &menu("system:user:page")).await.assert_ok().json();
+    assert_eq!(body["data"]["permission"], "system:user:page");
+    // 目录没有权限标识
+    app.post_json("/admin/system/menu/add", &menu("")).await.assert_ok();
+}
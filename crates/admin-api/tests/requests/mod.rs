@@ -0,0 +1,6 @@
+mod auth;
+mod health;
+mod log_level;
+mod menu;
+mod openapi;
+mod role;
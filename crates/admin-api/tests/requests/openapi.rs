@@ -0,0 +1,28 @@
+use admin_api::App;
+use admin_migration::Migrator;
+use bubo::testing::{self, TestApp};
+use serde_json::Value;
+
+#[tokio::test]
+async fn test_openapi() {
+    let mut config = testing::test_config();
+    config.openapi.enable = true;
+    let app = TestApp::with_config::<App, Migrator>(config).await;
+
+    let doc: Value = app.get("/openapi.json").await.json();
+    let page = &doc["paths"]["/admin/system/user/page"]["get"];
+    assert_eq!(page["x-permission"], "system:user:page");
+    let param = page["parameters"].as_array().unwrap().iter().find(|param| param["name"] == "page").unwrap();
+    assert_eq!(param["required"], true);
+    assert_eq!(param["schema"]["minimum"], 1.0);
+
+    let login = &doc["paths"]["/admin/auth/login/account"]["post"];
+    assert!(login.get("security").is_none());
+    let schemas = &doc["components"]["schemas"];
+    assert_eq!(schemas["LoginUserParams"]["properties"]["password"]["minLength"], 64);
+    assert_eq!(schemas["AdminUserResponse"]["properties"]["id"]["type"], "string");
+    assert_eq!(schemas["MenuResponse"]["properties"]["created_at"]["format"], "date-time");
+
+    let html = app.get("/docs").await.text();
+    assert!(html.contains("url: \"/openapi.json\""));
+}
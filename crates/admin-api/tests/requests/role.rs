@@ -0,0 +1,21 @@
+use admin_api::App;
+use admin_migration::Migrator;
+use bubo::testing::{self, TestApp};
+use serde_json::{json, Value};
+
+#[tokio::test]
+async fn test_role_page() {
+    let mut app = TestApp::new::<App, Migrator>().await;
+    app.login_as(testing::auth_user(1, true, &[])).await;
+
+    for i in 0..3 {
+        let role = json!({ "name": format!("角色{i}"), "code": format!("role{i}"), "display_order": i, "state": 1, "remark": "", "menu_ids": [] });
+        app.post_json("/admin/system/role/add", &role).await.assert_ok();
+    }
+    let body: Value = app.get("/admin/system/role/page?page=2&page_size=2").await.assert_ok().json();
+    assert_eq!(body["page"], 2);
+    assert_eq!(body["page_size"], 2);
+    assert_eq!(body["total"], 3);
+    assert_eq!(body["num_pages"], 2);
+    assert_eq!(body["data"].as_array().map(Vec::len), Some(1));
+}
@@ -0,0 +1,28 @@
+use sea_orm_migration::{prelude::*, schema::string_len_null};
+
+#[derive(DeriveMigrationName)]
+pub(crate) struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 用户的语言偏好，登录后错误消息等使用该语言
+        let table = Table::alter().table(AdminUser::Table)
+            .add_column(string_len_null(AdminUser::Locale, 20).comment("语言偏好，例如 en-US"))
+            .to_owned();
+        manager.alter_table(table).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::alter().table(AdminUser::Table)
+            .drop_column(AdminUser::Locale)
+            .to_owned();
+        manager.alter_table(table).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum AdminUser {
+    Table,
+    Locale,
+}
@@ -0,0 +1,94 @@
+use std::{num::NonZeroUsize, sync::Mutex, time::{Duration, Instant}};
+
+use async_trait::async_trait;
+use lru::LruCache;
+
+use crate::utils::error::BuboResult;
+
+use super::CacheBackend;
+
+struct Entry {
+    value: String,
+    expires_at: Option<Instant>,
+}
+
+///
+/// 进程内 LRU 缓存，超过容量时淘汰最久未使用的值
+///
+pub struct MemoryCache {
+    entries: Mutex<LruCache<String, Entry>>,
+}
+
+impl MemoryCache {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self { entries: Mutex::new(LruCache::new(capacity)) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    pub(crate) fn get_sync(&self, key: &str) -> Option<String> {
+        let mut entries = self.entries.lock().unwrap();
+        let expired = entries.get(key)?.expires_at.is_some_and(|at| at <= Instant::now());
+        if expired {
+            entries.pop(key);
+            return None;
+        }
+        entries.get(key).map(|entry| entry.value.clone())
+    }
+
+    pub(crate) fn set_sync(&self, key: &str, value: String, ttl: Option<Duration>) {
+        let expires_at = ttl.map(|ttl| Instant::now() + ttl);
+        self.entries.lock().unwrap().put(key.to_owned(), Entry { value, expires_at });
+    }
+
+    pub(crate) fn delete_sync(&self, key: &str) {
+        self.entries.lock().unwrap().pop(key);
+    }
+}
+
+#[async_trait]
+impl CacheBackend for MemoryCache {
+    async fn get(&self, key: &str) -> BuboResult<Option<String>> {
+        Ok(self.get_sync(key))
+    }
+
+    async fn set(&self, key: &str, value: String, ttl: Option<Duration>) -> BuboResult<()> {
+        self.set_sync(key, value, ttl);
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> BuboResult<()> {
+        self.delete_sync(key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_memory_cache() {
+        let cache = MemoryCache::new(2);
+        cache.set_sync("a", "1".to_owned(), None);
+        cache.set_sync("b", "2".to_owned(), None);
+        assert_eq!(cache.get_sync("a").as_deref(), Some("1"));
+        // b 最久未使用，被淘汰
+        cache.set_sync("c", "3".to_owned(), None);
+        assert_eq!(cache.get_sync("b"), None);
+        assert_eq!(cache.len(), 2);
+
+        cache.set_sync("d", "4".to_owned(), Some(Duration::ZERO));
+        assert_eq!(cache.get_sync("d"), None);
+        cache.delete_sync("a");
+        assert_eq!(cache.get_sync("a"), None);
+    }
+}
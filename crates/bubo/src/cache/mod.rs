@@ -0,0 +1,132 @@
+use std::{future::Future, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use fred::prelude::RedisPool;
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::warn;
+
+use crate::{config::{CacheBackendKind, CacheConfig, RedisConfig}, utils::error::BuboResult};
+
+pub mod memory;
+pub mod redis;
+pub mod two_tier;
+
+pub use memory::MemoryCache;
+pub use redis::RedisCache;
+pub use two_tier::TwoTierCache;
+
+///
+/// 缓存后端，值为 JSON 字符串，ttl 为 None 时不过期
+///
+#[async_trait]
+pub trait CacheBackend: Send + Sync + 'static {
+    async fn get(&self, key: &str) -> BuboResult<Option<String>>;
+
+    async fn set(&self, key: &str, value: String, ttl: Option<Duration>) -> BuboResult<()>;
+
+    async fn delete(&self, key: &str) -> BuboResult<()>;
+}
+
+///
+/// 缓存，按 JSON 序列化值
+///
+#[derive(Clone)]
+pub struct Cache {
+    backend: Arc<dyn CacheBackend>,
+    default_ttl: Option<Duration>,
+}
+
+impl Cache {
+    pub fn new(backend: Arc<dyn CacheBackend>, default_ttl: Option<Duration>) -> Self {
+        Self { backend, default_ttl }
+    }
+
+    ///
+    /// 按配置创建，two_tier 方式会订阅 redis 失效通知
+    ///
+    pub async fn from_config(app_name: &str, config: &CacheConfig, redis: &RedisPool, redis_config: &RedisConfig) -> BuboResult<Self> {
+        let prefix = format!("{}:cache", app_name);
+        let backend: Arc<dyn CacheBackend> = match config.backend {
+            CacheBackendKind::Memory => Arc::new(MemoryCache::new(config.capacity)),
+            CacheBackendKind::Redis => Arc::new(RedisCache::new(redis.clone(), prefix)),
+            CacheBackendKind::TwoTier => {
+                let local = MemoryCache::new(config.capacity);
+                let remote = RedisCache::new(redis.clone(), prefix.clone());
+                let local_ttl = Duration::from_secs(config.local_ttl);
+                Arc::new(TwoTierCache::connect(local, remote, local_ttl, &format!("{}:invalidate", prefix), redis_config).await?)
+            }
+        };
+        let default_ttl = (config.default_ttl > 0).then(|| Duration::from_secs(config.default_ttl));
+        Ok(Self::new(backend, default_ttl))
+    }
+
+    ///
+    /// 获取缓存，值无法反序列化时当作不存在
+    ///
+    pub async fn get<T: DeserializeOwned>(&self, key: &str) -> BuboResult<Option<T>> {
+        let Some(raw) = self.backend.get(key).await? else {
+            return Ok(None);
+        };
+        match serde_json::from_str(&raw) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) => {
+                warn!("cache {} deserialize error: {}", key, e);
+                Ok(None)
+            }
+        }
+    }
+
+    ///
+    /// 使用配置 cache.default_ttl 作为过期时间
+    ///
+    pub async fn set<T: Serialize + ?Sized>(&self, key: &str, value: &T) -> BuboResult<()> {
+        self.set_with_ttl(key, value, self.default_ttl).await
+    }
+
+    pub async fn set_with_ttl<T: Serialize + ?Sized>(&self, key: &str, value: &T, ttl: Option<Duration>) -> BuboResult<()> {
+        self.backend.set(key, serde_json::to_string(value)?, ttl).await
+    }
+
+    pub async fn delete(&self, key: &str) -> BuboResult<()> {
+        self.backend.delete(key).await
+    }
+
+    ///
+    /// 缓存不存在时调用 f 生成并写入缓存，ttl 为 None 时使用配置 cache.default_ttl
+    ///
+    pub async fn get_or_insert_with<T, F, Fut>(&self, key: &str, ttl: Option<Duration>, f: F) -> BuboResult<T>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = BuboResult<T>>,
+    {
+        if let Some(value) = self.get(key).await? {
+            return Ok(value);
+        }
+        let value = f().await?;
+        self.set_with_ttl(key, &value, ttl.or(self.default_ttl)).await?;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn test_get_or_insert_with() {
+        let cache = Cache::new(Arc::new(MemoryCache::new(10)), None);
+        let value: Option<i64> = cache.get_or_insert_with("user:1", None, || async { Ok(None) }).await.unwrap();
+        assert_eq!(value, None);
+        // 不存在的结果也会被缓存
+        let value: Option<i64> = cache.get_or_insert_with("user:1", None, || async { Ok(Some(1)) }).await.unwrap();
+        assert_eq!(value, None);
+
+        cache.set("user:2", &vec![1, 2]).await.unwrap();
+        assert_eq!(cache.get::<Vec<i64>>("user:2").await.unwrap(), Some(vec![1, 2]));
+        assert_eq!(cache.get::<String>("user:2").await.unwrap(), None);
+        cache.delete("user:2").await.unwrap();
+        assert_eq!(cache.get::<Vec<i64>>("user:2").await.unwrap(), None);
+    }
+}
@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use fred::{prelude::RedisPool, types::Expiration};
+
+use crate::utils::{error::BuboResult, redis};
+
+use super::CacheBackend;
+
+///
+/// Redis 缓存，多个实例共享
+///
+pub struct RedisCache {
+    redis: RedisPool,
+    prefix: String,
+}
+
+impl RedisCache {
+    ///
+    /// key 保存为 `{prefix}:{key}`
+    ///
+    pub fn new(redis: RedisPool, prefix: impl Into<String>) -> Self {
+        Self { redis, prefix: prefix.into() }
+    }
+
+    pub fn redis(&self) -> &RedisPool {
+        &self.redis
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}:{}", self.prefix, key)
+    }
+}
+
+#[async_trait]
+impl CacheBackend for RedisCache {
+    async fn get(&self, key: &str) -> BuboResult<Option<String>> {
+        redis::get_string(&self.redis, self.key(key)).await
+    }
+
+    async fn set(&self, key: &str, value: String, ttl: Option<Duration>) -> BuboResult<()> {
+        let expire = ttl.map(|ttl| Expiration::PX(ttl.as_millis().max(1) as i64));
+        redis::set_string(&self.redis, self.key(key), value, expire).await
+    }
+
+    async fn delete(&self, key: &str) -> BuboResult<()> {
+        redis::del(&self.redis, self.key(key)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::testing::MockRedis;
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn test_redis_cache() {
+        let mock = Arc::new(MockRedis::default());
+        let cache = RedisCache::new(mock.pool().await, "app:cache");
+        assert_eq!(cache.get("a").await.unwrap(), None);
+        cache.set("a", "1".to_owned(), None).await.unwrap();
+        assert_eq!(mock.string("app:cache:a").as_deref(), Some("1"));
+        assert_eq!(cache.get("a").await.unwrap().as_deref(), Some("1"));
+
+        cache.set("b", "2".to_owned(), Some(Duration::from_millis(1))).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(cache.get("b").await.unwrap(), None);
+
+        cache.delete("a").await.unwrap();
+        assert_eq!(cache.get("a").await.unwrap(), None);
+    }
+}
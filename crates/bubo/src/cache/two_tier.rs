@@ -0,0 +1,160 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use fred::{clients::SubscriberClient, prelude::{ClientLike, EventInterface, PubsubInterface}, types::{Builder, RedisConfig as FredRedisConfig}};
+use tracing::{debug, error, warn};
+
+use crate::{config::RedisConfig, utils::{error::BuboResult, prometheus}};
+
+use super::{CacheBackend, MemoryCache, RedisCache};
+
+///
+/// 两级缓存，本地 LRU 加 Redis，写入和删除时通过 Redis pub/sub 通知其他实例删除本地副本
+///
+pub struct TwoTierCache {
+    local: Arc<MemoryCache>,
+    remote: RedisCache,
+    // 本地副本最长保留时间，通知丢失时也不会长期不一致
+    local_ttl: Duration,
+    channel: String,
+    // 用于忽略自己发出的通知
+    instance_id: String,
+    // 订阅连接，和 TwoTierCache 一起释放
+    _subscriber: SubscriberClient,
+}
+
+impl TwoTierCache {
+    ///
+    /// 订阅失效通知
+    ///
+    pub async fn connect(local: MemoryCache, remote: RedisCache, local_ttl: Duration, channel: &str, redis_config: &RedisConfig) -> BuboResult<Self> {
+        let config = FredRedisConfig::from_url(&redis_config.url)?;
+        let subscriber = Builder::from_config(config)
+            .with_connection_config(|config| config.connection_timeout = Duration::from_secs(redis_config.connection_timeout))
+            .build_subscriber_client()?;
+        Self::with_subscriber(local, remote, local_ttl, channel, subscriber).await
+    }
+
+    ///
+    /// 使用已创建的订阅连接，测试时可以传入 mock 连接
+    ///
+    pub async fn with_subscriber(local: MemoryCache, remote: RedisCache, local_ttl: Duration, channel: &str, subscriber: SubscriberClient) -> BuboResult<Self> {
+        subscriber.init().await?;
+        // 断线重连后自动重新订阅
+        drop(subscriber.manage_subscriptions());
+        subscriber.subscribe(channel).await?;
+
+        let local = Arc::new(local);
+        let instance_id = uuid::Uuid::new_v4().simple().to_string();
+        let mut messages = subscriber.message_rx();
+        let (cache, own_id) = (local.clone(), instance_id.clone());
+        tokio::spawn(async move {
+            loop {
+                match messages.recv().await {
+                    Ok(message) => {
+                        if let Some(payload) = message.value.as_string() {
+                            invalidate_local(&cache, &own_id, &payload);
+                        }
+                    }
+                    // 消息积压时无法确定丢失了哪些 key，清空本地缓存
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
+                        warn!("cache invalidation lagged {} messages, clear local cache", n);
+                        cache.clear();
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+        Ok(Self { local, remote, local_ttl, channel: channel.to_owned(), instance_id, _subscriber: subscriber })
+    }
+
+    async fn invalidate_others(&self, key: &str) {
+        let payload = format!("{}:{}", self.instance_id, key);
+        if let Err(e) = self.remote.redis().next().publish::<(), _, _>(self.channel.as_str(), payload).await {
+            prometheus::record_redis_error(&e);
+            error!("cache publish invalidation error: {}", e);
+        }
+    }
+
+    fn local_ttl(&self, ttl: Option<Duration>) -> Duration {
+        ttl.map_or(self.local_ttl, |ttl| ttl.min(self.local_ttl))
+    }
+}
+
+///
+/// 处理 `{instance_id}:{key}` 格式的失效通知，忽略自己发出的通知
+///
+fn invalidate_local(cache: &MemoryCache, own_id: &str, payload: &str) {
+    if let Some((sender, key)) = payload.split_once(':') {
+        if sender != own_id {
+            debug!("cache invalidate: {}", key);
+            cache.delete_sync(key);
+        }
+    }
+}
+
+#[async_trait]
+impl CacheBackend for TwoTierCache {
+    async fn get(&self, key: &str) -> BuboResult<Option<String>> {
+        if let Some(value) = self.local.get_sync(key) {
+            return Ok(Some(value));
+        }
+        let value = self.remote.get(key).await?;
+        if let Some(value) = &value {
+            self.local.set_sync(key, value.clone(), Some(self.local_ttl));
+        }
+        Ok(value)
+    }
+
+    async fn set(&self, key: &str, value: String, ttl: Option<Duration>) -> BuboResult<()> {
+        self.remote.set(key, value.clone(), ttl).await?;
+        self.local.set_sync(key, value, Some(self.local_ttl(ttl)));
+        self.invalidate_others(key).await;
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> BuboResult<()> {
+        self.remote.delete(key).await?;
+        self.local.delete_sync(key);
+        self.invalidate_others(key).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::MockRedis;
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn test_two_tier_cache() {
+        let mock = Arc::new(MockRedis::default());
+        let subscriber = Builder::from_config(FredRedisConfig { mocks: Some(mock.clone()), ..Default::default() })
+            .build_subscriber_client()
+            .unwrap();
+        let remote = RedisCache::new(mock.pool().await, "app:cache");
+        let cache = TwoTierCache::with_subscriber(MemoryCache::new(10), remote, Duration::from_secs(60), "app:cache:invalidate", subscriber).await.unwrap();
+
+        cache.set("a", "1".to_owned(), None).await.unwrap();
+        assert_eq!(mock.string("app:cache:a").as_deref(), Some("1"));
+        assert_eq!(cache.local.get_sync("a").as_deref(), Some("1"));
+        assert_eq!(mock.published(), vec![("app:cache:invalidate".to_owned(), format!("{}:a", cache.instance_id))]);
+
+        // 本地没有时从 redis 读取并保存本地副本
+        cache.local.clear();
+        assert_eq!(cache.get("a").await.unwrap().as_deref(), Some("1"));
+        assert_eq!(cache.local.get_sync("a").as_deref(), Some("1"));
+
+        // 忽略自己的通知，其他实例的通知删除本地副本
+        invalidate_local(&cache.local, &cache.instance_id, &format!("{}:a", cache.instance_id));
+        assert_eq!(cache.local.get_sync("a").as_deref(), Some("1"));
+        invalidate_local(&cache.local, &cache.instance_id, "other:a");
+        assert_eq!(cache.local.get_sync("a"), None);
+
+        cache.delete("a").await.unwrap();
+        assert_eq!(cache.get("a").await.unwrap(), None);
+        assert_eq!(mock.published().len(), 2);
+    }
+}
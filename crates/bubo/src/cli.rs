@@ -0,0 +1,314 @@
+#![allow(clippy::print_stdout, clippy::print_stderr)]
+
+use std::{fmt::Display, path::PathBuf, process::exit};
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use clap::{Parser, Subcommand};
+use sea_orm_migration::MigratorTrait;
+use serde_json::Value;
+
+use crate::{config::{Config, Environment}, controllers::{log_level::SetLogLevelParams, routing::RouteInfo}, openapi, scheduler::Scheduler, server::{self, Hooks}, utils::{database, log::LogLevelStatus}};
+
+///
+/// 命令行参数，未指定子命令时启动服务
+///
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// 启动服务
+    Serve,
+    /// 数据库迁移
+    Migrate {
+        #[command(subcommand)]
+        command: MigrateCommand,
+    },
+    /// 打印路由及所需权限
+    Routes,
+    /// 生成 OpenAPI 文档
+    Openapi {
+        /// 输出文件，默认输出到标准输出
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// 用户管理
+    User {
+        #[command(subcommand)]
+        command: UserCommand,
+    },
+    /// 定时任务
+    Task {
+        #[command(subcommand)]
+        command: TaskCommand,
+    },
+    /// 配置
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// 查看或修改运行中服务的日志过滤指令
+    LogLevel {
+        #[command(subcommand)]
+        command: LogLevelCommand,
+        /// 服务地址，默认为 http://127.0.0.1:{server.port}
+        #[arg(long, global = true)]
+        url: Option<String>,
+        /// 有 logger.reload.permission 权限的 access token，默认读取环境变量 BUBO_TOKEN
+        #[arg(long, global = true)]
+        token: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum MigrateCommand {
+    /// 执行未执行的迁移
+    Up {
+        /// 执行的迁移数，默认全部
+        #[arg(short = 'n', long)]
+        steps: Option<u32>,
+    },
+    /// 回滚迁移
+    Down {
+        /// 回滚的迁移数
+        #[arg(short = 'n', long, default_value_t = 1)]
+        steps: u32,
+    },
+    /// 查看迁移状态
+    Status,
+    /// 删除全部表后重新执行迁移
+    Fresh {
+        /// 生产环境需要确认
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum UserCommand {
+    /// 创建管理员，未指定密码时随机生成
+    CreateAdmin {
+        #[arg(long, default_value = "admin")]
+        username: String,
+        #[arg(long)]
+        password: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TaskCommand {
+    /// 列出定时任务
+    List,
+    /// 立即执行一次定时任务
+    Run {
+        name: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// 检查配置
+    Check,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum LogLevelCommand {
+    /// 查看当前的过滤指令
+    Get,
+    /// 修改过滤指令，例如 `debug` 或 `info,bubo::http=debug`
+    Set {
+        directive: String,
+        /// 多少秒后自动恢复为配置的 level
+        #[arg(long)]
+        revert_after: Option<u64>,
+    },
+    /// 恢复为配置的 level
+    Reset,
+}
+
+///
+/// 解析命令行并执行
+///
+pub async fn main<H: Hooks, M: MigratorTrait + 'static>() {
+    let cli = Cli::parse();
+    let command = cli.command.unwrap_or(Command::Serve);
+    // 路由列表和文档在配置不完整时使用默认配置
+    if let Command::Routes = command {
+        dotenvy::dotenv().ok();
+        let config = Config::load().unwrap_or_default();
+        print_routes(server::routes::<H>(&config).registry().routes());
+        return;
+    }
+    if let Command::Openapi { output } = &command {
+        dotenvy::dotenv().ok();
+        let config = Config::load().unwrap_or_default();
+        let document = openapi::document(server::routes::<H>(&config).registry().routes(), H::app_name(), &config.openapi, config.server.error_mode);
+        let json = serde_json::to_string_pretty(&document).unwrap_or_default();
+        match output {
+            Some(path) => {
+                if let Err(e) = std::fs::write(path, json) {
+                    fail(e);
+                }
+                println!("OpenAPI 文档已写入 {}", path.display());
+            }
+            None => println!("{}", json),
+        }
+        return;
+    }
+    // 初始化环境变量
+    dotenvy::dotenv().ok();
+    // 加载配置
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => fail(e),
+    };
+
+    if let Command::Config { command: ConfigCommand::Check } = command {
+        println!("配置检查通过，当前环境: {}", config.environment);
+        return;
+    }
+
+    // 初始化日志
+    let _log_guard = match crate::utils::log::init(&config.logger, H::app_name()) {
+        Ok(guard) => guard,
+        Err(e) => fail(e),
+    };
+
+    match command {
+        Command::Serve => server::serve::<H, M>(config).await,
+        Command::Migrate { command } => migrate::<M>(config, command).await,
+        Command::User { command: UserCommand::CreateAdmin { username, password } } => {
+            // 错误消息使用默认语言
+            if let Err(e) = crate::i18n::init::<H>(&config.i18n) {
+                fail(e);
+            }
+            let db = database::init::<M>(&config.database).await;
+            let password = password.unwrap_or_else(generate_password);
+            if let Err(e) = H::create_admin(&db, &username, &password).await {
+                fail(e);
+            }
+            println!("管理员已创建\n用户名: {}\n密码: {}", username, password);
+        }
+        Command::LogLevel { command, url, token } => log_level(&config, command, url, token).await,
+        Command::Task { command } => {
+            let state = server::init_state::<H, M>(config).await;
+            let mut scheduler = Scheduler::new();
+            if let Err(e) = H::tasks(&mut scheduler, &state) {
+                fail(e);
+            }
+            match command {
+                TaskCommand::List => scheduler.names().iter().for_each(|name| println!("{}", name)),
+                TaskCommand::Run { name } => match scheduler.run_once(&name, &state).await {
+                    Ok(()) => println!("任务 {} 执行完成", name),
+                    Err(e) => fail(e),
+                },
+            }
+        }
+        Command::Config { .. } | Command::Routes | Command::Openapi { .. } => unreachable!(),
+    }
+}
+
+async fn migrate<M: MigratorTrait>(config: Config, command: MigrateCommand) {
+    let db = database::connect(&config.database).await;
+    let result = match command {
+        MigrateCommand::Up { steps } => M::up(&db, steps).await,
+        MigrateCommand::Down { steps } => M::down(&db, Some(steps)).await,
+        MigrateCommand::Status => M::get_migration_with_status(&db).await.map(|migrations| {
+            for migration in migrations {
+                println!("{:<10} {}", migration.status().to_string(), migration.name());
+            }
+        }),
+        MigrateCommand::Fresh { yes } => {
+            if config.environment == Environment::Production && !yes {
+                fail("生产环境执行 fresh 会删除全部数据，确认请添加 --yes");
+            }
+            M::fresh(&db).await
+        }
+    };
+    if let Err(e) = result {
+        fail(e);
+    }
+}
+
+///
+/// 调用运行中服务的 logger.reload.path 接口
+///
+async fn log_level(config: &Config, command: LogLevelCommand, url: Option<String>, token: Option<String>) {
+    let path = config.logger.reload.path.trim_end_matches('/');
+    if path.is_empty() {
+        fail("logger.reload.path 未设置，服务没有注册日志级别接口");
+    }
+    let Some(token) = token.or_else(|| std::env::var("BUBO_TOKEN").ok()) else {
+        fail("请用 --token 或环境变量 BUBO_TOKEN 指定 access token");
+    };
+    let base = url.unwrap_or_else(|| format!("http://127.0.0.1:{}", config.server.port));
+    let url = format!("{}{}", base.trim_end_matches('/'), path);
+    let client = reqwest::Client::new();
+    let request = match command {
+        LogLevelCommand::Get => client.get(url),
+        LogLevelCommand::Set { directive, revert_after } => client.post(url).json(&SetLogLevelParams { directive, revert_after }),
+        LogLevelCommand::Reset => client.post(format!("{url}/reset")),
+    };
+    let body: Value = match request.bearer_auth(token).send().await {
+        Ok(response) => response.json().await.unwrap_or_else(|e| fail(e)),
+        Err(e) => fail(e),
+    };
+    // problem 模式的 status 为 HTTP 状态码
+    if body["status"] != Value::Bool(true) {
+        fail(body["error_message"].as_str().or(body["detail"].as_str()).map(ToOwned::to_owned).unwrap_or_else(|| body.to_string()));
+    }
+    let status: LogLevelStatus = serde_json::from_value(body["data"].clone()).unwrap_or_else(|e| fail(e));
+    println!("过滤指令: {}", status.directive);
+    if status.overridden {
+        match status.revert_at {
+            Some(revert_at) => println!("运行时修改，{revert_at} 自动恢复"),
+            None => println!("运行时修改，不会自动恢复"),
+        }
+    }
+}
+
+fn print_routes(routes: &[RouteInfo]) {
+    let path_width = routes.iter().map(|route| route.path.len()).max().unwrap_or(0).max(4);
+    println!("{:<7} {:<path_width$} {:<10} PERMISSION", "METHOD", "PATH", "ACCESS");
+    for route in routes {
+        println!("{:<7} {:<path_width$} {:<10} {}", route.method, route.path, route.access.to_string(), route.permission().unwrap_or("-"));
+    }
+}
+
+///
+/// 随机生成 16 位密码，不含容易混淆的字符
+///
+fn generate_password() -> String {
+    const CHARSET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnpqrstuvwxyz23456789";
+    (0..16).map(|_| CHARSET[OsRng.next_u32() as usize % CHARSET.len()] as char).collect()
+}
+
+fn fail(e: impl Display) -> ! {
+    eprintln!("{}", e);
+    exit(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse() {
+        Cli::command().debug_assert();
+        let cli = Cli::parse_from(["app", "migrate", "down", "-n", "2"]);
+        assert!(matches!(cli.command, Some(Command::Migrate { command: MigrateCommand::Down { steps: 2 } })));
+        let cli = Cli::parse_from(["app", "task", "run", "clean"]);
+        assert!(matches!(cli.command, Some(Command::Task { command: TaskCommand::Run { name } }) if name == "clean"));
+        assert!(Cli::parse_from(["app"]).command.is_none());
+        let cli = Cli::parse_from(["app", "openapi", "-o", "openapi.json"]);
+        assert!(matches!(cli.command, Some(Command::Openapi { output: Some(path) }) if path == std::path::Path::new("openapi.json")));
+        assert_eq!(generate_password().len(), 16);
+    }
+}
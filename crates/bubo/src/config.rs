@@ -0,0 +1,1267 @@
+use std::{collections::HashMap, fmt, net::IpAddr, path::{Path, PathBuf}, str::FromStr};
+
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::controllers::middlewares::auth::{ACCESS_EXP, REFRESH_EXP};
+
+/// 环境变量覆盖配置项的前缀，例如 `BUBO_SERVER__PORT=8081`
+pub const ENV_PREFIX: &str = "BUBO_";
+/// 环境变量中配置层级的分隔符
+pub const ENV_SEPARATOR: &str = "__";
+
+/// 兼容旧版 .env 的环境变量与配置项的对应关系
+const LEGACY_ENV_VARS: &[(&str, &[&str])] = &[
+    ("DATABASE_URL", &["database", "url"]),
+    ("REDIS_URL", &["redis", "url"]),
+    ("REDIS_POOL_SIZE", &["redis", "pool_size"]),
+    ("JWT_SECRET_ACCESS", &["auth", "access_secret"]),
+    ("JWT_SECRET_REFRESH", &["auth", "refresh_secret"]),
+];
+
+///
+/// 运行环境
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Environment {
+    #[default]
+    Development,
+    Test,
+    Production,
+}
+
+impl Environment {
+    ///
+    /// 从 `BUBO_ENV` 读取当前运行环境，未设置时为 development
+    ///
+    pub fn from_env() -> ConfigResult<Self> {
+        match std::env::var("BUBO_ENV") {
+            Ok(v) => v.parse(),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Environment::Development => "development",
+            Environment::Test => "test",
+            Environment::Production => "production",
+        }
+    }
+}
+
+impl FromStr for Environment {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> ConfigResult<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "development" | "dev" => Ok(Environment::Development),
+            "test" => Ok(Environment::Test),
+            "production" | "prod" => Ok(Environment::Production),
+            _ => Err(ConfigError::UnknownEnvironment(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+///
+/// 应用配置
+///
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    #[serde(skip)]
+    pub environment: Environment,
+    pub server: ServerConfig,
+    pub metrics: MetricsConfig,
+    pub logger: LoggerConfig,
+    pub database: DatabaseConfig,
+    pub redis: RedisConfig,
+    pub auth: AuthConfig,
+    pub worker: WorkerConfig,
+    pub scheduler: SchedulerConfig,
+    pub mailer: MailerConfig,
+    pub storage: StorageConfig,
+    pub cache: CacheConfig,
+    pub rate_limit: RateLimitConfig,
+    pub cors: CorsConfig,
+    pub assets: AssetsConfig,
+    pub openapi: OpenApiConfig,
+    pub i18n: I18nConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    // 请求超时时间（秒）
+    pub request_timeout: u64,
+    // 最大并发请求数
+    pub concurrency_limit: usize,
+    // 监听 Unix domain socket 路径，设置后不再监听 host:port
+    pub unix_socket: Option<String>,
+    // 设置后启用 HTTPS
+    pub tls: Option<TlsConfig>,
+    // 就绪检查中每个组件的超时时间（秒）
+    pub health_check_timeout: u64,
+    // 收到停止信号后先让就绪检查返回 503，等待该时间（秒）让负载均衡摘除实例后再停止接收新连接，不计入 shutdown_timeout
+    pub drain_delay: u64,
+    // 优雅停机的最长等待时间（秒），包括处理完已有连接和 Hooks::on_shutdown
+    pub shutdown_timeout: u64,
+    // 请求体大小上限（字节），单个路由可以用 DefaultBodyLimit 覆盖
+    pub body_limit: u64,
+    // 解压 Content-Encoding 为 gzip/br/zstd/deflate 的请求体，解压后的大小同样受 body_limit 限制
+    pub request_decompression: bool,
+    // 错误响应格式
+    pub error_mode: ErrorMode,
+    pub compression: CompressionConfig,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "0.0.0.0".to_owned(),
+            port: 8080,
+            request_timeout: 60,
+            concurrency_limit: 1024,
+            unix_socket: None,
+            tls: None,
+            health_check_timeout: 3,
+            drain_delay: 5,
+            shutdown_timeout: 30,
+            body_limit: 2 * 1024 * 1024,
+            request_decompression: true,
+            error_mode: ErrorMode::Legacy,
+            compression: CompressionConfig::default(),
+        }
+    }
+}
+
+///
+/// 错误响应格式：legacy 为业务错误返回 200 和 `{"status": false, ...}`，
+/// status 使用同样的响应体但返回对应的 HTTP 状态码，problem 返回状态码和 RFC 7807 `application/problem+json`
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorMode {
+    #[default]
+    Legacy,
+    Status,
+    Problem,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompressionConfig {
+    pub enable: bool,
+    pub gzip: bool,
+    pub br: bool,
+    pub zstd: bool,
+    // 响应体小于该大小（字节）时不压缩
+    pub min_size: u16,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self { enable: true, gzip: true, br: true, zstd: true, min_size: 1024 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsConfig {
+    // PEM 格式证书链
+    pub cert: String,
+    // PEM 格式私钥
+    pub key: String,
+    // 检查证书文件变化的间隔（秒），0 表示不自动重新加载
+    pub reload_interval: u64,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self { cert: String::new(), key: String::new(), reload_interval: 60 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    pub enable: bool,
+    pub host: String,
+    pub port: u16,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self { enable: true, host: "0.0.0.0".to_owned(), port: 3001 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggerConfig {
+    // 日志目录
+    pub dir: String,
+    // 日志文件名前缀，为空时使用 app_name，文件名为 `{file_name}.log`，轮转后为 `{file_name}.{时间}.log`
+    pub file_name: String,
+    // 全局过滤指令，例如 `info,sqlx=warn`，设置了 RUST_LOG 时使用 RUST_LOG
+    pub level: String,
+    pub console: ConsoleLogConfig,
+    pub file: FileLogConfig,
+    pub otlp: OtlpConfig,
+    pub http: HttpLogConfig,
+    pub reload: LogReloadConfig,
+}
+
+impl Default for LoggerConfig {
+    fn default() -> Self {
+        Self {
+            dir: "./logs".to_owned(),
+            file_name: String::new(),
+            level: "info".to_owned(),
+            console: ConsoleLogConfig::default(),
+            file: FileLogConfig::default(),
+            otlp: OtlpConfig::default(),
+            http: HttpLogConfig::default(),
+            reload: LogReloadConfig::default(),
+        }
+    }
+}
+
+///
+/// 日志格式：full 为默认的单行格式，pretty 为多行格式，compact 为紧凑单行格式，
+/// json 每行一个 JSON 对象，包含当前 span 和 span 列表的字段（其中有请求 id `request_id`）
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Full,
+    Pretty,
+    Compact,
+    Json,
+}
+
+///
+/// 日志文件轮转方式，size 为文件超过 max_size 时轮转
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Minutely,
+    Hourly,
+    #[default]
+    Daily,
+    Never,
+    Size,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConsoleLogConfig {
+    pub enable: bool,
+    pub format: LogFormat,
+    // 该输出的过滤指令，为空时使用全局的 level
+    pub level: String,
+    pub ansi: bool,
+}
+
+impl Default for ConsoleLogConfig {
+    fn default() -> Self {
+        Self { enable: true, format: LogFormat::Full, level: String::new(), ansi: true }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileLogConfig {
+    pub enable: bool,
+    pub format: LogFormat,
+    // 该输出的过滤指令，为空时使用全局的 level
+    pub level: String,
+    pub rotation: LogRotation,
+    // rotation 为 size 时单个文件的大小上限（字节）
+    pub max_size: u64,
+    // 最多保留的日志文件数（包括当前文件），0 为不限制
+    pub max_files: usize,
+}
+
+impl Default for FileLogConfig {
+    fn default() -> Self {
+        Self { enable: true, format: LogFormat::Full, level: String::new(), rotation: LogRotation::Daily, max_size: 100 * 1024 * 1024, max_files: 0 }
+    }
+}
+
+///
+/// 通过 OTLP/HTTP 导出 span，例如导出到本地的 OpenTelemetry Collector 或 Jaeger
+///
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OtlpConfig {
+    pub enable: bool,
+    pub endpoint: String,
+    // 服务名，为空时使用 app_name
+    pub service_name: String,
+    // 没有上游 traceparent 时的采样比例，0 到 1，有上游时跟随上游的采样决定
+    pub sample_ratio: f64,
+    // 导出的过滤指令，为空时使用全局的 level，SeaORM 查询的 span 总是导出
+    pub level: String,
+    // 导出超时（秒）
+    pub timeout: u64,
+}
+
+impl Default for OtlpConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            endpoint: "http://localhost:4318/v1/traces".to_owned(),
+            service_name: String::new(),
+            sample_ratio: 1.0,
+            level: String::new(),
+            timeout: 10,
+        }
+    }
+}
+
+///
+/// 在 debug 级别记录请求和响应，日志 target 为 `bubo::http`，
+/// 敏感的请求头和 JSON 字段、query 参数替换为 `***`
+///
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpLogConfig {
+    pub enable: bool,
+    // 记录请求头和响应头
+    pub headers: bool,
+    // 记录 JSON 和表单的请求体、响应体，单个路由可以用 routing::Endpoint::skip_body_log 关闭
+    pub body: bool,
+    // 记录的请求体、响应体的最大长度（字节），超出部分截断
+    pub max_body_size: usize,
+    // 字段名包含其中任意一项时脱敏，不区分大小写
+    pub redact_fields: Vec<String>,
+    // 脱敏的请求头和响应头，不区分大小写
+    pub redact_headers: Vec<String>,
+}
+
+impl Default for HttpLogConfig {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            headers: true,
+            body: true,
+            max_body_size: 4096,
+            redact_fields: ["password", "token", "secret", "phone_number"].map(str::to_owned).to_vec(),
+            redact_headers: ["authorization", "proxy-authorization", "cookie", "set-cookie"].map(str::to_owned).to_vec(),
+        }
+    }
+}
+
+///
+/// 运行时查看和修改日志过滤指令的接口，`GET {path}` 查看，`POST {path}` 修改，`POST {path}/reset` 恢复为配置的 level
+///
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogReloadConfig {
+    // 接口路径，为空时不注册
+    pub path: String,
+    // 访问接口需要的权限，管理员不检查权限
+    pub permission: String,
+}
+
+impl Default for LogReloadConfig {
+    fn default() -> Self {
+        Self { path: "/system/log-level".to_owned(), permission: "system:log:level".to_owned() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    // 连接超时时间（秒）
+    pub connect_timeout: u64,
+    // 空闲连接回收时间（秒）
+    pub idle_timeout: u64,
+    // 启动时自动执行迁移
+    pub auto_migrate: bool,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self { url: String::new(), max_connections: 10, min_connections: 1, connect_timeout: 10, idle_timeout: 600, auto_migrate: true }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedisConfig {
+    pub url: String,
+    pub pool_size: usize,
+    // 连接超时时间（秒）
+    pub connection_timeout: u64,
+}
+
+impl Default for RedisConfig {
+    fn default() -> Self {
+        Self { url: String::new(), pool_size: 8, connection_timeout: 10 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    pub access_secret: String,
+    pub refresh_secret: String,
+    // access token 有效期（秒）
+    pub access_expire: i64,
+    // refresh token 有效期（秒）
+    pub refresh_expire: i64,
+    // 刷新后旧 access token 仍可使用的时间（秒）
+    pub access_grace_period: i64,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            access_secret: String::new(),
+            refresh_secret: String::new(),
+            access_expire: ACCESS_EXP,
+            refresh_expire: REFRESH_EXP,
+            access_grace_period: 300,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkerConfig {
+    pub enable: bool,
+    // 同时执行的任务数
+    pub concurrency: usize,
+    // 队列为空时的轮询间隔（毫秒）
+    pub poll_interval: u64,
+    // 失败后最大重试次数
+    pub max_retries: u32,
+    // 重试间隔基数（秒），按指数退避
+    pub retry_base_delay: u64,
+    // 死信队列最大长度
+    pub dead_max_len: u64,
+    // 执行中的任务所在实例超过该时间（秒）没有心跳时，任务放回队列由其他实例执行
+    pub stale_timeout: u64,
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        Self { enable: true, concurrency: 4, poll_interval: 500, max_retries: 5, retry_base_delay: 5, dead_max_len: 1000, stale_timeout: 60 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SchedulerConfig {
+    pub enable: bool,
+    // 每次触发的租约有效期（秒），保证集群中同一时刻只有一个实例执行
+    pub lease_ttl: u64,
+    // 按任务名覆盖配置
+    pub tasks: HashMap<String, TaskConfig>,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self { enable: true, lease_ttl: 60, tasks: HashMap::new() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TaskConfig {
+    pub enable: bool,
+    // 覆盖任务自带的 cron 表达式
+    pub cron: Option<String>,
+}
+
+impl Default for TaskConfig {
+    fn default() -> Self {
+        Self { enable: true, cron: None }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MailerConfig {
+    pub enable: bool,
+    pub transport: MailTransport,
+    // 默认发件人，例如 `Bubo <noreply@example.com>`
+    pub from: String,
+    // 邮件模板目录，模板命名为 `{name}.subject.txt`、`{name}.txt`、`{name}.html`
+    pub template_dir: String,
+    // file 方式保存邮件的目录
+    pub file_dir: String,
+    pub smtp: SmtpConfig,
+}
+
+impl Default for MailerConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            transport: MailTransport::Stub,
+            from: String::new(),
+            template_dir: "templates/mail".to_owned(),
+            file_dir: "./mails".to_owned(),
+            smtp: SmtpConfig::default(),
+        }
+    }
+}
+
+///
+/// 邮件发送方式，file 写入 .eml 文件，stub 只保存在内存中用于测试
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MailTransport {
+    Smtp,
+    File,
+    Stub,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SmtpConfig {
+    pub host: String,
+    // 不设置时按 security 选择：tls 为 465，starttls 为 587，none 为 25
+    pub port: Option<u16>,
+    pub username: String,
+    pub password: String,
+    pub security: SmtpSecurity,
+    // 连接超时（秒）
+    pub timeout: u64,
+}
+
+impl Default for SmtpConfig {
+    fn default() -> Self {
+        Self { host: String::new(), port: None, username: String::new(), password: String::new(), security: SmtpSecurity::StartTls, timeout: 10 }
+    }
+}
+
+impl SmtpConfig {
+    ///
+    /// 实际连接的端口
+    ///
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or(match self.security {
+            SmtpSecurity::Tls => 465,
+            SmtpSecurity::StartTls => 587,
+            SmtpSecurity::None => 25,
+        })
+    }
+}
+
+///
+/// SMTP 加密方式，tls 为直接 TLS 连接（通常端口 465），starttls 为连接后升级（通常端口 587）
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    None,
+    StartTls,
+    Tls,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    pub enable: bool,
+    pub backend: StorageBackend,
+    // local 方式的存储目录
+    pub root: String,
+    pub s3: S3Config,
+    // 下载地址前缀
+    pub url_prefix: String,
+    // 下载地址签名密钥
+    pub url_secret: String,
+    // 下载地址有效期（秒）
+    pub url_expire: u64,
+    // 单个上传文件大小上限（字节）
+    pub max_upload_size: u64,
+    // 允许上传的类型，支持 `image/*`，为空时不限制
+    pub allowed_types: Vec<String>,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            backend: StorageBackend::Local,
+            root: "./storage".to_owned(),
+            s3: S3Config::default(),
+            url_prefix: "/files".to_owned(),
+            url_secret: String::new(),
+            url_expire: 3600,
+            max_upload_size: 10 * 1024 * 1024,
+            allowed_types: Vec::new(),
+        }
+    }
+}
+
+///
+/// 文件存储方式，memory 只保存在内存中用于测试
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    Local,
+    Memory,
+    S3,
+}
+
+///
+/// S3 兼容存储，例如 AWS S3、MinIO
+///
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct S3Config {
+    // 例如 `https://s3.us-east-1.amazonaws.com`、`http://127.0.0.1:9000`
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    pub access_key: String,
+    pub secret_key: String,
+    // 使用 `{endpoint}/{bucket}/{key}` 形式的地址，MinIO 需要开启
+    pub path_style: bool,
+    // 请求超时（秒）
+    pub timeout: u64,
+}
+
+impl Default for S3Config {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            region: "us-east-1".to_owned(),
+            bucket: String::new(),
+            access_key: String::new(),
+            secret_key: String::new(),
+            path_style: true,
+            timeout: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub backend: CacheBackendKind,
+    // 本地缓存最多保存的条数
+    pub capacity: usize,
+    // 默认过期时间（秒），0 为不过期
+    pub default_ttl: u64,
+    // two_tier 方式本地副本最长保留时间（秒）
+    pub local_ttl: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self { backend: CacheBackendKind::Redis, capacity: 10_000, default_ttl: 300, local_ttl: 60 }
+    }
+}
+
+///
+/// 缓存方式，memory 为进程内 LRU，two_tier 为本地 LRU 加 Redis
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheBackendKind {
+    Memory,
+    Redis,
+    TwoTier,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub enable: bool,
+    // 是否信任 X-Forwarded-For，仅在服务部署在反向代理之后时开启
+    pub trust_proxy: bool,
+    // 可信代理的地址或网段，连接来自这些地址时才使用 X-Forwarded-For，
+    // 从右往左跳过可信代理后的第一个地址作为客户端 IP。监听 unix_socket 时连接只能来自本机，总是视为可信
+    pub trusted_proxies: Vec<String>,
+    // 应用到全部路由的分组名称，为空时不启用全局限流
+    pub global: Option<String>,
+    // 按名称配置的限流分组，路由通过 RateLimiter::new(state, 名称) 使用
+    pub groups: HashMap<String, RateLimitRule>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            trust_proxy: false,
+            trusted_proxies: vec!["127.0.0.1".to_owned(), "::1".to_owned()],
+            global: None,
+            groups: HashMap::new(),
+        }
+    }
+}
+
+impl RateLimitConfig {
+    ///
+    /// 解析 trusted_proxies，单个地址视为只包含该地址的网段，无法解析的忽略（validate 时报错）
+    ///
+    pub fn trusted_proxy_nets(&self) -> Vec<IpNet> {
+        self.trusted_proxies.iter().filter_map(|proxy| parse_ip_net(proxy)).collect()
+    }
+}
+
+fn parse_ip_net(s: &str) -> Option<IpNet> {
+    s.parse::<IpNet>().ok().or_else(|| s.parse::<IpAddr>().ok().map(IpNet::from))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitRule {
+    pub key: RateLimitKey,
+    // 时间窗口内允许的请求数
+    pub limit: u64,
+    // 滑动窗口长度（秒）
+    pub window: u64,
+}
+
+impl Default for RateLimitRule {
+    fn default() -> Self {
+        Self { key: RateLimitKey::Ip, limit: 100, window: 1 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AssetsConfig {
+    // 提供前端单页应用，Hooks::embedded_assets 返回资源时使用编译进二进制的资源，否则读取 dir
+    pub enable: bool,
+    pub dir: String,
+    // 前端路由所在的路径前缀
+    pub prefix: String,
+    pub index: String,
+    // 接口路径前缀，找不到路由时返回 JSON 错误而不是 index.html
+    pub api_prefixes: Vec<String>,
+}
+
+impl Default for AssetsConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            dir: "./dist".to_owned(),
+            prefix: "/".to_owned(),
+            index: "index.html".to_owned(),
+            api_prefixes: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OpenApiConfig {
+    // 提供 OpenAPI 文档和文档页面
+    pub enable: bool,
+    // OpenAPI JSON 路径
+    pub path: String,
+    // 文档页面路径，为空时不提供页面
+    pub viewer_path: String,
+    // Swagger UI 静态资源地址，内网环境可以改为自建地址
+    pub viewer_cdn: String,
+    // 为空时使用应用名
+    pub title: String,
+    pub version: String,
+}
+
+impl Default for OpenApiConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            path: "/openapi.json".to_owned(),
+            viewer_path: "/docs".to_owned(),
+            viewer_cdn: "https://cdn.jsdelivr.net/npm/swagger-ui-dist@5".to_owned(),
+            title: String::new(),
+            version: "1.0.0".to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct I18nConfig {
+    // 请求没有 Accept-Language 或不支持其中的语言时使用
+    pub default_locale: String,
+    // 应用的消息目录，其中的 `{locale}.toml` 覆盖内置和 Hooks::i18n 添加的消息，为空时不加载
+    pub dir: String,
+}
+
+impl Default for I18nConfig {
+    fn default() -> Self {
+        Self { default_locale: crate::i18n::ZH_CN.to_owned(), dir: String::new() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CorsConfig {
+    // 允许的来源，支持精确匹配 `https://example.com` 和子域名通配 `https://*.example.com`，`*` 为全部
+    pub allow_origins: Vec<String>,
+    // `*` 为全部
+    pub allow_methods: Vec<String>,
+    // `*` 为全部
+    pub allow_headers: Vec<String>,
+    // 允许前端读取的响应头
+    pub expose_headers: Vec<String>,
+    // 允许携带 cookie，开启时以上各项不能使用 `*`
+    pub allow_credentials: bool,
+    // 预检结果缓存时间（秒）
+    pub max_age: Option<u64>,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        let any = vec!["*".to_owned()];
+        Self {
+            allow_origins: any.clone(),
+            allow_methods: any.clone(),
+            allow_headers: any,
+            expose_headers: Vec::new(),
+            allow_credentials: false,
+            max_age: None,
+        }
+    }
+}
+
+///
+/// 限流维度，user 在未登录时按 ip 计数
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitKey {
+    Ip,
+    User,
+    Route,
+}
+
+impl Config {
+    ///
+    /// 按当前环境加载配置：默认值 <- 配置文件 <- 环境变量
+    ///
+    pub fn load() -> ConfigResult<Self> {
+        let dir = std::env::var("BUBO_CONFIG_DIR").unwrap_or_else(|_| "config".to_owned());
+        Self::load_from(dir, Environment::from_env()?)
+    }
+
+    ///
+    /// 从指定目录加载 `{environment}.toml` / `{environment}.yaml`，文件不存在时只使用默认值和环境变量
+    ///
+    pub fn load_from(dir: impl AsRef<Path>, environment: Environment) -> ConfigResult<Self> {
+        let mut value = serde_json::to_value(Config::default())?;
+        if let Some(path) = find_config_file(dir.as_ref(), environment) {
+            merge(&mut value, read_config_file(&path)?);
+        }
+        overlay_env(&mut value, std::env::vars());
+
+        let mut config: Config = serde_json::from_value(value)?;
+        config.environment = environment;
+        config.validate()?;
+        Ok(config)
+    }
+
+    ///
+    /// 校验配置，一次性返回全部问题
+    ///
+    pub fn validate(&self) -> ConfigResult<()> {
+        let mut errors = Vec::new();
+        if self.server.port == 0 {
+            errors.push("server.port 不能为 0".to_owned());
+        }
+        if self.server.request_timeout == 0 {
+            errors.push("server.request_timeout 必须大于 0".to_owned());
+        }
+        if self.server.concurrency_limit == 0 {
+            errors.push("server.concurrency_limit 必须大于 0".to_owned());
+        }
+        if self.server.shutdown_timeout == 0 {
+            errors.push("server.shutdown_timeout 必须大于 0".to_owned());
+        }
+        if self.server.body_limit == 0 {
+            errors.push("server.body_limit 必须大于 0".to_owned());
+        }
+        let compression = &self.server.compression;
+        if compression.enable && !(compression.gzip || compression.br || compression.zstd) {
+            errors.push("server.compression 开启时至少需要一种压缩算法".to_owned());
+        }
+        if self.server.health_check_timeout == 0 {
+            errors.push("server.health_check_timeout 必须大于 0".to_owned());
+        }
+        if let Some(tls) = &self.server.tls {
+            for (name, path) in [("server.tls.cert", &tls.cert), ("server.tls.key", &tls.key)] {
+                if path.is_empty() {
+                    errors.push(format!("{name} 未设置"));
+                } else if !Path::new(path).is_file() {
+                    errors.push(format!("{name} 文件不存在: {path}"));
+                }
+            }
+        }
+        if self.server.unix_socket.as_ref().is_some_and(|path| path.is_empty()) {
+            errors.push("server.unix_socket 不能为空".to_owned());
+        }
+        if self.metrics.enable && self.metrics.port == 0 {
+            errors.push("metrics.port 不能为 0".to_owned());
+        }
+        if self.logger.dir.is_empty() {
+            errors.push("logger.dir 不能为空".to_owned());
+        }
+        let levels = [
+            ("logger.level", &self.logger.level),
+            ("logger.console.level", &self.logger.console.level),
+            ("logger.file.level", &self.logger.file.level),
+            ("logger.otlp.level", &self.logger.otlp.level),
+        ];
+        for (name, level) in levels {
+            if let Err(e) = tracing_subscriber::EnvFilter::try_new(level) {
+                errors.push(format!("{name} 格式错误: {e}"));
+            }
+        }
+        if self.logger.file.rotation == LogRotation::Size && self.logger.file.max_size == 0 {
+            errors.push("logger.file.max_size 必须大于 0".to_owned());
+        }
+        for header in &self.logger.http.redact_headers {
+            if axum::http::HeaderName::try_from(header.as_str()).is_err() {
+                errors.push(format!("logger.http.redact_headers 中的请求头格式错误: {header}"));
+            }
+        }
+        if !self.logger.reload.path.is_empty() {
+            if !self.logger.reload.path.starts_with('/') {
+                errors.push("logger.reload.path 必须以 / 开头".to_owned());
+            }
+            if self.logger.reload.permission.is_empty() {
+                errors.push("logger.reload.permission 不能为空".to_owned());
+            }
+        }
+        if self.logger.otlp.enable {
+            if self.logger.otlp.endpoint.is_empty() {
+                errors.push("logger.otlp.endpoint 未设置".to_owned());
+            }
+            if !(0.0..=1.0).contains(&self.logger.otlp.sample_ratio) {
+                errors.push("logger.otlp.sample_ratio 必须在 0 到 1 之间".to_owned());
+            }
+        }
+        if self.database.url.is_empty() {
+            errors.push("database.url 未设置（或设置 DATABASE_URL）".to_owned());
+        }
+        if self.database.max_connections == 0 {
+            errors.push("database.max_connections 必须大于 0".to_owned());
+        }
+        if self.database.min_connections > self.database.max_connections {
+            errors.push("database.min_connections 不能大于 database.max_connections".to_owned());
+        }
+        if self.redis.url.is_empty() {
+            errors.push("redis.url 未设置（或设置 REDIS_URL）".to_owned());
+        }
+        if self.redis.pool_size == 0 {
+            errors.push("redis.pool_size 必须大于 0".to_owned());
+        }
+        if self.auth.access_secret.is_empty() {
+            errors.push("auth.access_secret 未设置（或设置 JWT_SECRET_ACCESS）".to_owned());
+        }
+        if self.auth.refresh_secret.is_empty() {
+            errors.push("auth.refresh_secret 未设置（或设置 JWT_SECRET_REFRESH）".to_owned());
+        }
+        if !self.auth.access_secret.is_empty() && self.auth.access_secret == self.auth.refresh_secret {
+            errors.push("auth.access_secret 与 auth.refresh_secret 不能相同".to_owned());
+        }
+        if self.auth.access_expire <= 0 || self.auth.refresh_expire <= 0 {
+            errors.push("auth.access_expire / auth.refresh_expire 必须大于 0".to_owned());
+        }
+        if self.worker.enable && self.worker.concurrency == 0 {
+            errors.push("worker.concurrency 必须大于 0".to_owned());
+        }
+        if self.worker.poll_interval == 0 {
+            errors.push("worker.poll_interval 必须大于 0".to_owned());
+        }
+        if self.worker.dead_max_len == 0 {
+            errors.push("worker.dead_max_len 必须大于 0".to_owned());
+        }
+        if self.worker.stale_timeout < 3 {
+            errors.push("worker.stale_timeout 不能小于 3".to_owned());
+        }
+        if self.scheduler.lease_ttl == 0 {
+            errors.push("scheduler.lease_ttl 必须大于 0".to_owned());
+        }
+        for (name, task) in &self.scheduler.tasks {
+            if let Some(Err(e)) = task.cron.as_deref().map(crate::scheduler::parse_cron) {
+                errors.push(format!("scheduler.tasks.{name}.cron {e}"));
+            }
+        }
+        if self.mailer.enable {
+            if self.mailer.from.parse::<lettre::message::Mailbox>().is_err() {
+                errors.push(format!("mailer.from 不是有效的邮箱地址: {}", self.mailer.from));
+            }
+            if self.mailer.transport == MailTransport::Smtp && self.mailer.smtp.host.is_empty() {
+                errors.push("mailer.smtp.host 未设置".to_owned());
+            }
+            // 直接 TLS 和 STARTTLS 的端口互相用错时无法建立连接
+            match (self.mailer.smtp.security, self.mailer.smtp.port) {
+                (SmtpSecurity::Tls, Some(587)) => errors.push("mailer.smtp.security 为 tls 时端口应为 465，587 端口请使用 starttls".to_owned()),
+                (SmtpSecurity::StartTls, Some(465)) => errors.push("mailer.smtp.security 为 starttls 时端口应为 587，465 端口请使用 tls".to_owned()),
+                _ => {}
+            }
+            if self.mailer.transport == MailTransport::File && self.mailer.file_dir.is_empty() {
+                errors.push("mailer.file_dir 不能为空".to_owned());
+            }
+        }
+        if self.storage.enable {
+            if self.storage.url_secret.is_empty() {
+                errors.push("storage.url_secret 未设置（或设置 BUBO_STORAGE__URL_SECRET）".to_owned());
+            }
+            if !self.storage.url_prefix.starts_with('/') {
+                errors.push("storage.url_prefix 必须以 / 开头".to_owned());
+            }
+            if self.storage.max_upload_size == 0 {
+                errors.push("storage.max_upload_size 必须大于 0".to_owned());
+            }
+            if self.storage.backend == StorageBackend::Local && self.storage.root.is_empty() {
+                errors.push("storage.root 不能为空".to_owned());
+            }
+            if self.storage.backend == StorageBackend::S3 {
+                let s3 = &self.storage.s3;
+                for (name, value) in [("endpoint", &s3.endpoint), ("region", &s3.region), ("bucket", &s3.bucket),
+                    ("access_key", &s3.access_key), ("secret_key", &s3.secret_key)] {
+                    if value.is_empty() {
+                        errors.push(format!("storage.s3.{name} 未设置"));
+                    }
+                }
+            }
+        }
+        if self.cache.backend != CacheBackendKind::Redis && self.cache.capacity == 0 {
+            errors.push("cache.capacity 必须大于 0".to_owned());
+        }
+        if self.cache.backend == CacheBackendKind::TwoTier && self.cache.local_ttl == 0 {
+            errors.push("cache.local_ttl 必须大于 0".to_owned());
+        }
+        for (name, rule) in &self.rate_limit.groups {
+            if rule.limit == 0 || rule.window == 0 {
+                errors.push(format!("rate_limit.groups.{name} 的 limit 和 window 必须大于 0"));
+            }
+        }
+        if let Some(global) = &self.rate_limit.global {
+            if !self.rate_limit.groups.contains_key(global) {
+                errors.push(format!("rate_limit.global 对应的分组 {global} 不存在"));
+            }
+        }
+        for proxy in &self.rate_limit.trusted_proxies {
+            if parse_ip_net(proxy).is_none() {
+                errors.push(format!("rate_limit.trusted_proxies 中的 {proxy} 不是有效的 IP 地址或网段"));
+            }
+        }
+        // unix socket 没有客户端地址，不使用 X-Forwarded-For 时所有请求共用一个计数
+        let by_ip = self.rate_limit.groups.values().any(|rule| rule.key != RateLimitKey::Route);
+        if self.rate_limit.enable && by_ip && self.server.unix_socket.is_some() && !self.rate_limit.trust_proxy {
+            errors.push("监听 server.unix_socket 时无法获取客户端 IP，按 ip 或 user 限流需要开启 rate_limit.trust_proxy".to_owned());
+        }
+        if self.assets.enable {
+            if !self.assets.prefix.starts_with('/') {
+                errors.push("assets.prefix 必须以 / 开头".to_owned());
+            }
+            if self.assets.index.is_empty() {
+                errors.push("assets.index 不能为空".to_owned());
+            }
+        }
+        if self.openapi.enable {
+            if !self.openapi.path.starts_with('/') {
+                errors.push("openapi.path 必须以 / 开头".to_owned());
+            }
+            if !self.openapi.viewer_path.is_empty() && !self.openapi.viewer_path.starts_with('/') {
+                errors.push("openapi.viewer_path 必须以 / 开头".to_owned());
+            }
+            if self.openapi.path == self.openapi.viewer_path {
+                errors.push("openapi.path 与 openapi.viewer_path 不能相同".to_owned());
+            }
+        }
+        if self.i18n.default_locale.is_empty() {
+            errors.push("i18n.default_locale 不能为空".to_owned());
+        }
+        if !self.i18n.dir.is_empty() && !Path::new(&self.i18n.dir).is_dir() {
+            errors.push(format!("i18n.dir 目录不存在: {}", self.i18n.dir));
+        }
+        if let Err(cors_errors) = crate::controllers::middlewares::cors::layer(&self.cors) {
+            errors.extend(cors_errors);
+        }
+        if self.auth.access_grace_period < 0 {
+            errors.push("auth.access_grace_period 不能小于 0".to_owned());
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(errors))
+        }
+    }
+}
+
+fn find_config_file(dir: &Path, environment: Environment) -> Option<PathBuf> {
+    ["toml", "yaml", "yml"]
+        .iter()
+        .map(|ext| dir.join(format!("{}.{}", environment, ext)))
+        .find(|path| path.is_file())
+}
+
+fn read_config_file(path: &Path) -> ConfigResult<Value> {
+    let content = std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.display().to_string(), e))?;
+    let is_toml = path.extension().is_some_and(|ext| ext == "toml");
+    if is_toml {
+        toml::from_str(&content).map_err(|e| ConfigError::Parse(path.display().to_string(), e.to_string()))
+    } else {
+        serde_yaml::from_str(&content).map_err(|e| ConfigError::Parse(path.display().to_string(), e.to_string()))
+    }
+}
+
+///
+/// 深度合并，overlay 中的值覆盖 base
+///
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (k, v) in overlay {
+                merge(base.entry(k).or_insert(Value::Null), v);
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+///
+/// 用环境变量覆盖配置，`BUBO_SERVER__PORT` 对应 `server.port`
+///
+fn overlay_env(value: &mut Value, vars: impl Iterator<Item = (String, String)>) {
+    let mut overrides = Vec::new();
+    for (key, val) in vars {
+        if let Some((_, path)) = LEGACY_ENV_VARS.iter().find(|(name, _)| *name == key) {
+            // 旧变量优先级低于带前缀的变量
+            overrides.insert(0, (path.iter().map(|s| (*s).to_owned()).collect::<Vec<_>>(), val));
+        } else if let Some(rest) = key.strip_prefix(ENV_PREFIX) {
+            if rest == "ENV" || rest == "CONFIG_DIR" {
+                continue;
+            }
+            overrides.push((rest.split(ENV_SEPARATOR).map(|s| s.to_ascii_lowercase()).collect(), val));
+        }
+    }
+    for (path, val) in overrides {
+        set_path(value, &path, val);
+    }
+}
+
+fn set_path(value: &mut Value, path: &[String], raw: String) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+    let mut current = value;
+    for key in parents {
+        if !current.is_object() {
+            *current = Value::Object(Map::new());
+        }
+        current = current.as_object_mut().unwrap().entry(key.as_str()).or_insert(Value::Object(Map::new()));
+    }
+    if !current.is_object() {
+        *current = Value::Object(Map::new());
+    }
+    let slot = current.as_object_mut().unwrap().entry(last.as_str()).or_insert(Value::Null);
+    // 原本是字符串的字段保持字符串，避免纯数字的密钥被解析成数字
+    *slot = if slot.is_string() {
+        Value::String(raw)
+    } else {
+        serde_json::from_str(&raw).unwrap_or(Value::String(raw))
+    };
+}
+
+pub type ConfigResult<T> = core::result::Result<T, ConfigError>;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("未知的运行环境: {0}")]
+    UnknownEnvironment(String),
+    #[error("读取配置文件 {0} 失败: {1}")]
+    Io(String, #[source] std::io::Error),
+    #[error("解析配置文件 {0} 失败: {1}")]
+    Parse(String, String),
+    #[error("配置格式错误: {0}")]
+    Deserialize(#[from] serde_json::Error),
+    #[error("配置校验失败:\n{}", .0.iter().map(|e| format!("  - {e}")).collect::<Vec<_>>().join("\n"))]
+    Invalid(Vec<String>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn vars(list: &[(&str, &str)]) -> impl Iterator<Item = (String, String)> {
+        list.iter().map(|(k, v)| ((*k).to_owned(), (*v).to_owned())).collect::<Vec<_>>().into_iter()
+    }
+
+    #[test]
+    fn test_overlay_env() {
+        let mut value = serde_json::to_value(Config::default()).unwrap();
+        merge(&mut value, toml::from_str("[server]\nport = 9000\n[redis]\nurl = \"redis://file\"").unwrap());
+        overlay_env(&mut value, vars(&[
+            ("BUBO_REDIS__POOL_SIZE", "4"),
+            ("BUBO_AUTH__ACCESS_SECRET", "123456"),
+            ("REDIS_URL", "redis://legacy"),
+            ("BUBO_REDIS__URL", "redis://env"),
+            ("PATH", "/usr/bin"),
+        ]));
+        let config: Config = serde_json::from_value(value).unwrap();
+        assert_eq!(config.server.port, 9000);
+        assert_eq!(config.redis.pool_size, 4);
+        assert_eq!(config.redis.url, "redis://env");
+        assert_eq!(config.auth.access_secret, "123456");
+        assert_eq!(config.server.host, "0.0.0.0");
+    }
+
+    #[test]
+    fn test_validate_reports_all_errors() {
+        let mut config = Config::default();
+        config.redis.pool_size = 0;
+        match config.validate() {
+            Err(ConfigError::Invalid(errors)) => {
+                assert_eq!(errors.len(), 5);
+                assert!(errors.iter().any(|e| e.starts_with("database.url")));
+                assert!(errors.iter().any(|e| e.starts_with("redis.pool_size")));
+            }
+            other => panic!("unexpected: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_smtp_port() {
+        let mut smtp = SmtpConfig { security: SmtpSecurity::Tls, ..Default::default() };
+        assert_eq!(smtp.port(), 465);
+        smtp.security = SmtpSecurity::StartTls;
+        assert_eq!(smtp.port(), 587);
+        smtp.port = Some(2525);
+        assert_eq!(smtp.port(), 2525);
+
+        let mut config = Config {
+            mailer: MailerConfig { enable: true, from: "Bubo <noreply@example.com>".to_owned(), ..Default::default() },
+            ..Default::default()
+        };
+        config.mailer.smtp.security = SmtpSecurity::Tls;
+        config.mailer.smtp.port = Some(587);
+        match config.validate() {
+            Err(ConfigError::Invalid(errors)) => assert!(errors.iter().any(|e| e.starts_with("mailer.smtp.security")), "{errors:?}"),
+            other => panic!("unexpected: {other:?}"),
+        }
+    }
+}
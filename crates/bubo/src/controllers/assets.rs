@@ -0,0 +1,252 @@
+use std::{borrow::Cow, path::PathBuf, sync::Arc};
+
+use axum::{body::Body, extract::{Request, State}, http::{header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH}, HeaderValue, Method, StatusCode, Uri}, response::{IntoResponse, Response}, routing::any, Router};
+use tower::ServiceExt;
+use tower_http::services::{ServeDir, ServeFile};
+
+use crate::{config::AssetsConfig, server::json_fallback};
+
+// 带 hash 的文件内容不会变化
+const CACHE_IMMUTABLE: &str = "public, max-age=31536000, immutable";
+// index.html 等每次都需要向服务器确认
+const CACHE_NO_CACHE: &str = "no-cache";
+
+///
+/// 编译进二进制的静态资源，path 为相对路径，例如 `assets/index-B2xd8Kq1.js`
+///
+pub trait EmbeddedAssets: Send + Sync + 'static {
+    fn get(&self, path: &str) -> Option<EmbeddedAsset>;
+}
+
+pub struct EmbeddedAsset {
+    pub data: Cow<'static, [u8]>,
+    // 内容 hash，用于 ETag
+    pub hash: Option<String>,
+}
+
+///
+/// 通过 rust-embed 嵌入的资源，需要开启 feature `embed`
+///
+/// ```ignore
+/// #[derive(bubo::rust_embed::RustEmbed)]
+/// #[crate_path = "bubo::rust_embed"]
+/// #[folder = "../admin-web/dist"]
+/// struct WebAssets;
+///
+/// fn embedded_assets() -> Option<Arc<dyn EmbeddedAssets>> {
+///     Some(Arc::new(Embedded::<WebAssets>::new()))
+/// }
+/// ```
+///
+#[cfg(feature = "embed")]
+pub struct Embedded<E>(std::marker::PhantomData<fn() -> E>);
+
+#[cfg(feature = "embed")]
+impl<E: rust_embed::RustEmbed> Embedded<E> {
+    pub fn new() -> Self {
+        Self(std::marker::PhantomData)
+    }
+}
+
+#[cfg(feature = "embed")]
+impl<E: rust_embed::RustEmbed> Default for Embedded<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "embed")]
+impl<E: rust_embed::RustEmbed + 'static> EmbeddedAssets for Embedded<E> {
+    fn get(&self, path: &str) -> Option<EmbeddedAsset> {
+        E::get(path).map(|file| EmbeddedAsset { hash: Some(hex::encode(file.metadata.sha256_hash())), data: file.data })
+    }
+}
+
+#[derive(Clone)]
+enum Source {
+    Dir(ServeDir),
+    Embedded(Arc<dyn EmbeddedAssets>),
+}
+
+#[derive(Clone)]
+struct Assets {
+    source: Source,
+    dir: PathBuf,
+    prefix: String,
+    index: String,
+    api_prefixes: Vec<String>,
+}
+
+///
+/// 前端单页应用，作为 fallback 使用：
+/// 存在的文件直接返回，prefix 下不带扩展名的路径返回 index.html 交给前端路由，
+/// api_prefixes 下以及其他找不到的路径仍然返回 json_fallback
+///
+pub fn fallback(config: &AssetsConfig, embedded: Option<Arc<dyn EmbeddedAssets>>) -> Router {
+    let dir = PathBuf::from(&config.dir);
+    let source = match embedded {
+        Some(embedded) => Source::Embedded(embedded),
+        None => Source::Dir(ServeDir::new(&dir).append_index_html_on_directories(false).precompressed_gzip().precompressed_br()),
+    };
+    let assets = Assets {
+        source,
+        dir,
+        prefix: config.prefix.trim_end_matches('/').to_owned(),
+        index: config.index.clone(),
+        api_prefixes: config.api_prefixes.iter().map(|prefix| prefix.trim_end_matches('/').to_owned()).collect(),
+    };
+    Router::new().fallback(any(serve)).with_state(assets)
+}
+
+async fn serve(State(assets): State<Assets>, req: Request) -> Response {
+    let path = req.uri().path().to_owned();
+    if !matches!(*req.method(), Method::GET | Method::HEAD) || assets.api_prefixes.iter().any(|prefix| under(&path, prefix)) {
+        return json_fallback(req.uri().clone()).await.into_response();
+    }
+    let Some(relative) = path.strip_prefix(assets.prefix.as_str()).filter(|_| under(&path, &assets.prefix)) else {
+        return json_fallback(req.uri().clone()).await.into_response();
+    };
+    let relative = relative.trim_start_matches('/').to_owned();
+
+    if !relative.is_empty() && relative != assets.index {
+        let response = match &assets.source {
+            Source::Dir(dir) => {
+                let uri = Uri::try_from(format!("/{relative}")).unwrap_or_default();
+                let response = dir.clone().oneshot(sub_request(&req, uri)).await.unwrap_or_else(|e| match e {});
+                (response.status() != StatusCode::NOT_FOUND).then(|| response.map(Body::new))
+            }
+            Source::Embedded(embedded) => embedded.get(&relative).map(|asset| embedded_response(&req, &relative, asset)),
+        };
+        if let Some(mut response) = response {
+            let cache = if is_hashed(&relative) { CACHE_IMMUTABLE } else { CACHE_NO_CACHE };
+            response.headers_mut().insert(CACHE_CONTROL, HeaderValue::from_static(cache));
+            return response;
+        }
+        // 带扩展名的文件不存在时不返回 index.html
+        if relative.rsplit('/').next().is_some_and(|name| name.contains('.')) {
+            return json_fallback(req.uri().clone()).await.into_response();
+        }
+    }
+
+    // 前端路由
+    let index = assets.index.as_str();
+    let mut response = match &assets.source {
+        Source::Dir(_) => {
+            let request = sub_request(&req, Uri::from_static("/"));
+            let response = ServeFile::new(assets.dir.join(index)).oneshot(request).await.unwrap_or_else(|e| match e {});
+            response.map(Body::new)
+        }
+        Source::Embedded(embedded) => match embedded.get(index) {
+            Some(asset) => embedded_response(&req, index, asset),
+            None => StatusCode::NOT_FOUND.into_response(),
+        },
+    };
+    if response.status() == StatusCode::NOT_FOUND {
+        return json_fallback(req.uri().clone()).await.into_response();
+    }
+    response.headers_mut().insert(CACHE_CONTROL, HeaderValue::from_static(CACHE_NO_CACHE));
+    response
+}
+
+///
+/// 转发给 ServeDir 的请求，保留条件请求和 Range 等请求头
+///
+fn sub_request(req: &Request, uri: Uri) -> Request {
+    let mut request = Request::new(Body::empty());
+    *request.method_mut() = req.method().clone();
+    *request.uri_mut() = uri;
+    *request.headers_mut() = req.headers().clone();
+    request
+}
+
+fn embedded_response(req: &Request, path: &str, asset: EmbeddedAsset) -> Response {
+    let etag = asset.hash.map(|hash| format!("\"{hash}\""));
+    let not_modified = etag.as_deref().is_some_and(|etag| {
+        req.headers().get(IF_NONE_MATCH).and_then(|value| value.to_str().ok()).is_some_and(|value| value.split(',').any(|v| v.trim() == etag))
+    });
+    let mut response = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        let content_type = mime_guess::from_path(path).first_or_octet_stream();
+        ([(CONTENT_TYPE, content_type.as_ref())], Body::from(asset.data)).into_response()
+    };
+    if let Some(etag) = etag.and_then(|etag| HeaderValue::try_from(etag).ok()) {
+        response.headers_mut().insert(ETAG, etag);
+    }
+    response
+}
+
+///
+/// path 等于 prefix 或在 prefix 之下，prefix 为空时匹配全部
+///
+fn under(path: &str, prefix: &str) -> bool {
+    prefix.is_empty() || path.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+///
+/// 文件名是否带构建工具生成的 hash，例如 `index-B2xd8Kq1.js`、`app.3f2a9c1b.css`
+///
+fn is_hashed(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let Some((stem, _ext)) = name.rsplit_once('.') else {
+        return false;
+    };
+    stem.rsplit(['-', '.'])
+        .next()
+        .filter(|hash| hash.len() < stem.len())
+        .is_some_and(|hash| hash.len() >= 8 && hash.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_') && hash.bytes().any(|b| b.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::to_bytes;
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    struct TestAssets;
+
+    impl EmbeddedAssets for TestAssets {
+        fn get(&self, path: &str) -> Option<EmbeddedAsset> {
+            let data: &'static [u8] = match path {
+                "index.html" => b"<html></html>",
+                "assets/index-B2xd8Kq1.js" => b"console.log(1)",
+                _ => return None,
+            };
+            Some(EmbeddedAsset { data: Cow::Borrowed(data), hash: Some(format!("{}", data.len())) })
+        }
+    }
+
+    #[test]
+    fn test_is_hashed() {
+        assert!(is_hashed("assets/index-B2xd8Kq1.js"));
+        assert!(is_hashed("app.3f2a9c1b.css"));
+        assert!(!is_hashed("index.html"));
+        assert!(!is_hashed("favicon.ico"));
+        assert!(!is_hashed("assets/vendor-library.js"));
+    }
+
+    #[tokio::test]
+    async fn test_fallback() {
+        let config = AssetsConfig { enable: true, prefix: "/".to_owned(), api_prefixes: vec!["/admin".to_owned()], ..Default::default() };
+        let app = fallback(&config, Some(Arc::new(TestAssets)));
+        let get = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+
+        let response = app.clone().oneshot(get("/assets/index-B2xd8Kq1.js")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CACHE_CONTROL], CACHE_IMMUTABLE);
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/javascript");
+
+        let request = Request::get("/assets/index-B2xd8Kq1.js").header(IF_NONE_MATCH, "\"14\"").body(Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::NOT_MODIFIED);
+
+        let response = app.clone().oneshot(get("/system/user")).await.unwrap();
+        assert_eq!(response.headers()[CACHE_CONTROL], CACHE_NO_CACHE);
+        assert_eq!(to_bytes(response.into_body(), usize::MAX).await.unwrap(), "<html></html>");
+
+        for uri in ["/admin/system/user/page", "/missing.js"] {
+            let response = app.clone().oneshot(get(uri)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{uri}");
+        }
+    }
+}
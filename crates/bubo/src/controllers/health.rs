@@ -0,0 +1,90 @@
+use std::{future::Future, time::{Duration, Instant}};
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, routing::get, Json, Router};
+use fred::prelude::ClientLike;
+use sea_orm_migration::MigratorTrait;
+use serde::Serialize;
+use serde_json::json;
+use tracing::warn;
+
+use crate::{server::AppState, utils::prometheus};
+
+pub const LIVE_PATH: &str = "/health/live";
+pub const READY_PATH: &str = "/health/ready";
+
+///
+/// 健康检查路由，M 用于检查是否有未执行的迁移
+///
+pub fn init_routes<M: MigratorTrait + 'static>(state: AppState) -> Router {
+    Router::new()
+        .route(LIVE_PATH, get(live_handler))
+        .route(READY_PATH, get(ready_handler::<M>))
+        .with_state(state)
+}
+
+#[derive(Debug, Serialize)]
+pub struct ComponentHealth {
+    pub status: bool,
+    pub latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending: Option<usize>,
+}
+
+///
+/// 存活检查，进程能处理请求即可
+///
+async fn live_handler() -> impl IntoResponse {
+    Json(json!({
+        "status": true,
+    }))
+}
+
+///
+/// 就绪检查，数据库、redis、迁移全部正常才返回 200，收到停止信号后在 server.drain_delay 内返回 503
+///
+async fn ready_handler<M: MigratorTrait>(State(state): State<AppState>) -> impl IntoResponse {
+    let timeout = Duration::from_secs(state.config.server.health_check_timeout);
+    let (database, redis, migrations) = tokio::join!(
+        probe(timeout, async { state.db.ping().await.map(|_| None).map_err(|e| e.to_string()) }),
+        probe(timeout, async {
+            state.redis.ping::<String>().await.map(|_| None).map_err(|e| {
+                prometheus::record_redis_error(&e);
+                e.to_string()
+            })
+        }),
+        probe(timeout, async {
+            match M::get_pending_migrations(&state.db).await {
+                Ok(pending) if pending.is_empty() => Ok(Some(0)),
+                Ok(pending) => Err(format!("{} pending migrations", pending.len())),
+                Err(e) => Err(e.to_string()),
+            }
+        }),
+    );
+    let shutting_down = state.shutdown.is_draining();
+    let ready = !shutting_down && database.status && redis.status && migrations.status;
+    if !ready {
+        warn!("readiness check failed, shutting_down: {}", shutting_down);
+    }
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(json!({
+        "status": ready,
+        "shutting_down": shutting_down,
+        "components": {
+            "database": database,
+            "redis": redis,
+            "migrations": migrations,
+        },
+    })))
+}
+
+async fn probe(timeout: Duration, f: impl Future<Output = Result<Option<usize>, String>>) -> ComponentHealth {
+    let start = Instant::now();
+    let result = tokio::time::timeout(timeout, f).await.unwrap_or_else(|_| Err("timeout".to_owned()));
+    let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+    match result {
+        Ok(pending) => ComponentHealth { status: true, latency_ms, error: None, pending },
+        Err(e) => ComponentHealth { status: false, latency_ms, error: Some(e), pending: None },
+    }
+}
@@ -0,0 +1,53 @@
+use std::{sync::Arc, time::Duration};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::{config::LogReloadConfig, utils::{error::{BuboError, BuboResult, BusinessErrorCode}, log::{self, LogLevel, LogLevelStatus}, validator::JsonValid}, views::response::ApiResponse};
+
+use super::routing::{get, post, secured, Routes};
+
+///
+/// 运行时查看和修改日志过滤指令，logger.reload.path 为空时不注册
+///
+pub fn routes(config: &LogReloadConfig) -> Routes {
+    if config.path.is_empty() {
+        return Routes::new();
+    }
+    let path = config.path.trim_end_matches('/');
+    Routes::new()
+        .route(path, secured(get(log_level)).permission(&config.permission)
+            .summary("查看日志级别").tag("log").response::<LogLevelStatus>())
+        .route(path, secured(post(set_log_level)).permission(&config.permission)
+            .summary("修改日志级别").tag("log").json::<SetLogLevelParams>().response::<LogLevelStatus>())
+        .route(&format!("{path}/reset"), secured(post(reset_log_level)).permission(&config.permission)
+            .summary("恢复配置的日志级别").tag("log").response::<LogLevelStatus>())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, JsonSchema)]
+pub struct SetLogLevelParams {
+    // 过滤指令，例如 `debug`、`info,bubo::http=debug`
+    #[validate(length(min = 1, max = 1024))]
+    pub directive: String,
+    // 多少秒后自动恢复为配置的 level，最长一天，不设置时一直生效
+    #[validate(range(min = 1, max = 86400))]
+    pub revert_after: Option<u64>,
+}
+
+fn current() -> BuboResult<Arc<LogLevel>> {
+    log::log_level().ok_or_else(|| BuboError::business_error(BusinessErrorCode::NotFound, "log.unavailable"))
+}
+
+async fn log_level() -> BuboResult<ApiResponse<LogLevelStatus>> {
+    Ok(ApiResponse::ok(current()?.status()))
+}
+
+async fn set_log_level(JsonValid(params): JsonValid<SetLogLevelParams>) -> BuboResult<ApiResponse<LogLevelStatus>> {
+    let status = current()?.set(&params.directive, params.revert_after.map(Duration::from_secs))?;
+    Ok(ApiResponse::ok(status))
+}
+
+async fn reset_log_level() -> BuboResult<ApiResponse<LogLevelStatus>> {
+    Ok(ApiResponse::ok(current()?.reset()?))
+}
@@ -1,13 +1,13 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, sync::Arc};
 
-use axum::{extract::{Request, State}, middleware::Next, response::IntoResponse, Extension};
+use axum::{extract::{Request, State}, http::{header::CONTENT_LANGUAGE, HeaderValue}, middleware::Next, response::{IntoResponse, Response}, Extension};
 use axum_extra::{headers::{authorization::Bearer, Authorization}, TypedHeader};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 use time::{Duration, OffsetDateTime};
 use tracing::{debug, warn};
 
-use crate::{server::AppState, utils::{error::{BuboError, BuboResult, BusinessErrorCode, SystemErrorCode}, redis, snowflake, time::{current_timestamp_sec, now_utc}}};
+use crate::{i18n, server::AppState, utils::{error::{BuboError, BuboResult, BusinessErrorCode, SystemErrorCode}, prometheus, snowflake, time::{current_timestamp_sec, now_utc}}};
 
 pub const TOKEN_TYPE: &str = "Bearer";
 pub const ACCESS_TYPE: &str = "ACCESS";
@@ -30,6 +30,9 @@ pub struct AuthUser {
     pub roles: HashSet<String>,
     pub permissions: HashSet<String>,
     pub menu_ids: HashSet<i64>,
+    // 语言偏好，例如 en-US，设置后错误消息等使用该语言
+    #[serde(default)]
+    pub locale: Option<String>,
 }
 
 impl AuthUser {
@@ -47,8 +50,14 @@ impl AuthUser {
             roles,
             permissions,
             menu_ids,
+            locale: None,
         }
     }
+
+    pub fn with_locale(mut self, locale: impl Into<String>) -> Self {
+        self.locale = Some(locale.into());
+        self
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -68,29 +77,53 @@ pub struct Claims {
 }
 
 pub async fn refresh(
-    TypedHeader(authorization): TypedHeader<Authorization<Bearer>>,
+    authorization: Option<TypedHeader<Authorization<Bearer>>>,
     State(state): State<AppState>,
-    mut req: Request,
+    req: Request,
     next: Next,
 ) -> BuboResult<impl IntoResponse> {
-    let token = authorization.token();
-    let auth_user = auth_token(state.clone(), token, REFRESH_TYPE).await?;
-    req.extensions_mut().insert(auth_user);
-    let result = next.run(req).await;
-    Ok(result)
+    let token = bearer_token(&authorization)?;
+    let auth_user = auth_token(state.clone(), token, REFRESH_TYPE).await
+        .inspect_err(|_| prometheus::record_token_refresh(false))?;
+    Ok(run_as(auth_user, req, next).await)
 }
 
 pub async fn auth(
-    TypedHeader(authorization): TypedHeader<Authorization<Bearer>>,
+    authorization: Option<TypedHeader<Authorization<Bearer>>>,
     State(state): State<AppState>,
-    mut req: Request,
+    req: Request,
     next: Next,
 ) -> BuboResult<impl IntoResponse> {
-    let token = authorization.token();
+    let token = bearer_token(&authorization)?;
     let auth_user = auth_token(state.clone(), token, ACCESS_TYPE).await?;
+    Ok(run_as(auth_user, req, next).await)
+}
+
+///
+/// 保存登录用户并继续处理请求，用户有语言偏好时改用用户的语言
+///
+async fn run_as(auth_user: AuthUser, mut req: Request, next: Next) -> Response {
+    let locale = auth_user.locale.clone();
     req.extensions_mut().insert(auth_user);
-    let result = next.run(req).await;
-    Ok(result)
+    match locale {
+        Some(locale) => i18n::with_locale(&locale, async move {
+            let mut response = next.run(req).await;
+            if let Ok(value) = HeaderValue::from_str(&i18n::locale()) {
+                response.headers_mut().insert(CONTENT_LANGUAGE, value);
+            }
+            response
+        }).await,
+        None => next.run(req).await,
+    }
+}
+
+///
+/// 没有 Authorization 头时同样返回 Unauthorized
+///
+fn bearer_token(authorization: &Option<TypedHeader<Authorization<Bearer>>>) -> BuboResult<&str> {
+    authorization.as_ref()
+        .map(|TypedHeader(authorization)| authorization.token())
+        .ok_or_else(|| BuboError::from(BusinessErrorCode::Unauthorized))
 }
 
 pub async fn  auth_token(
@@ -98,9 +131,9 @@ pub async fn  auth_token(
     token: &str,
     token_type: &str,
 ) -> BuboResult<AuthUser> {
-    let mut key = state.jwt_secret_access.as_bytes();
+    let mut key = state.config.auth.access_secret.as_bytes();
     if token_type == REFRESH_TYPE {
-        key = state.jwt_secret_refresh.as_bytes();
+        key = state.config.auth.refresh_secret.as_bytes();
     }
     let mut validation = Validation::default();
     validation.set_audience(&[state.app_name]);
@@ -108,24 +141,22 @@ pub async fn  auth_token(
     let claims = decode::<Claims>(token, &DecodingKey::from_secret(key), &validation)
         .map_err(|e| {
             warn!("jwt decode error:{:?}", e);
-            BuboError::business_error(BusinessErrorCode::Unauthorized, "unauthorized")
+            BuboError::from(BusinessErrorCode::Unauthorized)
         })?.claims;
-    let key = redis::gen_key(state.app_name, "auth-user", claims.sub);
-
-    let auth_user: AuthUser = redis::get(&state.redis, key).await?.ok_or(BuboError::business_error(BusinessErrorCode::Unauthorized, "unauthorized"))?;
+    let auth_user = state.sessions.get(claims.sub).await?.ok_or(BuboError::from(BusinessErrorCode::Unauthorized))?;
     if token_type == ACCESS_TYPE {
-        // 5分钟内旧access_token可以使用
+        // 刷新后一段时间内旧access_token可以使用
         if auth_user.access_token_id != claims.jti 
         && !auth_user.last_access_token_id.is_some_and(|x| { 
-            x == claims.jti && auth_user.refreshed_at.unwrap().unix_timestamp() + 300 > current_timestamp_sec()
+            x == claims.jti && auth_user.refreshed_at.unwrap().unix_timestamp() + state.config.auth.access_grace_period > current_timestamp_sec()
         }) {
             warn!("access token not equal");
-            return Err(BuboError::business_error(BusinessErrorCode::Unauthorized, "unauthorized"));
+            return Err(BuboError::from(BusinessErrorCode::Unauthorized));
         }
         
     } else if token_type == REFRESH_TYPE && auth_user.refresh_token_id != claims.jti {
         warn!("refresh token not equal");
-        return Err(BuboError::business_error(BusinessErrorCode::Unauthorized, "unauthorized"));
+        return Err(BuboError::from(BusinessErrorCode::Unauthorized));
     }
 
     Ok(auth_user)
@@ -145,24 +176,40 @@ pub fn encode_token(id: i64, aud: impl Into<String>, iss: impl Into<String>, jti
         .map_err(|_e| BuboError::system_error(SystemErrorCode::JwtEncodeError, "jwt claims encode error"))
 }
 
+///
+/// 创建令牌并保存会话，access_token_id 不为 0 时为刷新令牌，分别计数到 auth_token_refreshes_total 和 auth_logins_total
+///
 pub async fn create_token(state: &AppState, mut auth_user: AuthUser) -> BuboResult<(String, String, &'static str, i64)> {
-    if auth_user.access_token_id != 0 {
+    let refresh = auth_user.access_token_id != 0;
+    if refresh {
         auth_user.last_access_token_id = Some(auth_user.access_token_id);
         auth_user.refreshed_at = Some(now_utc());
     }
     auth_user.access_token_id = snowflake::new_id();
-    let access_token = encode_token(auth_user.id, state.app_name, state.app_name, auth_user.access_token_id, ACCESS_EXP, state.jwt_secret_access.as_bytes())?;
+    let access_token = encode_token(auth_user.id, state.app_name, state.app_name, auth_user.access_token_id, state.config.auth.access_expire, state.config.auth.access_secret.as_bytes())?;
     auth_user.refresh_token_id = snowflake::new_id();
-    let refresh_token = encode_token(auth_user.id, state.app_name, state.app_name, auth_user.refresh_token_id, REFRESH_EXP, state.jwt_secret_refresh.as_bytes())?;
-    
-    let key = redis::gen_key(state.app_name, "auth-user", auth_user.id);
-    
-    redis::set(&state.redis, key, &auth_user, Some(fred::types::Expiration::EX(604800))).await?;
-    Ok((access_token, refresh_token, TOKEN_TYPE, ACCESS_EXP))
+    let refresh_token = encode_token(auth_user.id, state.app_name, state.app_name, auth_user.refresh_token_id, state.config.auth.refresh_expire, state.config.auth.refresh_secret.as_bytes())?;
+
+    let ttl = std::time::Duration::from_secs(u64::try_from(state.config.auth.refresh_expire).unwrap_or_default());
+    state.sessions.set(&auth_user, ttl).await?;
+    if refresh {
+        prometheus::record_token_refresh(true);
+    } else {
+        prometheus::record_login(true);
+    }
+    Ok((access_token, refresh_token, TOKEN_TYPE, state.config.auth.access_expire))
+}
+
+///
+/// 路由对应的权限标识，`/system/user/page` 对应 `system:user:page`
+///
+pub fn path_permission(path: &str) -> String {
+    path.strip_prefix('/').unwrap_or(path).replace('/', ":")
 }
 
 ///
-///  权限验证
+///  权限验证，权限标识由请求路径生成，
+///  通过 routing::Routes 注册的路由使用 `secured(..).permission(..)` 指定的权限标识
 /// 
 pub async fn permission(
     // State(state): State<Arc<AppState>>,
@@ -170,16 +217,31 @@ pub async fn permission(
     req: Request,
     next: Next,
 ) -> BuboResult<impl IntoResponse> {
+    let permission = path_permission(req.uri().path());
+    check_permission(&auth_user, &permission)?;
+    Ok(next.run(req).await)
+}
+
+///
+/// 验证指定的权限，由 routing::Endpoint::permission 添加
+///
+pub async fn require_permission(
+    State(permission): State<Arc<str>>,
+    Extension(auth_user): Extension<AuthUser>,
+    req: Request,
+    next: Next,
+) -> BuboResult<impl IntoResponse> {
+    check_permission(&auth_user, &permission)?;
+    Ok(next.run(req).await)
+}
+
+fn check_permission(auth_user: &AuthUser, permission: &str) -> BuboResult<()> {
     // 不是管管理员需验证权限
     if !auth_user.is_admin {
-        let mut permission = req.uri().path().replace("/", ":");
-        if !permission.is_empty() {
-            permission.remove(0);
-        }
         debug!("permission:{}", permission);
-        if !auth_user.permissions.contains(permission.as_str()) {
-            return Err(BuboError::business_error(BusinessErrorCode::Forbidden, "forbidden"));
+        if !auth_user.permissions.contains(permission) {
+            return Err(BuboError::from(BusinessErrorCode::Forbidden));
         }
     }
-    Ok(next.run(req).await)
+    Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,172 @@
+use std::time::Duration;
+
+use axum::http::{uri::Uri, HeaderName, HeaderValue, Method};
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer, ExposeHeaders};
+
+use crate::config::CorsConfig;
+
+const ANY: &str = "*";
+
+///
+/// 来源匹配规则
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OriginPattern {
+    // `https://example.com`
+    Exact(String),
+    // `https://*.example.com`，不匹配 `https://example.com` 本身
+    Subdomain { scheme: String, suffix: String },
+}
+
+impl OriginPattern {
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        let pattern = pattern.trim().trim_end_matches('/').to_ascii_lowercase();
+        let invalid = || format!("cors.allow_origins 中的 {pattern} 格式错误，应为 scheme://host[:port]");
+        let (scheme, host) = pattern.split_once("://").ok_or_else(invalid)?;
+        let (wildcard, host) = match host.strip_prefix("*.") {
+            Some(rest) => (true, rest),
+            None => (false, host),
+        };
+        if host.is_empty() || host.contains('*') {
+            return Err(invalid());
+        }
+        // 只允许 scheme 和 authority
+        let uri: Uri = format!("{scheme}://{host}").parse().map_err(|_| invalid())?;
+        if uri.scheme().is_none() || uri.authority().map(|a| a.as_str()) != Some(host) || uri.path() != "/" {
+            return Err(invalid());
+        }
+        if wildcard {
+            Ok(Self::Subdomain { scheme: scheme.to_owned(), suffix: format!(".{host}") })
+        } else {
+            Ok(Self::Exact(pattern))
+        }
+    }
+
+    pub fn matches(&self, origin: &str) -> bool {
+        let origin = origin.to_ascii_lowercase();
+        match self {
+            Self::Exact(exact) => origin == *exact,
+            Self::Subdomain { scheme, suffix } => origin
+                .strip_prefix(scheme.as_str())
+                .and_then(|rest| rest.strip_prefix("://"))
+                .and_then(|host| host.strip_suffix(suffix.as_str()))
+                .is_some_and(|sub| !sub.is_empty() && sub.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'.')),
+        }
+    }
+}
+
+///
+/// 按配置创建 CorsLayer，返回全部配置错误
+///
+pub fn layer(config: &CorsConfig) -> Result<CorsLayer, Vec<String>> {
+    let mut errors = Vec::new();
+    let is_any = |values: &[String]| values.iter().any(|v| v == ANY);
+
+    let origin = if is_any(&config.allow_origins) {
+        AllowOrigin::any()
+    } else {
+        let patterns: Vec<OriginPattern> = config.allow_origins.iter()
+            .filter_map(|origin| OriginPattern::parse(origin).map_err(|e| errors.push(e)).ok())
+            .collect();
+        AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+            origin.to_str().is_ok_and(|origin| patterns.iter().any(|pattern| pattern.matches(origin)))
+        })
+    };
+
+    let methods = if is_any(&config.allow_methods) {
+        AllowMethods::any()
+    } else {
+        AllowMethods::list(parse_all(&config.allow_methods, "allow_methods", |m| m.to_ascii_uppercase().parse::<Method>().ok(), &mut errors))
+    };
+    let headers = if is_any(&config.allow_headers) {
+        AllowHeaders::any()
+    } else {
+        AllowHeaders::list(parse_all(&config.allow_headers, "allow_headers", |h| h.parse::<HeaderName>().ok(), &mut errors))
+    };
+    let expose = if is_any(&config.expose_headers) {
+        ExposeHeaders::any()
+    } else {
+        ExposeHeaders::list(parse_all(&config.expose_headers, "expose_headers", |h| h.parse::<HeaderName>().ok(), &mut errors))
+    };
+
+    if config.allow_credentials {
+        // 浏览器不接受携带凭证时的通配响应，tower-http 也会直接 panic
+        for (name, values) in [("allow_origins", &config.allow_origins), ("allow_methods", &config.allow_methods),
+            ("allow_headers", &config.allow_headers), ("expose_headers", &config.expose_headers)] {
+            if is_any(values) {
+                errors.push(format!("cors.allow_credentials 开启时 cors.{name} 不能使用 *"));
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut layer = CorsLayer::new()
+        .allow_origin(origin)
+        .allow_methods(methods)
+        .allow_headers(headers)
+        .expose_headers(expose)
+        .allow_credentials(config.allow_credentials);
+    if let Some(max_age) = config.max_age {
+        layer = layer.max_age(Duration::from_secs(max_age));
+    }
+    Ok(layer)
+}
+
+fn parse_all<T>(values: &[String], name: &str, parse: impl Fn(&str) -> Option<T>, errors: &mut Vec<String>) -> Vec<T> {
+    values.iter()
+        .filter_map(|value| {
+            let parsed = parse(value.trim());
+            if parsed.is_none() {
+                errors.push(format!("cors.{name} 中的 {value} 无效"));
+            }
+            parsed
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_origin_pattern() {
+        let exact = OriginPattern::parse("https://Example.com/").unwrap();
+        assert_eq!(exact, OriginPattern::Exact("https://example.com".to_owned()));
+        assert!(exact.matches("https://example.com"));
+        assert!(!exact.matches("http://example.com"));
+        assert!(!exact.matches("https://a.example.com"));
+
+        let wildcard = OriginPattern::parse("https://*.example.com:8443").unwrap();
+        assert!(wildcard.matches("https://a.example.com:8443"));
+        assert!(wildcard.matches("https://a.b.example.com:8443"));
+        assert!(!wildcard.matches("https://example.com:8443"));
+        assert!(!wildcard.matches("https://a.example.com"));
+        assert!(!wildcard.matches("https://evil.com/.example.com:8443"));
+        assert!(!wildcard.matches("https://aexample.com:8443"));
+
+        for invalid in ["example.com", "https://", "https://*", "https://a.*.com", "https://example.com/path"] {
+            assert!(OriginPattern::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_layer_validation() {
+        assert!(layer(&CorsConfig::default()).is_ok());
+
+        let config = CorsConfig { allow_credentials: true, ..Default::default() };
+        assert_eq!(layer(&config).unwrap_err().len(), 3);
+
+        let config = CorsConfig {
+            allow_origins: vec!["https://*.example.com".to_owned()],
+            allow_methods: vec!["get".to_owned(), "POST".to_owned()],
+            allow_headers: vec!["authorization".to_owned(), "content-type".to_owned()],
+            expose_headers: vec!["bad header".to_owned()],
+            allow_credentials: true,
+            max_age: Some(600),
+        };
+        assert_eq!(layer(&config).unwrap_err(), vec!["cors.expose_headers 中的 bad header 无效".to_owned()]);
+    }
+}
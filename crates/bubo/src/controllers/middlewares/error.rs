@@ -0,0 +1,90 @@
+use axum::{body::Body, extract::{Request, State}, http::{header::{CONTENT_LENGTH, CONTENT_TYPE}, HeaderValue}, middleware::Next, response::Response};
+use tower_http::request_id::RequestId;
+
+use crate::{config::ErrorMode, utils::error::ErrorDetails, views::response::ProblemDetails};
+
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+///
+/// 按 server.error_mode 转换错误响应，legacy 模式不添加该中间件。
+/// 只处理带有 ErrorDetails 的响应，保留其他响应头，例如限流的 Retry-After
+///
+pub async fn error_mode(State(mode): State<ErrorMode>, req: Request, next: Next) -> Response {
+    let instance = req.uri().path().to_owned();
+    let request_id = req.extensions().get::<RequestId>()
+        .and_then(|id| id.header_value().to_str().ok())
+        .map(ToOwned::to_owned);
+    let mut response = next.run(req).await;
+    let Some(details) = response.extensions_mut().remove::<ErrorDetails>() else {
+        return response;
+    };
+    let (mut parts, _) = response.into_parts();
+    parts.status = details.status;
+    parts.headers.remove(CONTENT_LENGTH);
+    let body = match mode {
+        ErrorMode::Problem => {
+            parts.headers.insert(CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+            serde_json::to_vec(&problem(details, instance, request_id))
+        }
+        _ => serde_json::to_vec(&details.body),
+    };
+    Response::from_parts(parts, Body::from(body.unwrap_or_default()))
+}
+
+fn problem(details: ErrorDetails, instance: String, request_id: Option<String>) -> ProblemDetails {
+    ProblemDetails {
+        type_: "about:blank".to_owned(),
+        title: details.status.canonical_reason().unwrap_or_default().to_owned(),
+        status: details.status.as_u16(),
+        detail: details.body.error_message,
+        instance,
+        error_code: details.body.error_code,
+        request_id,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::to_bytes, http::StatusCode, middleware, routing::get, Router};
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::utils::error::{BuboError, BusinessErrorCode};
+    use pretty_assertions::assert_eq;
+
+    async fn forbidden() -> Result<(), BuboError> {
+        Err(BuboError::business_error(BusinessErrorCode::Forbidden, "forbidden"))
+    }
+
+    async fn call(mode: Option<ErrorMode>) -> (StatusCode, Option<HeaderValue>, Value) {
+        let mut app = Router::new().route("/forbidden", get(forbidden));
+        if let Some(mode) = mode {
+            app = app.layer(middleware::from_fn_with_state(mode, error_mode));
+        }
+        let response = app.oneshot(Request::get("/forbidden").body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let content_type = response.headers().get(CONTENT_TYPE).cloned();
+        let body = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        (status, content_type, body)
+    }
+
+    #[tokio::test]
+    async fn test_error_mode() {
+        let legacy = json!({"status": false, "error_code": BusinessErrorCode::Forbidden as usize, "error_message": "forbidden"});
+        assert_eq!(call(None).await, (StatusCode::OK, Some(HeaderValue::from_static("application/json")), legacy.clone()));
+        assert_eq!(call(Some(ErrorMode::Status)).await, (StatusCode::FORBIDDEN, Some(HeaderValue::from_static("application/json")), legacy));
+
+        let (status, content_type, body) = call(Some(ErrorMode::Problem)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(content_type, Some(HeaderValue::from_static(PROBLEM_JSON)));
+        assert_eq!(body, json!({
+            "type": "about:blank",
+            "title": "Forbidden",
+            "status": 403,
+            "detail": "forbidden",
+            "instance": "/forbidden",
+            "error_code": BusinessErrorCode::Forbidden as usize,
+        }));
+    }
+}
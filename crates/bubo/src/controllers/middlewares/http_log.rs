@@ -0,0 +1,309 @@
+use std::{collections::HashSet, sync::Arc};
+
+use axum::{body::{to_bytes, Body, Bytes, HttpBody}, extract::{Request, State}, http::{header::{CONTENT_LENGTH, CONTENT_TYPE}, HeaderMap, HeaderName}, middleware::Next, response::{IntoResponse, Response}};
+use serde_json::Value;
+use tracing::{debug, enabled, Level};
+
+use crate::{config::HttpLogConfig, utils::error::{BuboError, BusinessErrorCode, SystemErrorCode}};
+
+const TARGET: &str = "bubo::http";
+const REDACTED: &str = "***";
+
+///
+/// 不记录该路由的请求体和响应体，由 routing::Endpoint::skip_body_log 添加到响应，
+/// 也可以在 Hooks::router 注册的路由中手动添加到响应的 extensions
+///
+#[derive(Debug, Clone, Copy)]
+pub struct SkipBodyLog;
+
+///
+/// 请求日志和脱敏规则，由 logger.http 生成
+///
+#[derive(Debug, Clone)]
+pub struct HttpLogger {
+    headers: bool,
+    body: bool,
+    max_body_size: usize,
+    // 超过该大小的请求体不读取，和 server.body_limit 相同
+    body_limit: usize,
+    redact_fields: Vec<String>,
+    redact_headers: HashSet<HeaderName>,
+}
+
+impl HttpLogger {
+    pub fn new(config: &HttpLogConfig, body_limit: usize) -> Self {
+        Self {
+            headers: config.headers,
+            body: config.body,
+            max_body_size: config.max_body_size,
+            body_limit,
+            redact_fields: config.redact_fields.iter().map(|field| field.to_ascii_lowercase()).collect(),
+            redact_headers: config.redact_headers.iter().filter_map(|header| HeaderName::try_from(header.as_str()).ok()).collect(),
+        }
+    }
+
+    fn redact_field(&self, name: &str) -> bool {
+        let name = name.to_ascii_lowercase();
+        self.redact_fields.iter().any(|field| name.contains(field.as_str()))
+    }
+
+    ///
+    /// `name: value` 格式的请求头，敏感的请求头替换为 `***`
+    ///
+    pub fn redact_headers(&self, headers: &HeaderMap) -> String {
+        headers.iter()
+            .map(|(name, value)| {
+                let value = if self.redact_headers.contains(name) { REDACTED } else { value.to_str().unwrap_or("<binary>") };
+                format!("{name}: {value}")
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    ///
+    /// 递归替换 JSON 中的敏感字段
+    ///
+    pub fn redact_json(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (name, value) in map.iter_mut() {
+                    if self.redact_field(name) {
+                        *value = Value::String(REDACTED.to_owned());
+                    } else {
+                        self.redact_json(value);
+                    }
+                }
+            }
+            Value::Array(values) => values.iter_mut().for_each(|value| self.redact_json(value)),
+            _ => {}
+        }
+    }
+
+    ///
+    /// 替换 query 参数或表单中的敏感字段
+    ///
+    pub fn redact_query(&self, query: &str) -> String {
+        query.split('&')
+            .map(|pair| match pair.split_once('=') {
+                Some((name, _)) if self.redact_field(name) => format!("{name}={REDACTED}"),
+                _ => pair.to_owned(),
+            })
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    ///
+    /// 脱敏并截断后的请求体或响应体，无法解析的内容只记录大小
+    ///
+    pub fn redact_body(&self, content_type: BodyKind, bytes: &Bytes) -> String {
+        let text = match content_type {
+            BodyKind::Json => match serde_json::from_slice::<Value>(bytes) {
+                Ok(mut value) => {
+                    self.redact_json(&mut value);
+                    value.to_string()
+                }
+                Err(_) => return format!("({} bytes)", bytes.len()),
+            },
+            BodyKind::Form => match std::str::from_utf8(bytes) {
+                Ok(form) => self.redact_query(form),
+                Err(_) => return format!("({} bytes)", bytes.len()),
+            },
+        };
+        self.truncate(text)
+    }
+
+    fn truncate(&self, text: String) -> String {
+        if text.len() <= self.max_body_size {
+            return text;
+        }
+        let mut end = self.max_body_size;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        format!("{}...({} bytes)", &text[..end], text.len())
+    }
+}
+
+///
+/// 记录内容的请求体、响应体类型，其他类型只记录大小
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyKind {
+    Json,
+    Form,
+}
+
+impl BodyKind {
+    fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let content_type = headers.get(CONTENT_TYPE)?.to_str().ok()?;
+        let mime = content_type.split(';').next().unwrap_or_default().trim();
+        if mime == "application/json" || mime.ends_with("+json") {
+            Some(BodyKind::Json)
+        } else if mime == "application/x-www-form-urlencoded" {
+            Some(BodyKind::Form)
+        } else {
+            None
+        }
+    }
+}
+
+///
+/// 在 debug 级别记录脱敏后的请求和响应，请求体在响应之后记录，以便路由关闭请求体日志。
+/// 只读取有 Content-Length 且不超过 body_limit 的请求体和大小确定的响应体，不影响流式响应
+///
+pub async fn log(State(logger): State<Arc<HttpLogger>>, req: Request, next: Next) -> Response {
+    if !enabled!(target: TARGET, Level::DEBUG) {
+        return next.run(req).await;
+    }
+    let uri = match req.uri().query() {
+        Some(query) => format!("{}?{}", req.uri().path(), logger.redact_query(query)),
+        None => req.uri().path().to_owned(),
+    };
+    if logger.headers {
+        debug!(target: TARGET, "request {} {} headers: {}", req.method(), uri, logger.redact_headers(req.headers()));
+    } else {
+        debug!(target: TARGET, "request {} {}", req.method(), uri);
+    }
+
+    let request_kind = BodyKind::from_headers(req.headers()).filter(|_| logger.body);
+    let content_length = req.headers().get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+    let mut request_body = None;
+    let req = match (request_kind, content_length) {
+        (Some(kind), Some(length)) if length <= logger.body_limit => {
+            let (parts, body) = req.into_parts();
+            let bytes = match to_bytes(body, logger.body_limit).await {
+                Ok(bytes) => bytes,
+                Err(_) if kind == BodyKind::Json => return BuboError::from(BusinessErrorCode::JsonRejectionError).into_response(),
+                Err(_) => return BuboError::from(BusinessErrorCode::FormRejectionError).into_response(),
+            };
+            request_body = Some((kind, bytes.clone()));
+            Request::from_parts(parts, Body::from(bytes))
+        }
+        _ => req,
+    };
+
+    let response = next.run(req).await;
+    let skip_body = response.extensions().get::<SkipBodyLog>().is_some();
+    if let Some((kind, bytes)) = request_body.filter(|_| !skip_body) {
+        debug!(target: TARGET, "request body: {}", logger.redact_body(kind, &bytes));
+    }
+    if logger.headers {
+        debug!(target: TARGET, "response {} headers: {}", response.status(), logger.redact_headers(response.headers()));
+    } else {
+        debug!(target: TARGET, "response {}", response.status());
+    }
+
+    let response_kind = BodyKind::from_headers(response.headers()).filter(|_| logger.body && !skip_body);
+    let exact_size = response.body().size_hint().exact().is_some();
+    match response_kind {
+        Some(kind) if exact_size => {
+            let (parts, body) = response.into_parts();
+            let Ok(bytes) = to_bytes(body, usize::MAX).await else {
+                return BuboError::from(SystemErrorCode::InternalServerError).into_response();
+            };
+            debug!(target: TARGET, "response body: {}", logger.redact_body(kind, &bytes));
+            Response::from_parts(parts, Body::from(bytes))
+        }
+        _ => response,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, sync::Mutex};
+
+    use axum::{http::HeaderValue, middleware, routing::post, Json, Router};
+    use serde_json::json;
+    use tower::ServiceExt;
+    use tracing_subscriber::fmt::MakeWriter;
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn logger() -> HttpLogger {
+        HttpLogger::new(&HttpLogConfig { max_body_size: 64, ..Default::default() }, 1024)
+    }
+
+    #[test]
+    fn test_redact() {
+        let logger = logger();
+        let mut value = json!({"username": "admin", "password": "123", "data": [{"access_token": "a", "refresh_token": "b"}], "phone_number": 1});
+        logger.redact_json(&mut value);
+        assert_eq!(value, json!({"username": "admin", "password": "***", "data": [{"access_token": "***", "refresh_token": "***"}], "phone_number": "***"}));
+        assert_eq!(logger.redact_query("key=a&signature=b&Token=c&flag"), "key=a&signature=b&Token=***&flag");
+
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", HeaderValue::from_static("Bearer abc"));
+        headers.insert("cookie", HeaderValue::from_static("session=abc"));
+        headers.insert("accept", HeaderValue::from_static("*/*"));
+        assert_eq!(logger.redact_headers(&headers), "authorization: ***, cookie: ***, accept: */*");
+
+        let body = Bytes::from(json!({"remark": "长".repeat(30)}).to_string());
+        let text = logger.redact_body(BodyKind::Json, &body);
+        assert!(text.ends_with(&format!("...({} bytes)", body.len())));
+        assert_eq!(logger.redact_body(BodyKind::Json, &Bytes::from_static(b"not json")), "(8 bytes)");
+    }
+
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl MakeWriter<'_> for Capture {
+        type Writer = Capture;
+
+        fn make_writer(&self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    async fn login(Json(_params): Json<Value>) -> Json<Value> {
+        Json(json!({"access_token": "secret-access", "expires_in": 3600}))
+    }
+
+    #[tokio::test]
+    async fn test_log() {
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::fmt().with_max_level(Level::DEBUG).with_ansi(false).with_writer(capture.clone()).finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let skipped = post(login).route_layer(middleware::map_response(|mut response: Response| async move {
+            response.extensions_mut().insert(SkipBodyLog);
+            response
+        }));
+        let app = Router::new()
+            .route("/login", post(login))
+            .route("/skipped", skipped)
+            .layer(middleware::from_fn_with_state(Arc::new(logger()), log));
+        for uri in ["/login", "/skipped"] {
+            let body = json!({"username": "admin", "password": "secret-password"}).to_string();
+            let request = Request::post(uri)
+                .header(CONTENT_TYPE, "application/json")
+                .header(CONTENT_LENGTH, body.len())
+                .header("authorization", "Bearer secret-bearer")
+                .body(Body::from(body))
+                .unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            let body: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+            assert_eq!(body["access_token"], "secret-access");
+        }
+
+        let output = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        assert!(!output.contains("secret"), "{output}");
+        assert!(output.contains(r#"request body: {"password":"***","username":"admin"}"#), "{output}");
+        assert!(output.contains(r#"response body: {"access_token":"***","expires_in":3600}"#), "{output}");
+        assert!(output.contains("authorization: ***"), "{output}");
+        assert_eq!(output.matches("request body").count(), 1);
+    }
+}
@@ -0,0 +1,24 @@
+use std::sync::Arc;
+
+use axum::{extract::{Request, State}, http::{header::{ACCEPT_LANGUAGE, CONTENT_LANGUAGE}, HeaderValue}, middleware::Next, response::Response};
+
+use crate::i18n::{self, I18n};
+
+///
+/// 按 Accept-Language 选择语言，不支持时使用 i18n.default_locale，并设置响应头 Content-Language。
+/// 登录用户有语言偏好时由 auth 中间件改用用户的语言
+///
+pub async fn locale(State(i18n): State<Arc<I18n>>, req: Request, next: Next) -> Response {
+    let locale = req.headers().get(ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| i18n.negotiate(value))
+        .unwrap_or(i18n.default_locale())
+        .to_owned();
+    let mut response = i18n::scope(i18n, locale.clone(), next.run(req)).await;
+    if !response.headers().contains_key(CONTENT_LANGUAGE) {
+        if let Ok(value) = HeaderValue::from_str(&locale) {
+            response.headers_mut().insert(CONTENT_LANGUAGE, value);
+        }
+    }
+    response
+}
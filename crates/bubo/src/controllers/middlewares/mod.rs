@@ -1 +1,6 @@
-pub mod auth;
\ No newline at end of file
+pub mod auth;
+pub mod cors;
+pub mod error;
+pub mod http_log;
+pub mod i18n;
+pub mod rate_limit;
@@ -0,0 +1,258 @@
+use std::{net::{IpAddr, SocketAddr}, sync::Arc};
+
+use axum::{extract::{ConnectInfo, MatchedPath, Request, State}, http::{header::RETRY_AFTER, HeaderMap, HeaderValue}, middleware::Next, response::{IntoResponse, Response}};
+use fred::prelude::LuaInterface;
+use ipnet::IpNet;
+use tracing::{error, warn};
+
+use crate::{config::{RateLimitKey, RateLimitRule}, server::AppState, utils::error::{BuboError, BuboResult, BusinessErrorCode}};
+
+use super::auth::AuthUser;
+
+// 滑动窗口计数，使用 redis 服务器时间，多实例之间不受本地时钟影响
+// 返回 [是否允许, 剩余次数, 需要等待的毫秒数]
+const SLIDING_WINDOW_SCRIPT: &str = r#"
+local key = KEYS[1]
+local limit = tonumber(ARGV[1])
+local window = tonumber(ARGV[2])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+redis.call('ZREMRANGEBYSCORE', key, 0, now - window)
+local count = redis.call('ZCARD', key)
+if count < limit then
+    redis.call('ZADD', key, now, ARGV[3])
+    redis.call('PEXPIRE', key, window)
+    return {1, limit - count - 1, 0}
+end
+local oldest = redis.call('ZRANGE', key, 0, 0, 'WITHSCORES')
+return {0, 0, tonumber(oldest[2]) + window - now}
+"#;
+
+///
+/// 限流器，对应配置 rate_limit.groups 中的一个分组，分组不存在或未启用限流时直接放行
+///
+/// ```ignore
+/// .route("/auth/login/account", post(handler)
+///     .route_layer(middleware::from_fn_with_state(RateLimiter::new(&state, "login"), rate_limit::limit))
+/// )
+/// ```
+///
+/// 按 user 限流时需要放在 auth 中间件内层（先于 auth 调用 route_layer），否则取不到登录用户
+///
+#[derive(Clone)]
+pub struct RateLimiter {
+    state: AppState,
+    group: String,
+    rule: Option<RateLimitRule>,
+    // 开启 trust_proxy 时的可信代理
+    trusted_proxies: Option<Arc<[IpNet]>>,
+}
+
+impl RateLimiter {
+    pub fn new(state: &AppState, group: impl Into<String>) -> Self {
+        let group = group.into();
+        let config = &state.config.rate_limit;
+        let rule = config.enable.then(|| config.groups.get(&group).cloned()).flatten();
+        if config.enable && rule.is_none() {
+            warn!("rate limit group {} is not configured", group);
+        }
+        let trusted_proxies = config.trust_proxy.then(|| config.trusted_proxy_nets().into());
+        Self { state: state.clone(), group, rule, trusted_proxies }
+    }
+
+    ///
+    /// 计数并返回需要等待的毫秒数，None 表示允许请求
+    ///
+    async fn check(&self, rule: &RateLimitRule, subject: &str) -> BuboResult<Option<u64>> {
+        let key = format!("{}:rate-limit:{}:{}", self.state.app_name, self.group, subject);
+        let member = uuid::Uuid::new_v4().simple().to_string();
+        let window = rule.window * 1000;
+        let result: Vec<i64> = self.state.redis.next()
+            .eval(SLIDING_WINDOW_SCRIPT, key, vec![rule.limit.to_string(), window.to_string(), member])
+            .await?;
+        match result.as_slice() {
+            [1, ..] => Ok(None),
+            [_, _, wait] => Ok(Some((*wait).max(0) as u64)),
+            _ => Ok(None),
+        }
+    }
+}
+
+///
+/// 限流中间件，超出限制时返回 TooManyRequests 错误和 Retry-After 头
+///
+pub async fn limit(State(limiter): State<RateLimiter>, req: Request, next: Next) -> Response {
+    let Some(rule) = &limiter.rule else {
+        return next.run(req).await;
+    };
+    let subject = subject(rule.key, &req, limiter.trusted_proxies.as_deref());
+    match limiter.check(rule, &subject).await {
+        Ok(Some(wait)) => {
+            warn!("rate limited: group={} subject={}", limiter.group, subject);
+            too_many_requests(wait)
+        }
+        Ok(None) => next.run(req).await,
+        // redis 不可用时放行，不影响正常请求，错误在转为 BuboError 时已计入 redis_errors_total
+        Err(e) => {
+            error!("rate limit check error: {}", e);
+            next.run(req).await
+        }
+    }
+}
+
+fn too_many_requests(wait_millis: u64) -> Response {
+    let retry_after = wait_millis.div_ceil(1000).max(1);
+    let mut response = BuboError::from(BusinessErrorCode::TooManyRequests).into_response();
+    response.headers_mut().insert(RETRY_AFTER, HeaderValue::from(retry_after));
+    response
+}
+
+///
+/// 计数对象，按 user 限流但未登录时使用 ip
+///
+fn subject(key: RateLimitKey, req: &Request, trusted_proxies: Option<&[IpNet]>) -> String {
+    match key {
+        RateLimitKey::Ip => format!("ip:{}", client_ip(req, trusted_proxies)),
+        RateLimitKey::User => match req.extensions().get::<AuthUser>() {
+            Some(auth_user) => format!("user:{}", auth_user.id),
+            None => format!("ip:{}", client_ip(req, trusted_proxies)),
+        },
+        RateLimitKey::Route => {
+            let path = req.extensions().get::<MatchedPath>().map_or(req.uri().path(), |path| path.as_str());
+            format!("route:{}:{}", req.method(), path)
+        }
+    }
+}
+
+///
+/// 客户端 IP，trusted_proxies 为 None 时使用连接地址。
+/// 连接来自可信代理时从右往左取 X-Forwarded-For 中第一个不是可信代理的地址，左侧的地址客户端可以任意伪造。
+/// Unix socket 没有连接地址，连接只能来自本机代理，视为可信，没有 X-Forwarded-For 时返回 unknown
+///
+pub fn client_ip(req: &Request, trusted_proxies: Option<&[IpNet]>) -> String {
+    let peer = req.extensions().get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| addr.ip());
+    if let Some(trusted_proxies) = trusted_proxies {
+        let is_trusted = |ip: &IpAddr| trusted_proxies.iter().any(|net| net.contains(ip));
+        if peer.as_ref().map_or(true, is_trusted) {
+            if let Some(ip) = forwarded_for(req.headers(), is_trusted) {
+                return ip.to_string();
+            }
+        }
+    }
+    peer.map_or_else(|| "unknown".to_owned(), |ip| ip.to_string())
+}
+
+fn forwarded_for(headers: &HeaderMap, is_trusted: impl Fn(&IpAddr) -> bool) -> Option<IpAddr> {
+    // 多个 X-Forwarded-For 头按顺序拼接，无法解析的头或地址记为 None
+    let mut ips = Vec::new();
+    for value in headers.get_all("x-forwarded-for") {
+        match value.to_str() {
+            Ok(value) => ips.extend(value.split(',').map(|ip| ip.trim().parse::<IpAddr>().ok())),
+            Err(_) => ips.push(None),
+        }
+    }
+    // 从右往左遇到无法解析的地址时停止，使用最后一个有效的地址，全部是可信代理时使用最左侧的地址
+    let mut last = None;
+    for ip in ips.into_iter().rev() {
+        let Some(ip) = ip else {
+            break;
+        };
+        if !is_trusted(&ip) {
+            return Some(ip);
+        }
+        last = Some(ip);
+    }
+    last
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use axum::{body::Body, http::StatusCode, middleware, routing::post, Router};
+    use metrics_exporter_prometheus::PrometheusBuilder;
+    use tower::ServiceExt;
+
+    use crate::{server::Hooks, testing::{self, MockRedis}};
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn request(peer: Option<[u8; 4]>, forwarded_for: &str) -> Request {
+        let mut req = Request::builder().uri("/auth/login").header("x-forwarded-for", forwarded_for).body(Body::empty()).unwrap();
+        if let Some(peer) = peer {
+            req.extensions_mut().insert(ConnectInfo(SocketAddr::from((peer, 5000))));
+        }
+        req
+    }
+
+    #[test]
+    fn test_subject() {
+        let trusted: Vec<IpNet> = vec!["127.0.0.0/8".parse().unwrap()];
+        let mut req = request(Some([127, 0, 0, 1]), "10.0.0.1, 10.0.0.2");
+        assert_eq!(subject(RateLimitKey::Ip, &req, None), "ip:127.0.0.1");
+        assert_eq!(subject(RateLimitKey::Ip, &req, Some(&trusted)), "ip:10.0.0.2");
+        assert_eq!(subject(RateLimitKey::User, &req, None), "ip:127.0.0.1");
+        assert_eq!(subject(RateLimitKey::Route, &req, None), "route:GET:/auth/login");
+
+        req.extensions_mut().insert(AuthUser::new(7, "u", "u", false, 0, 0, HashSet::new(), HashSet::new(), HashSet::new()));
+        assert_eq!(subject(RateLimitKey::User, &req, None), "user:7");
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let trusted: Vec<IpNet> = vec!["127.0.0.0/8".parse().unwrap(), "10.0.0.0/8".parse().unwrap()];
+        // 代理在右侧追加真实地址，客户端伪造的左侧地址不影响计数
+        assert_eq!(client_ip(&request(Some([127, 0, 0, 1]), "203.0.113.9"), Some(&trusted)), "203.0.113.9");
+        assert_eq!(client_ip(&request(Some([127, 0, 0, 1]), "1.2.3.4, 203.0.113.9"), Some(&trusted)), "203.0.113.9");
+        assert_eq!(client_ip(&request(Some([127, 0, 0, 1]), "1.2.3.4, 203.0.113.9, 10.0.0.2"), Some(&trusted)), "203.0.113.9");
+        // 不是来自可信代理的连接忽略 X-Forwarded-For
+        assert_eq!(client_ip(&request(Some([203, 0, 113, 9]), "1.2.3.4"), Some(&trusted)), "203.0.113.9");
+        // 左侧无法解析的地址不影响右侧的地址
+        assert_eq!(client_ip(&request(Some([127, 0, 0, 1]), "not-an-ip, 203.0.113.9"), Some(&trusted)), "203.0.113.9");
+        assert_eq!(client_ip(&request(Some([127, 0, 0, 1]), "203.0.113.9, not-an-ip, 10.0.0.2"), Some(&trusted)), "10.0.0.2");
+        assert_eq!(client_ip(&request(Some([127, 0, 0, 1]), "203.0.113.9, not-an-ip"), Some(&trusted)), "127.0.0.1");
+        assert_eq!(client_ip(&request(Some([127, 0, 0, 1]), "10.0.0.3, 10.0.0.2"), Some(&trusted)), "10.0.0.3");
+        // unix socket
+        assert_eq!(client_ip(&request(None, "203.0.113.9"), Some(&trusted)), "203.0.113.9");
+        assert_eq!(client_ip(&request(None, "203.0.113.9"), None), "unknown");
+    }
+
+    struct TestHooks;
+
+    impl Hooks for TestHooks {
+        fn app_name() -> &'static str {
+            "bubo-test"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_redis_error() {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        let _guard = metrics::set_default_local_recorder(&recorder);
+
+        let mut config = testing::test_config();
+        config.rate_limit.enable = true;
+        config.rate_limit.groups.insert("login".to_owned(), RateLimitRule { key: RateLimitKey::Ip, limit: 1, window: 60 });
+        // MockRedis 不支持 EVAL，限流检查失败时放行并计数
+        let state = testing::mock_state::<TestHooks>(config, &Arc::new(MockRedis::default())).await;
+        let app = Router::new()
+            .route("/login", post(|| async {}))
+            .layer(middleware::from_fn_with_state(RateLimiter::new(&state, "login"), limit));
+        for _ in 0..2 {
+            let response = app.clone().oneshot(Request::post("/login").body(Body::empty()).unwrap()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        let output = handle.render();
+        assert!(output.contains("redis_errors_total{kind=\"Unknown Error\"} 2"), "{output}");
+    }
+
+    #[test]
+    fn test_too_many_requests() {
+        let response = too_many_requests(1500);
+        assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "2");
+        let response = too_many_requests(0);
+        assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "1");
+    }
+}
@@ -1,12 +1,20 @@
+use schemars::JsonSchema;
 use serde::Deserialize;
 use validator::Validate;
 use crate::utils::{serde::to_vec_i64};
 
+pub mod assets;
+pub mod health;
+pub mod log_level;
 pub mod middlewares;
+pub mod openapi;
+pub mod routing;
+pub mod storage;
 
-#[derive(Debug, Deserialize, Validate)]
+#[derive(Debug, Deserialize, Validate, JsonSchema)]
 pub struct RemoveParams {
     #[serde(deserialize_with = "to_vec_i64")]
+    #[schemars(with = "Vec<crate::openapi::Id>")]
     #[validate(length(min = 1, max = 100))]
     pub ids: Vec<i64>,
 }
\ No newline at end of file
@@ -0,0 +1,44 @@
+use axum::{http::header::CONTENT_TYPE, response::Html, routing::get, Router};
+use bytes::Bytes;
+use serde_json::Value;
+
+use crate::config::OpenApiConfig;
+
+const VIEWER_TEMPLATE: &str = r##"<!DOCTYPE html>
+<html lang="zh-CN">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<link rel="stylesheet" href="{cdn}/swagger-ui.css">
+</head>
+<body>
+<div id="swagger-ui"></div>
+<script src="{cdn}/swagger-ui-bundle.js"></script>
+<script>
+window.ui = SwaggerUIBundle({ url: "{url}", dom_id: "#swagger-ui", persistAuthorization: true });
+</script>
+</body>
+</html>
+"##;
+
+///
+/// OpenAPI 文档和 Swagger UI 页面，文档在启动时生成
+///
+pub fn init_routes(config: &OpenApiConfig, document: Value) -> Router {
+    let title = document["info"]["title"].as_str().unwrap_or_default().to_owned();
+    let json = Bytes::from(serde_json::to_vec(&document).unwrap_or_default());
+    let mut router = Router::new().route(&config.path, get(move || async move { ([(CONTENT_TYPE, "application/json")], json) }));
+    if !config.viewer_path.is_empty() {
+        let html = VIEWER_TEMPLATE
+            .replace("{title}", &escape(&title))
+            .replace("{cdn}", &escape(config.viewer_cdn.trim_end_matches('/')))
+            .replace("{url}", &escape(&config.path));
+        router = router.route(&config.viewer_path, get(move || async move { Html(html) }));
+    }
+    router
+}
+
+fn escape(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,404 @@
+use std::{collections::BTreeSet, fmt::Display, sync::Arc};
+
+use axum::{extract::DefaultBodyLimit, handler::Handler, http::Method, middleware, response::Response, routing::{self, MethodRouter}, Router};
+use schemars::{gen::SchemaGenerator, JsonSchema};
+
+use crate::{openapi::{BodyDoc, ResponseDoc, RouteDoc}, server::AppState, views::response::{ApiResponse, PageResponse}};
+
+use super::middlewares::{auth, http_log::SkipBodyLog, rate_limit::{self, RateLimiter}};
+
+type LayerFn = Arc<dyn Fn(MethodRouter<AppState>, &AppState) -> MethodRouter<AppState> + Send + Sync>;
+
+///
+/// 路由的访问控制
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Access {
+    // 无需登录
+    Public,
+    // 需要 access token
+    Auth,
+    // 需要 refresh token，用于刷新令牌
+    Refresh,
+    // 需要登录和权限，管理员不检查权限
+    Permission(String),
+}
+
+impl Display for Access {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Access::Public => write!(f, "public"),
+            Access::Auth => write!(f, "auth"),
+            Access::Refresh => write!(f, "refresh"),
+            Access::Permission(_) => write!(f, "permission"),
+        }
+    }
+}
+
+///
+/// 已注册路由的信息，用于命令行 `routes`、OpenAPI 文档和菜单权限校验
+///
+#[derive(Debug, Clone)]
+pub struct RouteInfo {
+    pub method: String,
+    pub path: String,
+    pub access: Access,
+    pub doc: RouteDoc,
+}
+
+impl RouteInfo {
+    ///
+    /// 是否需要登录
+    ///
+    pub fn auth(&self) -> bool {
+        self.access != Access::Public
+    }
+
+    pub fn permission(&self) -> Option<&str> {
+        match &self.access {
+            Access::Permission(permission) => Some(permission),
+            _ => None,
+        }
+    }
+}
+
+///
+/// 一个请求方法对应的处理函数，以及访问控制、中间件和接口文档
+///
+#[derive(Clone)]
+pub struct Endpoint {
+    method: Method,
+    router: MethodRouter<AppState>,
+    access: Access,
+    layers: Vec<LayerFn>,
+    skip_body_log: bool,
+    doc: RouteDoc,
+}
+
+macro_rules! endpoint_fn {
+    ($name:ident, $method:ident) => {
+        pub fn $name<H, T>(handler: H) -> Endpoint
+        where
+            H: Handler<T, AppState>,
+            T: 'static,
+        {
+            Endpoint::new(Method::$method, routing::$name(handler))
+        }
+    };
+}
+
+endpoint_fn!(get, GET);
+endpoint_fn!(post, POST);
+endpoint_fn!(put, PUT);
+endpoint_fn!(patch, PATCH);
+endpoint_fn!(delete, DELETE);
+
+///
+/// 需要登录，继续调用 permission 时还需要权限
+///
+/// ```ignore
+/// Routes::new().route("/system/user/page", secured(get(user_page)).permission("system:user:page"))
+/// ```
+///
+pub fn secured(endpoint: Endpoint) -> Endpoint {
+    Endpoint { access: Access::Auth, ..endpoint }
+}
+
+///
+/// 需要 refresh token
+///
+pub fn refresh(endpoint: Endpoint) -> Endpoint {
+    Endpoint { access: Access::Refresh, ..endpoint }
+}
+
+impl Endpoint {
+    fn new(method: Method, router: MethodRouter<AppState>) -> Self {
+        Self { method, router, access: Access::Public, layers: Vec::new(), skip_body_log: false, doc: RouteDoc::default() }
+    }
+
+    ///
+    /// 需要登录和权限
+    ///
+    pub fn permission(mut self, permission: impl Into<String>) -> Self {
+        self.access = Access::Permission(permission.into());
+        self
+    }
+
+    ///
+    /// 按 rate_limit.groups 中的分组限流，在登录验证之后执行，可以按用户限流
+    ///
+    pub fn rate_limit(self, group: impl Into<String>) -> Self {
+        let group = group.into();
+        self.with_layer(move |router, state| {
+            router.route_layer(middleware::from_fn_with_state(RateLimiter::new(state, group.clone()), rate_limit::limit))
+        })
+    }
+
+    ///
+    /// 覆盖 server.body_limit，例如文件上传接口，按解压后的大小计算
+    ///
+    pub fn body_limit(self, bytes: usize) -> Self {
+        self.with_layer(move |router, _| router.layer(DefaultBodyLimit::max(bytes)))
+    }
+
+    ///
+    /// 不在请求日志中记录该路由的请求体和响应体，例如返回大量数据或包含无法脱敏的敏感内容的接口，
+    /// 登录、权限验证和限流拒绝请求时同样不记录
+    ///
+    pub fn skip_body_log(mut self) -> Self {
+        self.skip_body_log = true;
+        self
+    }
+
+    ///
+    /// 添加需要 AppState 的中间件，在登录和权限验证之后执行
+    ///
+    pub fn with_layer<F>(mut self, layer: F) -> Self
+    where
+        F: Fn(MethodRouter<AppState>, &AppState) -> MethodRouter<AppState> + Send + Sync + 'static,
+    {
+        self.layers.push(Arc::new(layer));
+        self
+    }
+
+    pub fn summary(mut self, summary: impl Into<String>) -> Self {
+        self.doc.summary = Some(summary.into());
+        self
+    }
+
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.doc.tag = Some(tag.into());
+        self
+    }
+
+    ///
+    /// query 参数
+    ///
+    pub fn query<T: JsonSchema>(mut self) -> Self {
+        self.doc.query = Some(T::json_schema);
+        self
+    }
+
+    ///
+    /// JSON 请求体
+    ///
+    pub fn json<T: JsonSchema>(mut self) -> Self {
+        self.doc.body = Some(BodyDoc::Json(SchemaGenerator::subschema_for::<T>));
+        self
+    }
+
+    ///
+    /// multipart 文件上传
+    ///
+    pub fn multipart(mut self) -> Self {
+        self.doc.body = Some(BodyDoc::Multipart);
+        self
+    }
+
+    ///
+    /// 响应 ApiResponse<T>
+    ///
+    pub fn response<T: JsonSchema>(mut self) -> Self {
+        self.doc.response = ResponseDoc::Schema(SchemaGenerator::subschema_for::<ApiResponse<T>>);
+        self
+    }
+
+    ///
+    /// 分页响应 PageResponse<T>
+    ///
+    pub fn page<T: JsonSchema>(mut self) -> Self {
+        self.doc.response = ResponseDoc::Schema(SchemaGenerator::subschema_for::<PageResponse<T>>);
+        self
+    }
+
+    ///
+    /// 响应为 T 本身
+    ///
+    pub fn raw_response<T: JsonSchema>(mut self) -> Self {
+        self.doc.response = ResponseDoc::Schema(SchemaGenerator::subschema_for::<T>);
+        self
+    }
+
+    ///
+    /// 按访问控制添加登录和权限中间件
+    ///
+    fn into_method_router(self, state: &AppState) -> MethodRouter<AppState> {
+        let mut router = self.router;
+        for layer in &self.layers {
+            router = layer(router, state);
+        }
+        router = match self.access {
+            Access::Public => router,
+            Access::Auth => router.route_layer(middleware::from_fn_with_state(state.clone(), auth::auth)),
+            Access::Refresh => router.route_layer(middleware::from_fn_with_state(state.clone(), auth::refresh)),
+            Access::Permission(permission) => router
+                .route_layer(middleware::from_fn_with_state(Arc::<str>::from(permission), auth::require_permission))
+                .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth)),
+        };
+        // 放在最外层，请求被拒绝时的响应同样带有标记
+        if self.skip_body_log {
+            router = router.route_layer(middleware::map_response(|mut response: Response| async move {
+                response.extensions_mut().insert(SkipBodyLog);
+                response
+            }));
+        }
+        router
+    }
+}
+
+///
+/// 路由注册表，记录每个路由的请求方法、路径、访问控制和接口文档，
+/// 由 Hooks::routes 返回，生成 axum 路由和 RouteRegistry
+///
+#[derive(Clone, Default)]
+pub struct Routes {
+    endpoints: Vec<(String, Endpoint)>,
+}
+
+impl Routes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn route(mut self, path: &str, endpoint: Endpoint) -> Self {
+        self.endpoints.push((path.to_owned(), endpoint));
+        self
+    }
+
+    pub fn merge(mut self, other: Routes) -> Self {
+        self.endpoints.extend(other.endpoints);
+        self
+    }
+
+    ///
+    /// other 中的路由加上路径前缀 prefix
+    ///
+    pub fn nest(mut self, prefix: &str, other: Routes) -> Self {
+        let prefix = prefix.trim_end_matches('/');
+        self.endpoints.extend(other.endpoints.into_iter().map(|(path, endpoint)| (format!("{prefix}{path}"), endpoint)));
+        self
+    }
+
+    pub fn registry(&self) -> RouteRegistry {
+        let routes = self.endpoints.iter()
+            .map(|(path, endpoint)| RouteInfo {
+                method: endpoint.method.to_string(),
+                path: path.clone(),
+                access: endpoint.access.clone(),
+                doc: endpoint.doc.clone(),
+            })
+            .collect();
+        RouteRegistry { routes }
+    }
+
+    pub fn into_router(self, state: &AppState) -> Router {
+        self.endpoints.into_iter()
+            .fold(Router::new(), |router, (path, endpoint)| router.route(&path, endpoint.into_method_router(state)))
+            .with_state(state.clone())
+    }
+}
+
+///
+/// 全部路由信息，保存在 AppState::routes
+///
+#[derive(Debug, Clone, Default)]
+pub struct RouteRegistry {
+    routes: Vec<RouteInfo>,
+}
+
+impl RouteRegistry {
+    pub fn routes(&self) -> &[RouteInfo] {
+        &self.routes
+    }
+
+    ///
+    /// 全部权限标识，用于配置菜单
+    ///
+    pub fn permissions(&self) -> BTreeSet<&str> {
+        self.routes.iter().filter_map(RouteInfo::permission).collect()
+    }
+
+    pub fn contains_permission(&self, permission: &str) -> bool {
+        self.routes.iter().any(|route| route.permission() == Some(permission))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, extract::Request, http::StatusCode};
+    use tower::ServiceExt;
+
+    use crate::{server::Hooks, testing::{self, MockRedis}, utils::error::BusinessErrorCode};
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    async fn handler() {}
+
+    struct TestHooks;
+
+    impl Hooks for TestHooks {
+        fn app_name() -> &'static str {
+            "bubo-test"
+        }
+    }
+
+    #[test]
+    fn test_registry() {
+        let system = Routes::new()
+            .route("/system/user/page", secured(get(handler)).permission("system:user:page"))
+            .route("/system/user/add", secured(post(handler)).permission("system:user:add"))
+            .route("/system/user/add", secured(get(handler)));
+        let routes = Routes::new()
+            .route("/auth/login", post(handler).rate_limit("login"))
+            .route("/auth/refresh", refresh(post(handler)))
+            .nest("/admin/", system);
+        let registry = routes.registry();
+
+        let summary: Vec<(&str, &str, String)> = registry.routes().iter()
+            .map(|route| (route.method.as_str(), route.path.as_str(), route.access.to_string()))
+            .collect();
+        assert_eq!(summary, vec![
+            ("POST", "/auth/login", "public".to_owned()),
+            ("POST", "/auth/refresh", "refresh".to_owned()),
+            ("GET", "/admin/system/user/page", "permission".to_owned()),
+            ("POST", "/admin/system/user/add", "permission".to_owned()),
+            ("GET", "/admin/system/user/add", "auth".to_owned()),
+        ]);
+        assert_eq!(registry.permissions(), BTreeSet::from(["system:user:add", "system:user:page"]));
+        assert!(registry.contains_permission("system:user:page"));
+        assert!(!registry.contains_permission("system:user:remove"));
+        assert!(!registry.routes()[0].auth());
+    }
+
+    #[tokio::test]
+    async fn test_skip_body_log() {
+        let state = testing::mock_state::<TestHooks>(testing::test_config(), &Arc::new(MockRedis::default())).await;
+        let router = Routes::new()
+            .route("/upload", secured(post(handler)).permission("file:upload").skip_body_log())
+            .route("/login", post(handler))
+            .into_router(&state);
+
+        // 未登录被拒绝时也不记录请求体
+        let response = router.clone().oneshot(Request::post("/upload").body(Body::empty()).unwrap()).await.unwrap();
+        assert!(response.extensions().get::<SkipBodyLog>().is_some());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error_code"].as_u64(), Some(usize::from(BusinessErrorCode::Unauthorized) as u64));
+        let response = router.oneshot(Request::post("/login").body(Body::empty()).unwrap()).await.unwrap();
+        assert!(response.extensions().get::<SkipBodyLog>().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_body_limit() {
+        let state = testing::mock_state::<TestHooks>(testing::test_config(), &Arc::new(MockRedis::default())).await;
+        let router = Routes::new()
+            .route("/upload", post(|body: String| async move { body.len().to_string() }).body_limit(8))
+            .into_router(&state);
+
+        let response = router.clone().oneshot(Request::post("/upload").body(Body::from("12345678")).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = router.oneshot(Request::post("/upload").body(Body::from("123456789")).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
@@ -0,0 +1,46 @@
+use axum::{extract::{Path, Query, State}, http::header, response::{IntoResponse, Response}, routing::get, Router};
+use serde::Deserialize;
+
+use crate::{server::AppState, storage::{self, guess_content_type, is_inline_safe, verify_signature}, utils::{error::{BuboError, BuboResult, BusinessErrorCode}, time::current_timestamp_sec}};
+
+///
+/// 文件下载路由，地址通过 storage::signed_url 生成
+///
+pub fn init_routes(state: AppState) -> Router {
+    let path = format!("{}/*key", state.config.storage.url_prefix.trim_end_matches('/'));
+    Router::new()
+        .route(&path, get(download_handler))
+        .with_state(state)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DownloadParams {
+    pub expires: i64,
+    pub signature: String,
+}
+
+///
+/// 校验签名后返回文件，浏览器缓存时间不超过地址有效期，
+/// 禁止浏览器猜测类型，图片以外的文件按附件下载，避免上传的页面在站点域名下执行脚本
+///
+async fn download_handler(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    Query(params): Query<DownloadParams>,
+) -> BuboResult<Response> {
+    verify_signature(&state.config.storage, &key, params.expires, &params.signature)?;
+    let object = storage::storage(&state)?.get(&key).await?
+        .ok_or_else(|| BuboError::from(BusinessErrorCode::NotFound))?;
+    let content_type = object.content_type.unwrap_or_else(|| guess_content_type(&key));
+    let max_age = (params.expires - current_timestamp_sec()).max(0);
+    let disposition = if is_inline_safe(&content_type) { "inline" } else { "attachment" };
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CONTENT_DISPOSITION, disposition.to_owned()),
+            (header::CACHE_CONTROL, format!("private, max-age={}", max_age)),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_owned()),
+        ],
+        object.data,
+    ).into_response())
+}
@@ -0,0 +1,368 @@
+use std::{collections::BTreeMap, fmt::Display, future::Future, path::Path, sync::Arc};
+
+use once_cell::sync::OnceCell;
+use serde_json::Value;
+use validator::{ValidationError, ValidationErrors, ValidationErrorsKind};
+
+use crate::{config::I18nConfig, server::Hooks, utils::error::{BuboError, BuboResult, SystemErrorCode}};
+
+pub const ZH_CN: &str = "zh-CN";
+pub const EN_US: &str = "en-US";
+
+const BUILTIN: &[(&str, &str)] = &[
+    (ZH_CN, include_str!("../locales/zh-CN.toml")),
+    (EN_US, include_str!("../locales/en-US.toml")),
+];
+
+tokio::task_local! {
+    static CURRENT: Current;
+}
+
+// 请求之外（命令行、后台任务）使用的消息目录，init 时设置
+static GLOBAL: OnceCell<Arc<I18n>> = OnceCell::new();
+
+#[derive(Clone)]
+struct Current {
+    i18n: Arc<I18n>,
+    locale: String,
+}
+
+///
+/// 多语言消息目录，按语言保存 key 到消息模板，模板中的 `{name}` 由参数替换
+///
+/// 错误码的默认消息为 `business.{code}`、`system.{code}`，例如 `business.unauthorized`，
+/// 参数校验消息为 `validation.{rule}`，应用可以添加任意 key
+///
+#[derive(Debug, Clone)]
+pub struct I18n {
+    default_locale: String,
+    catalogs: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl I18n {
+    ///
+    /// 包含内置的 zh-CN 和 en-US 消息
+    ///
+    pub fn new(default_locale: impl Into<String>) -> Self {
+        let mut i18n = Self { default_locale: default_locale.into(), catalogs: BTreeMap::new() };
+        for (locale, content) in BUILTIN {
+            i18n.add_toml(locale, content).expect("Invalid builtin locale file");
+        }
+        i18n
+    }
+
+    pub fn default_locale(&self) -> &str {
+        &self.default_locale
+    }
+
+    pub fn locales(&self) -> impl Iterator<Item = &str> {
+        self.catalogs.keys().map(String::as_str)
+    }
+
+    pub fn add(&mut self, locale: &str, key: impl Into<String>, message: impl Into<String>) -> &mut Self {
+        self.catalogs.entry(locale.to_owned()).or_default().insert(key.into(), message.into());
+        self
+    }
+
+    ///
+    /// 添加 TOML 格式的消息，嵌套的表以 `.` 连接为 key，例如 `[role] not_found = "..."` 为 `role.not_found`
+    ///
+    pub fn add_toml(&mut self, locale: &str, content: &str) -> Result<&mut Self, toml::de::Error> {
+        let table: toml::Table = toml::from_str(content)?;
+        let catalog = self.catalogs.entry(locale.to_owned()).or_default();
+        flatten("", table, catalog);
+        Ok(self)
+    }
+
+    ///
+    /// 加载目录中的 `{locale}.toml`
+    ///
+    pub fn load_dir(&mut self, dir: impl AsRef<Path>) -> BuboResult<&mut Self> {
+        let dir = dir.as_ref();
+        let entries = std::fs::read_dir(dir).map_err(|e| load_error(dir, e))?;
+        for entry in entries {
+            let path = entry.map_err(|e| load_error(dir, e))?.path();
+            let Some(locale) = path.file_stem().and_then(|stem| stem.to_str()).filter(|_| path.extension().is_some_and(|ext| ext == "toml")) else {
+                continue;
+            };
+            let content = std::fs::read_to_string(&path).map_err(|e| load_error(&path, e))?;
+            self.add_toml(locale, &content).map_err(|e| load_error(&path, e))?;
+        }
+        Ok(self)
+    }
+
+    ///
+    /// 指定语言的消息，没有时使用默认语言的消息
+    ///
+    pub fn get(&self, locale: &str, key: &str) -> Option<&str> {
+        self.catalogs.get(locale).and_then(|catalog| catalog.get(key))
+            .or_else(|| self.catalogs.get(&self.default_locale).and_then(|catalog| catalog.get(key)))
+            .map(String::as_str)
+    }
+
+    ///
+    /// 已有消息的语言，忽略大小写，只有语言部分相同时也匹配，例如 `en` 和 `en-GB` 匹配 `en-US`
+    ///
+    pub fn supported(&self, tag: &str) -> Option<&str> {
+        let language = tag.split(['-', '_']).next().unwrap_or_default();
+        self.catalogs.keys()
+            .find(|locale| locale.eq_ignore_ascii_case(tag) || locale.replace('-', "_").eq_ignore_ascii_case(tag))
+            .or_else(|| {
+                let same_language = |locale: &&String| locale.split('-').next().is_some_and(|l| l.eq_ignore_ascii_case(language));
+                // 同一语言有多个时优先默认语言
+                self.catalogs.keys().filter(same_language).find(|locale| **locale == self.default_locale)
+                    .or_else(|| self.catalogs.keys().find(same_language))
+            })
+            .map(String::as_str)
+    }
+
+    ///
+    /// 按 Accept-Language 的权重选择支持的语言
+    ///
+    pub fn negotiate(&self, accept_language: &str) -> Option<&str> {
+        let mut ranges: Vec<(&str, f32)> = accept_language.split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let tag = parts.next()?.trim();
+                let quality = parts.find_map(|param| param.trim().strip_prefix("q=")).map_or(1.0, |q| q.trim().parse().unwrap_or(0.0));
+                (!tag.is_empty() && tag != "*" && quality > 0.0).then_some((tag, quality))
+            })
+            .collect();
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranges.into_iter().find_map(|(tag, _)| self.supported(tag))
+    }
+
+    ///
+    /// 按参数替换消息模板，key 不存在时返回 None
+    ///
+    pub fn translate(&self, locale: &str, key: &str, args: &[(&str, &dyn Display)]) -> Option<String> {
+        self.get(locale, key).map(|template| format(template, args))
+    }
+
+    ///
+    /// 参数校验错误的消息，每个错误按 `validation.{rule}` 生成，字段名可以通过 `field.{name}` 翻译
+    ///
+    pub fn validation_message(&self, locale: &str, errors: &ValidationErrors) -> String {
+        let mut messages = Vec::new();
+        self.validation_messages(locale, errors, "", &mut messages);
+        messages.join(", ")
+    }
+
+    fn validation_messages(&self, locale: &str, errors: &ValidationErrors, prefix: &str, messages: &mut Vec<String>) {
+        let mut fields: Vec<_> = errors.errors().iter().collect();
+        fields.sort_by_key(|(field, _)| *field);
+        for (field, kind) in fields {
+            let path = if prefix.is_empty() { (*field).to_owned() } else { format!("{prefix}.{field}") };
+            match kind {
+                ValidationErrorsKind::Field(errors) => {
+                    let name = self.get(locale, &format!("field.{field}")).unwrap_or(path.as_str());
+                    messages.extend(errors.iter().map(|error| self.field_message(locale, name, error)));
+                }
+                ValidationErrorsKind::Struct(errors) => self.validation_messages(locale, errors, &path, messages),
+                ValidationErrorsKind::List(items) => {
+                    for (index, errors) in items {
+                        self.validation_messages(locale, errors, &format!("{path}[{index}]"), messages);
+                    }
+                }
+            }
+        }
+    }
+
+    fn field_message(&self, locale: &str, field: &str, error: &ValidationError) -> String {
+        let mut params: Vec<(&str, String)> = error.params.iter().map(|(name, value)| (name.as_ref(), param(value))).collect();
+        // range 的 exclusive_min/exclusive_max 同样使用 {min}/{max}
+        for (exclusive, name) in [("exclusive_min", "min"), ("exclusive_max", "max")] {
+            if !error.params.contains_key(name) {
+                if let Some(value) = error.params.get(exclusive) {
+                    params.push((name, param(value)));
+                }
+            }
+        }
+        let mut args: Vec<(&str, &dyn Display)> = params.iter().map(|(name, value)| (*name, value as &dyn Display)).collect();
+        args.push(("field", &field));
+
+        if let Some(message) = &error.message {
+            return self.translate(locale, message, &args).unwrap_or_else(|| format(message, &args));
+        }
+        let has = |name: &str| params.iter().any(|(param, _)| *param == name);
+        let variant = match (has("min"), has("max"), has("equal")) {
+            (_, _, true) => "_equal",
+            (true, true, _) => "_min_max",
+            (true, false, _) => "_min",
+            (false, true, _) => "_max",
+            _ => "",
+        };
+        [format!("validation.{}{variant}", error.code), format!("validation.{}", error.code), "validation.default".to_owned()]
+            .iter()
+            .find_map(|key| self.translate(locale, key, &args))
+            .unwrap_or_else(|| format!("{field}: {}", error.code))
+    }
+}
+
+impl Default for I18n {
+    fn default() -> Self {
+        Self::new(ZH_CN)
+    }
+}
+
+///
+/// 按配置创建消息目录：内置消息 <- Hooks::i18n <- i18n.dir，同时作为请求之外使用的消息目录
+///
+pub fn init<H: Hooks>(config: &I18nConfig) -> BuboResult<Arc<I18n>> {
+    let mut i18n = I18n::new(config.default_locale.as_str());
+    H::i18n(&mut i18n);
+    if !config.dir.is_empty() {
+        i18n.load_dir(&config.dir)?;
+    }
+    let i18n = Arc::new(i18n);
+    let _ = GLOBAL.set(i18n.clone());
+    Ok(i18n)
+}
+
+///
+/// 在 f 中使用指定的消息目录和语言，由 i18n 中间件对每个请求调用
+///
+pub async fn scope<F: Future>(i18n: Arc<I18n>, locale: impl Into<String>, f: F) -> F::Output {
+    CURRENT.scope(Current { i18n, locale: locale.into() }, f).await
+}
+
+///
+/// 在 f 中改用 locale，例如登录用户的语言偏好，不支持该语言时不改变
+///
+pub async fn with_locale<F: Future>(locale: &str, f: F) -> F::Output {
+    let current = CURRENT.try_with(|current| current.i18n.supported(locale).map(|locale| Current { i18n: current.i18n.clone(), locale: locale.to_owned() }));
+    match current {
+        Ok(Some(current)) => CURRENT.scope(current, f).await,
+        _ => f.await,
+    }
+}
+
+///
+/// 当前请求的语言
+///
+pub fn locale() -> String {
+    with_current(|_, locale| locale.to_owned())
+}
+
+///
+/// 当前语言的消息，key 不存在时返回 key 本身，因此也可以传入不需要翻译的文字
+///
+pub fn t(key: &str) -> String {
+    t_args(key, &[])
+}
+
+///
+/// 当前语言的消息并替换参数：`t_args("upload.too_many_files", &[("max", &10)])`
+///
+pub fn t_args(key: &str, args: &[(&str, &dyn Display)]) -> String {
+    with_current(|i18n, locale| i18n.translate(locale, key, args)).unwrap_or_else(|| format(key, args))
+}
+
+///
+/// 当前语言的参数校验错误消息
+///
+pub fn validation_message(errors: &ValidationErrors) -> String {
+    with_current(|i18n, locale| i18n.validation_message(locale, errors))
+}
+
+fn with_current<R>(f: impl FnOnce(&I18n, &str) -> R) -> R {
+    match CURRENT.try_with(|current| current.clone()) {
+        Ok(current) => f(&current.i18n, &current.locale),
+        Err(_) => {
+            let i18n = GLOBAL.get_or_init(|| Arc::new(I18n::default()));
+            f(i18n, i18n.default_locale())
+        }
+    }
+}
+
+fn flatten(prefix: &str, table: toml::Table, catalog: &mut BTreeMap<String, String>) {
+    for (key, value) in table {
+        let key = if prefix.is_empty() { key } else { format!("{prefix}.{key}") };
+        match value {
+            toml::Value::Table(table) => flatten(&key, table, catalog),
+            toml::Value::String(message) => {
+                catalog.insert(key, message);
+            }
+            value => {
+                catalog.insert(key, value.to_string());
+            }
+        }
+    }
+}
+
+fn format(template: &str, args: &[(&str, &dyn Display)]) -> String {
+    args.iter().fold(template.to_owned(), |message, (name, value)| message.replace(&format!("{{{name}}}"), &value.to_string()))
+}
+
+fn param(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn load_error(path: &Path, e: impl Display) -> BuboError {
+    BuboError::system_error(SystemErrorCode::InternalServerError, format!("加载消息文件失败 {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use validator::Validate;
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[derive(Validate)]
+    struct Params {
+        #[validate(length(min = 3, max = 20))]
+        username: String,
+        #[validate(range(min = 1))]
+        page: u64,
+        #[validate(email(message = "invalid_email"))]
+        email: String,
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let i18n = I18n::default();
+        assert_eq!(i18n.negotiate("en-US,en;q=0.9,zh-CN;q=0.8"), Some(EN_US));
+        assert_eq!(i18n.negotiate("fr;q=1, zh;q=0.5, en;q=0.7"), Some(EN_US));
+        assert_eq!(i18n.negotiate("zh-Hans"), Some(ZH_CN));
+        assert_eq!(i18n.negotiate("fr, *"), None);
+        assert_eq!(i18n.supported("en_us"), Some(EN_US));
+    }
+
+    #[test]
+    fn test_translate() {
+        let mut i18n = I18n::default();
+        i18n.add_toml(EN_US, "[role]\ncode_exists = \"Role code {code} already exists\"").unwrap();
+        assert_eq!(i18n.translate(EN_US, "role.code_exists", &[("code", &"admin")]).as_deref(), Some("Role code admin already exists"));
+        assert_eq!(i18n.translate(EN_US, "business.unauthorized", &[]).as_deref(), Some("Not logged in or session expired"));
+        assert_eq!(i18n.translate(ZH_CN, "business.unauthorized", &[]).as_deref(), Some("未登录或登录已过期"));
+        // 没有翻译时使用默认语言
+        assert_eq!(i18n.translate("ja-JP", "upload.too_many_files", &[("max", &1)]).as_deref(), Some("最多上传 1 个文件"));
+        assert_eq!(i18n.translate(EN_US, "missing", &[]), None);
+    }
+
+    #[test]
+    fn test_validation_message() {
+        let mut i18n = I18n::default();
+        i18n.add(ZH_CN, "field.username", "用户名").add(ZH_CN, "invalid_email", "{field} 格式错误: {value}")
+            .add(EN_US, "field.username", "Username").add(EN_US, "invalid_email", "{field} is not an email: {value}");
+        let errors = Params { username: "ab".to_owned(), page: 0, email: "x".to_owned() }.validate().unwrap_err();
+        assert_eq!(i18n.validation_message(ZH_CN, &errors), "email 格式错误: x, page 不能小于 1, 用户名 长度必须在 3 到 20 之间");
+        assert_eq!(i18n.validation_message(EN_US, &errors), "email is not an email: x, page must be at least 1, Username length must be between 3 and 20");
+    }
+
+    #[tokio::test]
+    async fn test_scope() {
+        let i18n = Arc::new(I18n::default());
+        let message = scope(i18n, EN_US, async {
+            let outer = t("business.forbidden");
+            let inner = with_locale("zh", async { (locale(), t("business.forbidden")) }).await;
+            (outer, inner)
+        }).await;
+        assert_eq!(message, ("Access denied".to_owned(), (ZH_CN.to_owned(), "没有访问权限".to_owned())));
+        assert_eq!(t("plain text"), "plain text");
+    }
+}
@@ -1,13 +1,28 @@
 use sea_orm_migration::MigratorTrait;
 use server::Hooks;
 
+pub mod cache;
+pub mod cli;
+pub mod config;
+pub mod i18n;
+pub mod mailer;
+pub mod openapi;
+pub mod scheduler;
 pub mod server;
+pub mod session;
+pub mod storage;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod utils;
 pub mod controllers;
 pub mod views;
+pub mod worker;
 
-pub async fn main<H: Hooks, M: MigratorTrait>() {
-    server::main::<H, M>().await;
+#[cfg(feature = "embed")]
+pub use rust_embed;
+
+pub async fn main<H: Hooks, M: MigratorTrait + 'static>() {
+    cli::main::<H, M>().await;
 }
 
 pub fn add(left: u64, right: u64) -> u64 {
@@ -0,0 +1,358 @@
+use std::{path::{Path, PathBuf}, sync::{Arc, Mutex}, time::Duration};
+
+use async_trait::async_trait;
+use lettre::{message::{header::ContentType, Mailbox, MultiPart, SinglePart}, transport::smtp::authentication::Credentials, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use minijinja::{path_loader, Environment, ErrorKind};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info};
+
+use crate::{config::{MailTransport, MailerConfig, SmtpConfig, SmtpSecurity}, server::AppState, utils::{error::{BuboError, BuboResult, SystemErrorCode}, time::current_timestamp_ms}, worker::Worker};
+
+///
+/// 邮件，可以序列化后放入后台队列
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Email {
+    // 为空时使用配置 mailer.from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    pub to: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cc: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bcc: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<String>,
+    pub subject: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub html: Option<String>,
+}
+
+impl Email {
+    pub fn new(subject: impl Into<String>) -> Self {
+        Self { subject: subject.into(), ..Default::default() }
+    }
+
+    pub fn from(mut self, from: impl Into<String>) -> Self {
+        self.from = Some(from.into());
+        self
+    }
+
+    pub fn to(mut self, to: impl Into<String>) -> Self {
+        self.to.push(to.into());
+        self
+    }
+
+    pub fn cc(mut self, cc: impl Into<String>) -> Self {
+        self.cc.push(cc.into());
+        self
+    }
+
+    pub fn bcc(mut self, bcc: impl Into<String>) -> Self {
+        self.bcc.push(bcc.into());
+        self
+    }
+
+    pub fn reply_to(mut self, reply_to: impl Into<String>) -> Self {
+        self.reply_to = Some(reply_to.into());
+        self
+    }
+
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.text = Some(text.into());
+        self
+    }
+
+    pub fn html(mut self, html: impl Into<String>) -> Self {
+        self.html = Some(html.into());
+        self
+    }
+
+    ///
+    /// 转换为 MIME 邮件，同时有文本和 HTML 时生成 multipart/alternative
+    ///
+    pub fn to_message(&self) -> BuboResult<Message> {
+        let from = self.from.as_deref().ok_or_else(|| mail_error("missing from address"))?;
+        if self.to.is_empty() {
+            return Err(mail_error("missing to address"));
+        }
+        let mut builder = Message::builder().from(parse_mailbox(from)?).subject(self.subject.as_str());
+        for to in &self.to {
+            builder = builder.to(parse_mailbox(to)?);
+        }
+        for cc in &self.cc {
+            builder = builder.cc(parse_mailbox(cc)?);
+        }
+        for bcc in &self.bcc {
+            builder = builder.bcc(parse_mailbox(bcc)?);
+        }
+        if let Some(reply_to) = &self.reply_to {
+            builder = builder.reply_to(parse_mailbox(reply_to)?);
+        }
+        let message = match (&self.text, &self.html) {
+            (Some(text), Some(html)) => builder.multipart(MultiPart::alternative_plain_html(text.clone(), html.clone())),
+            (Some(text), None) => builder.singlepart(SinglePart::plain(text.clone())),
+            (None, Some(html)) => builder.singlepart(SinglePart::html(html.clone())),
+            (None, None) => builder.header(ContentType::TEXT_PLAIN).body(String::new()),
+        };
+        message.map_err(mail_error)
+    }
+}
+
+fn parse_mailbox(address: &str) -> BuboResult<Mailbox> {
+    address.parse().map_err(|e| mail_error(format!("invalid address `{}`: {}", address, e)))
+}
+
+fn mail_error(e: impl ToString) -> BuboError {
+    BuboError::system_error(SystemErrorCode::MailError, e.to_string())
+}
+
+///
+/// 邮件发送方式
+///
+#[async_trait]
+pub trait Transport: Send + Sync + 'static {
+    async fn send(&self, email: &Email) -> BuboResult<()>;
+}
+
+///
+/// 通过 SMTP 发送
+///
+pub struct SmtpTransport(AsyncSmtpTransport<Tokio1Executor>);
+
+impl SmtpTransport {
+    pub fn new(config: &SmtpConfig) -> BuboResult<Self> {
+        let builder = match config.security {
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host).map_err(mail_error)?,
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host).map_err(mail_error)?,
+            SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+        };
+        let mut builder = builder.port(config.port()).timeout(Some(Duration::from_secs(config.timeout)));
+        if !config.username.is_empty() {
+            builder = builder.credentials(Credentials::new(config.username.clone(), config.password.clone()));
+        }
+        Ok(Self(builder.build()))
+    }
+}
+
+#[async_trait]
+impl Transport for SmtpTransport {
+    async fn send(&self, email: &Email) -> BuboResult<()> {
+        self.0.send(email.to_message()?).await.map_err(mail_error)?;
+        Ok(())
+    }
+}
+
+///
+/// 把邮件写入目录下的 .eml 文件，用于开发环境
+///
+pub struct FileTransport {
+    dir: PathBuf,
+}
+
+impl FileTransport {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+#[async_trait]
+impl Transport for FileTransport {
+    async fn send(&self, email: &Email) -> BuboResult<()> {
+        let message = email.to_message()?;
+        tokio::fs::create_dir_all(&self.dir).await.map_err(mail_error)?;
+        let path = self.dir.join(format!("{}-{}.eml", current_timestamp_ms(), uuid::Uuid::new_v4().simple()));
+        tokio::fs::write(&path, message.formatted()).await.map_err(mail_error)?;
+        info!("mail saved to {}", path.display());
+        Ok(())
+    }
+}
+
+///
+/// 只在内存中保存邮件，用于测试断言
+///
+#[derive(Default)]
+pub struct StubTransport {
+    deliveries: Mutex<Vec<Email>>,
+}
+
+impl StubTransport {
+    ///
+    /// 已发送的邮件
+    ///
+    pub fn deliveries(&self) -> Vec<Email> {
+        self.deliveries.lock().unwrap().clone()
+    }
+
+    pub fn clear(&self) {
+        self.deliveries.lock().unwrap().clear();
+    }
+}
+
+#[async_trait]
+impl Transport for StubTransport {
+    async fn send(&self, email: &Email) -> BuboResult<()> {
+        // 与真实发送一样校验地址
+        email.to_message()?;
+        self.deliveries.lock().unwrap().push(email.clone());
+        Ok(())
+    }
+}
+
+///
+/// 邮件组件，渲染模板并通过配置的方式发送
+///
+pub struct Mailer {
+    from: String,
+    transport: Arc<dyn Transport>,
+    stub: Option<Arc<StubTransport>>,
+    templates: Environment<'static>,
+}
+
+impl Mailer {
+    ///
+    /// 按配置创建，模板目录存在时从目录加载模板
+    ///
+    pub fn new(config: &MailerConfig) -> BuboResult<Self> {
+        let mut stub = None;
+        let transport: Arc<dyn Transport> = match config.transport {
+            MailTransport::Smtp => Arc::new(SmtpTransport::new(&config.smtp)?),
+            MailTransport::File => Arc::new(FileTransport::new(&config.file_dir)),
+            MailTransport::Stub => {
+                let transport = Arc::new(StubTransport::default());
+                stub = Some(transport.clone());
+                transport
+            }
+        };
+        let mut mailer = Self::with_transport(config.from.clone(), transport);
+        mailer.stub = stub;
+        if Path::new(&config.template_dir).is_dir() {
+            mailer.templates.set_loader(path_loader(&config.template_dir));
+        }
+        Ok(mailer)
+    }
+
+    pub fn with_transport(from: impl Into<String>, transport: Arc<dyn Transport>) -> Self {
+        Self { from: from.into(), transport, stub: None, templates: Environment::new() }
+    }
+
+    ///
+    /// 使用 stub 方式发送时返回 stub，用于测试断言
+    ///
+    pub fn stub(&self) -> Option<&StubTransport> {
+        self.stub.as_deref()
+    }
+
+    ///
+    /// 注册模板，名称以 .html 结尾的模板会自动转义变量
+    ///
+    pub fn add_template(&mut self, name: impl Into<String>, source: impl Into<String>) -> BuboResult<()> {
+        self.templates.add_template_owned(name.into(), source.into()).map_err(mail_error)
+    }
+
+    ///
+    /// 渲染模板 `{name}.subject.txt`、`{name}.txt`、`{name}.html`，文本和 HTML 至少有一个
+    ///
+    pub fn render(&self, name: &str, context: impl Serialize) -> BuboResult<Email> {
+        let context = minijinja::Value::from_serialize(context);
+        let subject = self.render_template(&format!("{}.subject.txt", name), &context)?
+            .ok_or_else(|| mail_error(format!("mail template {}.subject.txt not found", name)))?;
+        let text = self.render_template(&format!("{}.txt", name), &context)?;
+        let html = self.render_template(&format!("{}.html", name), &context)?;
+        if text.is_none() && html.is_none() {
+            return Err(mail_error(format!("mail template {}.txt or {}.html not found", name, name)));
+        }
+        Ok(Email { subject: subject.trim().to_owned(), text, html, ..Default::default() })
+    }
+
+    fn render_template(&self, name: &str, context: &minijinja::Value) -> BuboResult<Option<String>> {
+        let template = match self.templates.get_template(name) {
+            Ok(template) => template,
+            Err(e) if e.kind() == ErrorKind::TemplateNotFound => return Ok(None),
+            Err(e) => return Err(mail_error(e)),
+        };
+        template.render(context).map(Some).map_err(mail_error)
+    }
+
+    ///
+    /// 立即发送
+    ///
+    pub async fn send(&self, mut email: Email) -> BuboResult<()> {
+        if email.from.is_none() {
+            email.from = Some(self.from.clone());
+        }
+        self.transport.send(&email).await?;
+        debug!("mail sent to {:?}: {}", email.to, email.subject);
+        Ok(())
+    }
+}
+
+///
+/// 放入后台队列发送，未启用后台任务时在新的 tokio 任务中发送，不阻塞请求
+///
+pub async fn deliver_later(state: &AppState, email: Email) -> BuboResult<()> {
+    let Some(mailer) = state.mailer.clone() else {
+        return Err(mail_error("mailer is not enabled"));
+    };
+    if state.config.worker.enable {
+        MailWorker::perform_later(state, email).await?;
+    } else {
+        tokio::spawn(async move {
+            if let Err(e) = mailer.send(email).await {
+                error!("send mail error: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+///
+/// 后台发送邮件，启用 mailer 时自动注册
+///
+pub struct MailWorker(pub Arc<Mailer>);
+
+#[async_trait]
+impl Worker for MailWorker {
+    type Args = Email;
+
+    fn name() -> &'static str {
+        "bubo:mailer"
+    }
+
+    async fn perform(&self, email: Email) -> BuboResult<()> {
+        self.0.send(email).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn test_render_and_send() {
+        let stub = Arc::new(StubTransport::default());
+        let mut mailer = Mailer::with_transport("Bubo <noreply@example.com>", stub.clone());
+        mailer.add_template("welcome.subject.txt", "Welcome {{ name }}").unwrap();
+        mailer.add_template("welcome.txt", "Hi {{ name }}").unwrap();
+        mailer.add_template("welcome.html", "<p>Hi {{ name }}</p>").unwrap();
+
+        let email = mailer.render("welcome", serde_json::json!({ "name": "<Bob>" })).unwrap().to("bob@example.com");
+        assert_eq!(email.subject, "Welcome <Bob>");
+        assert_eq!(email.text.as_deref(), Some("Hi <Bob>"));
+        assert_eq!(email.html.as_deref(), Some("<p>Hi &lt;Bob&gt;</p>"));
+        assert!(mailer.render("missing", ()).is_err());
+
+        mailer.send(email).await.unwrap();
+        let deliveries = stub.deliveries();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].from.as_deref(), Some("Bubo <noreply@example.com>"));
+        assert!(mailer.send(Email::new("no recipient").text("hi")).await.is_err());
+    }
+}
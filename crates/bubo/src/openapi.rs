@@ -0,0 +1,314 @@
+use schemars::{gen::{SchemaGenerator, SchemaSettings}, schema::{InstanceType, Schema, SchemaObject}, JsonSchema};
+use serde_json::{json, Map, Value};
+
+use crate::{config::{ErrorMode, OpenApiConfig}, controllers::{middlewares::error::PROBLEM_JSON, routing::RouteInfo}, views::response::{ErrorResponse, ProblemDetails}};
+
+// 生成 schema 的函数，由 routing::Endpoint 的 query/json/response 等方法指定类型
+pub type SchemaFn = fn(&mut SchemaGenerator) -> Schema;
+
+///
+/// 路由的接口文档
+///
+#[derive(Debug, Clone, Default)]
+pub struct RouteDoc {
+    pub summary: Option<String>,
+    // 为空时使用路径去掉最后一段，例如 `/admin/system/role`
+    pub tag: Option<String>,
+    // QueryValid/Query 参数，每个字段作为一个 query 参数
+    pub query: Option<SchemaFn>,
+    pub body: Option<BodyDoc>,
+    pub response: ResponseDoc,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum BodyDoc {
+    // JsonValid/Json 参数
+    Json(SchemaFn),
+    // 文件上传
+    Multipart,
+}
+
+///
+/// 成功响应
+///
+#[derive(Debug, Clone, Copy, Default)]
+pub enum ResponseDoc {
+    // `{"status": true}`
+    #[default]
+    Status,
+    // 响应类型，例如 ApiResponse<T>、PageResponse<T>
+    Schema(SchemaFn),
+}
+
+///
+/// 以字符串传输的 i64，例如雪花 id：`#[schemars(with = "bubo::openapi::Id")]`
+///
+pub struct Id;
+
+impl JsonSchema for Id {
+    fn schema_name() -> String {
+        "Id".to_owned()
+    }
+
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        SchemaObject { instance_type: Some(InstanceType::String.into()), format: Some("int64".to_owned()), ..Default::default() }.into()
+    }
+}
+
+///
+/// RFC 3339 时间：`#[schemars(with = "bubo::openapi::DateTime")]`
+///
+pub struct DateTime;
+
+impl JsonSchema for DateTime {
+    fn schema_name() -> String {
+        "DateTime".to_owned()
+    }
+
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        SchemaObject { instance_type: Some(InstanceType::String.into()), format: Some("date-time".to_owned()), ..Default::default() }.into()
+    }
+}
+
+///
+/// 由路由信息生成 OpenAPI 3 文档，错误响应的格式由 error_mode 决定
+///
+pub fn document(routes: &[RouteInfo], app_name: &str, config: &OpenApiConfig, error_mode: ErrorMode) -> Value {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+    let error_response = error_response(error_mode, &mut gen);
+    let mut paths = Map::new();
+    for route in routes {
+        let mut operation = operation(route, &mut gen);
+        operation["responses"]["default"] = error_response.clone();
+        let item = paths.entry(openapi_path(&route.path)).or_insert_with(|| json!({}));
+        item[route.method.to_ascii_lowercase()] = operation;
+    }
+
+    let schemas: Map<String, Value> = gen.take_definitions().into_iter().map(|(name, schema)| (name, to_value(&schema))).collect();
+    let title = if config.title.is_empty() { app_name } else { config.title.as_str() };
+    json!({
+        "openapi": "3.0.3",
+        "info": { "title": title, "version": config.version },
+        "paths": paths,
+        "components": {
+            "schemas": schemas,
+            "securitySchemes": {
+                "bearerAuth": { "type": "http", "scheme": "bearer", "bearerFormat": "JWT" },
+            },
+        },
+    })
+}
+
+fn operation(route: &RouteInfo, gen: &mut SchemaGenerator) -> Value {
+    let doc = &route.doc;
+    let mut operation = json!({
+        "tags": [doc.tag.clone().unwrap_or_else(|| default_tag(&route.path))],
+        "responses": {
+            "200": {
+                "description": "成功",
+                "content": { "application/json": { "schema": response_schema(doc.response, gen) } },
+            },
+        },
+    });
+    if let Some(summary) = &doc.summary {
+        operation["summary"] = json!(summary);
+    }
+    if route.auth() {
+        operation["security"] = json!([{ "bearerAuth": [] }]);
+    }
+    if let Some(permission) = route.permission() {
+        operation["description"] = json!(format!("权限: `{permission}`"));
+        operation["x-permission"] = json!(permission);
+    }
+    if let Some(query) = doc.query {
+        operation["parameters"] = Value::Array(query_parameters(query(gen)));
+    }
+    match doc.body {
+        Some(BodyDoc::Json(body)) => {
+            operation["requestBody"] = json!({
+                "required": true,
+                "content": { "application/json": { "schema": to_value(&body(gen)) } },
+            });
+        }
+        Some(BodyDoc::Multipart) => {
+            operation["requestBody"] = json!({
+                "required": true,
+                "content": { "multipart/form-data": { "schema": {
+                    "type": "object",
+                    "properties": { "file": { "type": "array", "items": { "type": "string", "format": "binary" } } },
+                } } },
+            });
+        }
+        None => {}
+    }
+    operation
+}
+
+fn error_response(error_mode: ErrorMode, gen: &mut SchemaGenerator) -> Value {
+    match error_mode {
+        ErrorMode::Legacy => json!({
+            "description": "错误，业务错误的 HTTP 状态码同样为 200，由 error_code 区分",
+            "content": { "application/json": { "schema": to_value(&gen.subschema_for::<ErrorResponse>()) } },
+        }),
+        ErrorMode::Status => json!({
+            "description": "错误，HTTP 状态码对应错误类型，由 error_code 区分",
+            "content": { "application/json": { "schema": to_value(&gen.subschema_for::<ErrorResponse>()) } },
+        }),
+        ErrorMode::Problem => json!({
+            "description": "错误，HTTP 状态码对应错误类型，由 error_code 区分",
+            "content": { PROBLEM_JSON: { "schema": to_value(&gen.subschema_for::<ProblemDetails>()) } },
+        }),
+    }
+}
+
+fn response_schema(response: ResponseDoc, gen: &mut SchemaGenerator) -> Value {
+    match response {
+        ResponseDoc::Status => json!({
+            "type": "object",
+            "required": ["status"],
+            "properties": { "status": { "type": "boolean" } },
+        }),
+        ResponseDoc::Schema(schema) => to_value(&schema(gen)),
+    }
+}
+
+///
+/// 参数结构体的每个字段对应一个 query 参数
+///
+fn query_parameters(schema: Schema) -> Vec<Value> {
+    let Schema::Object(SchemaObject { object: Some(object), .. }) = schema else {
+        return Vec::new();
+    };
+    object.properties.iter()
+        .map(|(name, schema)| {
+            let mut schema = to_value(schema);
+            let description = schema.as_object_mut().and_then(|schema| schema.remove("description"));
+            let mut parameter = json!({ "name": name, "in": "query", "required": object.required.contains(name), "schema": schema });
+            if let Some(description) = description {
+                parameter["description"] = description;
+            }
+            parameter
+        })
+        .collect()
+}
+
+fn to_value(schema: &Schema) -> Value {
+    serde_json::to_value(schema).unwrap_or_default()
+}
+
+///
+/// axum 路径参数 `:id`、`*path` 转为 `{id}`、`{path}`
+///
+fn openapi_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| match segment.strip_prefix(':').or_else(|| segment.strip_prefix('*')) {
+            Some(name) => format!("{{{name}}}"),
+            None => segment.to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn default_tag(path: &str) -> String {
+    match path.rsplit_once('/') {
+        Some((parent, _)) if !parent.is_empty() => parent.to_owned(),
+        _ => "/".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use schemars::JsonSchema;
+    use validator::Validate;
+
+    use crate::controllers::routing::{get, post, secured, Routes};
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    async fn handler() {}
+
+    #[allow(dead_code)]
+    #[derive(JsonSchema, Validate)]
+    struct PageParams {
+        name: Option<String>,
+        #[validate(range(min = 1))]
+        page: u64,
+    }
+
+    #[allow(dead_code)]
+    #[derive(JsonSchema, Validate)]
+    struct AddParams {
+        #[validate(length(min = 3, max = 20))]
+        username: String,
+        #[schemars(with = "HashSet<Id>")]
+        role_ids: HashSet<i64>,
+    }
+
+    #[allow(dead_code)]
+    #[derive(JsonSchema)]
+    struct UserResponse {
+        #[schemars(with = "Id")]
+        id: i64,
+        #[schemars(with = "DateTime")]
+        created_at: String,
+    }
+
+    #[test]
+    fn test_openapi_path() {
+        assert_eq!(openapi_path("/admin/user/:id"), "/admin/user/{id}");
+        assert_eq!(openapi_path("/files/*path"), "/files/{path}");
+        assert_eq!(default_tag("/admin/system/role/page"), "/admin/system/role");
+        assert_eq!(default_tag("/health"), "/");
+    }
+
+    #[test]
+    fn test_document() {
+        let system = Routes::new()
+            .route("/system/user/page", secured(get(handler)).permission("system:user:page").summary("用户分页").query::<PageParams>().page::<UserResponse>());
+        let routes = Routes::new()
+            .route("/admin/system/user/add", secured(post(handler)).json::<AddParams>().response::<UserResponse>())
+            .route("/admin/auth/logout", post(handler))
+            .nest("/admin", system);
+        let doc = document(routes.registry().routes(), "bubo", &OpenApiConfig::default(), ErrorMode::Legacy);
+        assert_eq!(doc["info"]["title"], "bubo");
+
+        let page = &doc["paths"]["/admin/system/user/page"]["get"];
+        assert_eq!(page["summary"], "用户分页");
+        assert_eq!(page["x-permission"], "system:user:page");
+        assert_eq!(page["security"], json!([{ "bearerAuth": [] }]));
+        assert_eq!(page["parameters"][0], json!({ "name": "name", "in": "query", "required": false, "schema": { "type": "string", "nullable": true } }));
+        assert_eq!(page["parameters"][1]["schema"]["minimum"], 1.0);
+        let response = &page["responses"]["200"]["content"]["application/json"]["schema"];
+        assert_eq!(response["$ref"], "#/components/schemas/PageResponse_for_UserResponse");
+
+        let schemas = &doc["components"]["schemas"];
+        assert_eq!(schemas["PageResponse_for_UserResponse"]["properties"]["data"]["items"]["$ref"], "#/components/schemas/UserResponse");
+        assert_eq!(schemas["ErrorResponse"]["required"], json!(["error_code", "error_message", "status"]));
+        assert_eq!(schemas["UserResponse"]["properties"]["id"], json!({ "type": "string", "format": "int64" }));
+        assert_eq!(schemas["UserResponse"]["properties"]["created_at"]["format"], "date-time");
+        assert_eq!(schemas["AddParams"]["properties"]["username"]["minLength"], 3);
+        assert_eq!(schemas["AddParams"]["properties"]["username"]["maxLength"], 20);
+        assert_eq!(schemas["AddParams"]["properties"]["role_ids"]["uniqueItems"], true);
+
+        let logout = &doc["paths"]["/admin/auth/logout"]["post"];
+        assert!(logout.get("security").is_none());
+        assert_eq!(logout["responses"]["200"]["content"]["application/json"]["schema"]["required"], json!(["status"]));
+        assert_eq!(logout["responses"]["default"]["content"]["application/json"]["schema"]["$ref"], "#/components/schemas/ErrorResponse");
+
+        let doc = document(routes.registry().routes(), "bubo", &OpenApiConfig::default(), ErrorMode::Problem);
+        let logout = &doc["paths"]["/admin/auth/logout"]["post"];
+        assert_eq!(logout["responses"]["default"]["content"][PROBLEM_JSON]["schema"]["$ref"], "#/components/schemas/ProblemDetails");
+        assert!(doc["components"]["schemas"].get("ErrorResponse").is_none());
+    }
+}
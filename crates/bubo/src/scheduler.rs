@@ -0,0 +1,370 @@
+use std::{collections::HashMap, str::FromStr, sync::Arc, time::Instant};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use fred::{prelude::KeysInterface, types::{Expiration, SetOptions}};
+use serde::{Deserialize, Serialize};
+use tokio::task::{AbortHandle, JoinHandle};
+use tracing::{debug, error, info, warn};
+
+use crate::{config::SchedulerConfig, server::AppState, utils::{error::{BuboError, BuboResult, SystemErrorCode}, redis}};
+
+// 任务执行记录保留 7 天
+const STATUS_EXPIRE: i64 = 7 * 24 * 3600;
+
+///
+/// 定时任务
+///
+#[async_trait]
+pub trait Task: Send + Sync + 'static {
+    ///
+    /// 任务名称，同一应用内唯一
+    ///
+    fn name() -> &'static str
+    where
+        Self: Sized;
+
+    ///
+    /// cron 表达式，支持 5 位（分 时 日 月 周）或 6 位（秒 分 时 日 月 周），UTC 时间
+    ///
+    fn cron(&self) -> &str;
+
+    async fn run(&self, state: &AppState) -> BuboResult<()>;
+}
+
+///
+/// 任务执行记录
+///
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TaskStatus {
+    pub name: String,
+    pub cron: String,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_duration_ms: Option<u64>,
+    pub last_outcome: Option<String>,
+    pub next_run_at: Option<DateTime<Utc>>,
+}
+
+///
+/// 解析 cron 表达式，5 位表达式补充秒为 0
+///
+pub fn parse_cron(expr: &str) -> BuboResult<Schedule> {
+    let expr = expr.trim();
+    let normalized = if expr.split_whitespace().count() == 5 { format!("0 {}", expr) } else { expr.to_owned() };
+    Schedule::from_str(&normalized)
+        .map_err(|e| BuboError::system_error(SystemErrorCode::InternalServerError, format!("invalid cron expression `{}`: {}", expr, e)))
+}
+
+struct Entry {
+    task: Arc<dyn Task>,
+    cron: String,
+    schedule: Schedule,
+}
+
+///
+/// 定时任务调度，通过 Hooks::tasks 注册任务
+///
+#[derive(Default)]
+pub struct Scheduler {
+    entries: HashMap<&'static str, Entry>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// 注册任务，配置 scheduler.tasks.{name}.cron 可以覆盖任务自带的 cron 表达式
+    ///
+    pub fn register<T: Task>(&mut self, task: T, config: &SchedulerConfig) -> BuboResult<()> {
+        let task_config = config.tasks.get(T::name());
+        if task_config.is_some_and(|c| !c.enable) {
+            info!("task {} disabled", T::name());
+            return Ok(());
+        }
+        let cron = task_config.and_then(|c| c.cron.clone()).unwrap_or_else(|| task.cron().to_owned());
+        let schedule = parse_cron(&cron)?;
+        if self.entries.insert(T::name(), Entry { task: Arc::new(task), cron, schedule }).is_some() {
+            warn!("task {} registered more than once", T::name());
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut names: Vec<_> = self.entries.keys().copied().collect();
+        names.sort_unstable();
+        names
+    }
+
+    ///
+    /// 立即执行一次任务，不受租约限制
+    ///
+    pub async fn run_once(&self, name: &str, state: &AppState) -> BuboResult<()> {
+        let entry = self.entries.get(name)
+            .ok_or_else(|| BuboError::system_error(SystemErrorCode::NotFound, format!("task {} not found", name)))?;
+        entry.task.run(state).await
+    }
+
+    ///
+    /// 启动全部任务，停机开始后不再触发新的执行，在停机截止时间前等待执行中的任务完成，超时后中止
+    ///
+    pub async fn run(self, state: AppState) {
+        let config = state.config.scheduler.clone();
+        if !config.enable || self.is_empty() {
+            return;
+        }
+        info!("scheduler started, tasks: {:?}", self.names());
+        let mut handles = Vec::with_capacity(self.entries.len());
+        for (name, entry) in self.entries {
+            handles.push(tokio::spawn(task_loop(name, entry, state.clone(), config.clone())));
+        }
+
+        let deadline = state.shutdown.wait().await;
+        let mut finished = true;
+        for handle in &mut handles {
+            if tokio::time::timeout_at(deadline, handle).await.is_err() {
+                finished = false;
+                break;
+            }
+        }
+        if finished {
+            info!("scheduler stopped");
+        } else {
+            warn!("scheduler stop timeout, running tasks aborted");
+            handles.iter().for_each(JoinHandle::abort);
+        }
+    }
+}
+
+pub fn status_key(app_name: &str, name: &str) -> String {
+    format!("{}:scheduler:status:{}", app_name, name)
+}
+
+fn lease_key(app_name: &str, name: &str, tick: i64) -> String {
+    format!("{}:scheduler:lease:{}:{}", app_name, name, tick)
+}
+
+///
+/// 获取任务执行记录
+///
+pub async fn status(state: &AppState, name: &str) -> BuboResult<Option<TaskStatus>> {
+    redis::get(&state.redis, status_key(state.app_name, name)).await
+}
+
+async fn task_loop(name: &'static str, entry: Entry, state: AppState, config: SchedulerConfig) {
+    let Some(next) = entry.schedule.upcoming(Utc).next() else {
+        warn!("task {} has no upcoming schedule", name);
+        return;
+    };
+    update_status(&state, name, &entry, |status| status.next_run_at = Some(next)).await;
+    loop {
+        let Some(next) = entry.schedule.upcoming(Utc).next() else {
+            warn!("task {} has no upcoming schedule", name);
+            return;
+        };
+        let wait = (next - Utc::now()).to_std().unwrap_or_default();
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {},
+            _ = state.shutdown.wait() => return,
+        }
+        tick(name, &entry, &state, &config, next).await;
+    }
+}
+
+///
+/// 执行 scheduled_at 这一次触发，只有拿到租约的实例执行并写入执行记录，返回是否执行
+///
+async fn tick(name: &'static str, entry: &Entry, state: &AppState, config: &SchedulerConfig, scheduled_at: DateTime<Utc>) -> bool {
+    let lease = lease_key(state.app_name, name, scheduled_at.timestamp());
+    let acquired = state.redis
+        .set::<Option<String>, _, _>(lease, std::process::id().to_string(), Some(Expiration::EX(config.lease_ttl as i64)), Some(SetOptions::NX), false)
+        .await;
+    match acquired {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            debug!("task {} tick {} is running on another instance", name, scheduled_at);
+            return false;
+        }
+        Err(e) => {
+            error!("task {} acquire lease error: {}", name, e);
+            return false;
+        }
+    }
+
+    let start = Instant::now();
+    // 在单独的任务中执行，run 中的 panic 只影响这一次执行
+    let (task, task_state) = (entry.task.clone(), state.clone());
+    let handle = tokio::spawn(async move { task.run(&task_state).await });
+    let _abort = AbortOnDrop(handle.abort_handle());
+    let outcome = match handle.await {
+        Ok(Ok(())) => {
+            debug!("task {} done in {}ms", name, start.elapsed().as_millis());
+            "ok".to_owned()
+        }
+        Ok(Err(e)) => {
+            error!("task {} failed: {}", name, e);
+            e.to_string()
+        }
+        Err(e) => {
+            error!("task {} {}", name, e);
+            e.to_string()
+        }
+    };
+    let duration_ms = start.elapsed().as_millis() as u64;
+    let next = entry.schedule.after(&scheduled_at).next();
+    update_status(state, name, entry, |status| {
+        status.last_run_at = Some(scheduled_at);
+        status.last_duration_ms = Some(duration_ms);
+        status.last_outcome = Some(outcome);
+        status.next_run_at = next;
+    }).await;
+    true
+}
+
+///
+/// 调度循环被中止时同时中止执行中的任务
+///
+struct AbortOnDrop(AbortHandle);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+///
+/// 重新读取执行记录后修改并保存，避免覆盖其他实例写入的结果
+///
+async fn update_status(state: &AppState, name: &str, entry: &Entry, f: impl FnOnce(&mut TaskStatus)) {
+    let mut status = status(state, name).await.ok().flatten().unwrap_or_default();
+    status.name = name.to_owned();
+    status.cron = entry.cron.clone();
+    f(&mut status);
+    if let Err(e) = redis::set(&state.redis, status_key(state.app_name, name), &status, Some(Expiration::EX(STATUS_EXPIRE))).await {
+        warn!("task {} save status error: {}", name, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use chrono::{TimeZone, Timelike};
+
+    use crate::{server::Hooks, testing::{self, MockRedis}};
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    struct TestHooks;
+
+    impl Hooks for TestHooks {
+        fn app_name() -> &'static str {
+            "bubo-test"
+        }
+    }
+
+    struct CountTask(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl Task for CountTask {
+        fn name() -> &'static str {
+            "count"
+        }
+
+        fn cron(&self) -> &str {
+            "0 * * * *"
+        }
+
+        async fn run(&self, _state: &AppState) -> BuboResult<()> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            Err(BuboError::system_error(SystemErrorCode::InternalServerError, "task failed"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tick() {
+        let mock = Arc::new(MockRedis::default());
+        // 两个实例共享同一个 redis
+        let (first, second) = (testing::mock_state::<TestHooks>(testing::test_config(), &mock).await, testing::mock_state::<TestHooks>(testing::test_config(), &mock).await);
+        let runs = Arc::new(AtomicUsize::new(0));
+        let config = SchedulerConfig::default();
+        let mut scheduler = Scheduler::new();
+        scheduler.register(CountTask(runs.clone()), &config).unwrap();
+        let entry = &scheduler.entries["count"];
+
+        let scheduled_at = Utc.with_ymd_and_hms(2024, 1, 1, 3, 0, 0).unwrap();
+        let (a, b) = tokio::join!(tick("count", entry, &first, &config, scheduled_at), tick("count", entry, &second, &config, scheduled_at));
+        assert!(a ^ b);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        let saved = status(&first, "count").await.unwrap().unwrap();
+        assert_eq!(saved.last_run_at, Some(scheduled_at));
+        assert_eq!(saved.last_outcome.as_deref(), Some("task failed"));
+        assert_eq!(saved.next_run_at, Some(Utc.with_ymd_and_hms(2024, 1, 1, 4, 0, 0).unwrap()));
+
+        // 没拿到租约的实例不修改执行记录，启动时只更新 next_run_at
+        assert!(!tick("count", entry, &second, &config, scheduled_at).await);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        update_status(&second, "count", entry, |status| status.next_run_at = None).await;
+        let saved = status(&first, "count").await.unwrap().unwrap();
+        assert_eq!((saved.last_run_at, saved.last_outcome, saved.next_run_at), (Some(scheduled_at), Some("task failed".to_owned()), None));
+
+        let next = Utc.with_ymd_and_hms(2024, 1, 1, 4, 0, 0).unwrap();
+        assert!(tick("count", entry, &second, &config, next).await);
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+        assert_eq!(status(&first, "count").await.unwrap().unwrap().last_run_at, Some(next));
+    }
+
+    struct PanicTask;
+
+    #[async_trait]
+    impl Task for PanicTask {
+        fn name() -> &'static str {
+            "panic"
+        }
+
+        fn cron(&self) -> &str {
+            "0 * * * *"
+        }
+
+        async fn run(&self, _state: &AppState) -> BuboResult<()> {
+            panic!("task panicked");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tick_panic() {
+        let mock = Arc::new(MockRedis::default());
+        let state = testing::mock_state::<TestHooks>(testing::test_config(), &mock).await;
+        let config = SchedulerConfig::default();
+        let mut scheduler = Scheduler::new();
+        scheduler.register(PanicTask, &config).unwrap();
+        let entry = &scheduler.entries["panic"];
+
+        // panic 记录为执行结果，之后的触发照常执行
+        let scheduled_at = Utc.with_ymd_and_hms(2024, 1, 1, 3, 0, 0).unwrap();
+        assert!(tick("panic", entry, &state, &config, scheduled_at).await);
+        let saved = status(&state, "panic").await.unwrap().unwrap();
+        assert!(saved.last_outcome.unwrap().contains("task panicked"));
+        let next = Utc.with_ymd_and_hms(2024, 1, 1, 4, 0, 0).unwrap();
+        assert!(tick("panic", entry, &state, &config, next).await);
+        assert_eq!(status(&state, "panic").await.unwrap().unwrap().last_run_at, Some(next));
+    }
+
+    #[test]
+    fn test_parse_cron() {
+        let schedule = parse_cron("30 2 * * *").unwrap();
+        let after = Utc.with_ymd_and_hms(2024, 1, 1, 3, 0, 0).unwrap();
+        let next = schedule.after(&after).next().unwrap();
+        assert_eq!((next.hour(), next.minute(), next.second()), (2, 30, 0));
+        assert!(parse_cron("*/10 * * * * *").is_ok());
+        assert!(parse_cron("not a cron").is_err());
+    }
+}
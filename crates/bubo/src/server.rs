@@ -1,18 +1,17 @@
-use std::{fmt, future::ready, net::SocketAddr, str::FromStr, time::{Duration, SystemTime}};
+use std::{fmt, future::ready, net::SocketAddr, str::FromStr, sync::{atomic::{AtomicBool, Ordering}, Arc}, time::{Duration, SystemTime}};
 
-use axum::{async_trait, body::Body, error_handling::HandleErrorLayer, http::{Request, Response, HeaderMap, StatusCode, Uri}, middleware, routing::get, BoxError, Json, Router};
+use axum::{async_trait, body::Body, extract::DefaultBodyLimit, error_handling::HandleErrorLayer, http::{Extensions, Request, Response, HeaderMap, StatusCode, Uri}, middleware, routing::get, BoxError, Router};
 use bytes::Bytes;
 use fred::prelude::RedisPool;
 use sea_orm::DatabaseConnection;
 use sea_orm_migration::MigratorTrait;
 use serde::{de, Deserialize, Deserializer};
-use serde_json::json;
-use tokio::signal;
+use tokio::{signal, sync::watch, time::Instant};
 use tower::ServiceBuilder;
-use tower_http::{classify::ServerErrorsFailureClass, cors::{Any, CorsLayer}, request_id::{MakeRequestUuid, RequestId}, trace::TraceLayer, ServiceBuilderExt};
-use tracing::{debug, info, Span};
+use tower_http::{classify::ServerErrorsFailureClass, compression::{predicate::{NotForContentType, Predicate, SizeAbove}, CompressionLayer}, decompression::RequestDecompressionLayer, request_id::{MakeRequestUuid, RequestId}, trace::TraceLayer, ServiceBuilderExt};
+use tracing::{debug, info, warn, Span};
 
-use crate::utils::error::SystemErrorCode;
+use crate::{cache::Cache, i18n::I18n, config::{CompressionConfig, Config, ErrorMode}, controllers::{assets::EmbeddedAssets, middlewares::{cors, error, http_log::{self, HttpLogger}, i18n, rate_limit::{self, RateLimiter}}, routing::{RouteRegistry, Routes}}, mailer::{MailWorker, Mailer}, scheduler::Scheduler, session::{RedisSessionStore, SessionStore}, storage::Storage, views::response::ErrorResponse, worker::Processor, utils::{error::{BuboError, BuboResult, ErrorDetails, SystemErrorCode}, listener::{self, Listener}, prometheus::MetricsRegistry}};
 
 #[derive(Clone)]
 pub struct AppState {
@@ -21,43 +20,266 @@ pub struct AppState {
     pub db: DatabaseConnection,
     // A redis pool used by the application.
     pub redis: RedisPool,
-    pub jwt_secret_access: String,
-    pub jwt_secret_refresh: String,
     // Configuration settings for the application
-    // pub config: Config,
-    // An optional email sender component that can be used to send email.
-    // pub mailer: Option<EmailSender>,
-    // An optional storage instance for the application
-    // pub storage: Arc<Storage>,
-    // Cache instance for the application
-    // pub cache: Arc<cache::Cache>,
+    pub config: Arc<Config>,
+    // 停机通知
+    pub shutdown: Shutdown,
+    // 应用自定义组件，通过 Hooks::extensions 注册
+    pub extensions: Arc<Extensions>,
+    // 邮件组件，配置 mailer.enable 时创建
+    pub mailer: Option<Arc<Mailer>>,
+    // 文件存储，配置 storage.enable 时创建
+    pub storage: Option<Arc<dyn Storage>>,
+    // 缓存，按配置 cache.backend 创建
+    pub cache: Arc<Cache>,
+    // 登录会话
+    pub sessions: Arc<dyn SessionStore>,
+    // Hooks::routes 注册的路由，用于校验菜单权限等
+    pub routes: Arc<RouteRegistry>,
+    // 多语言消息
+    pub i18n: Arc<I18n>,
+}
+
+impl AppState {
+    ///
+    /// 获取应用自定义组件
+    ///
+    pub fn extension<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.extensions.get::<T>()
+    }
+}
+
+///
+/// 停机通知，收到停止信号后所有持有者都能感知，并共享同一个停机截止时间
+///
+#[derive(Clone)]
+pub struct Shutdown {
+    sender: Arc<watch::Sender<Option<Instant>>>,
+    draining: Arc<AtomicBool>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        let (sender, _) = watch::channel(None);
+        Self { sender: Arc::new(sender), draining: Arc::new(AtomicBool::new(false)) }
+    }
+
+    ///
+    /// 停机前的摘流阶段，就绪检查开始返回 503，但仍然正常接收和处理请求
+    ///
+    pub fn drain(&self) {
+        self.draining.store(true, Ordering::Relaxed);
+    }
+
+    ///
+    /// 是否已进入摘流或停机阶段
+    ///
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed) || self.is_shutting_down()
+    }
+
+    ///
+    /// 开始停机，timeout 后为停机截止时间，重复调用不会推迟截止时间
+    ///
+    pub fn trigger(&self, timeout: Duration) {
+        self.sender.send_if_modified(|deadline| {
+            if deadline.is_some() {
+                return false;
+            }
+            *deadline = Some(Instant::now() + timeout);
+            true
+        });
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.sender.borrow().is_some()
+    }
+
+    ///
+    /// 停机截止时间，未开始停机时为 None
+    ///
+    pub fn deadline(&self) -> Option<Instant> {
+        *self.sender.borrow()
+    }
+
+    ///
+    /// 等待停机开始，返回停机截止时间
+    ///
+    pub async fn wait(&self) -> Instant {
+        let mut receiver = self.sender.subscribe();
+        let deadline = match receiver.wait_for(|v| v.is_some()).await {
+            Ok(deadline) => *deadline,
+            Err(_) => None,
+        };
+        deadline.unwrap_or_else(Instant::now)
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[async_trait]
 pub trait Hooks {
     fn app_name() -> &'static str;
-    fn router(state: AppState) -> Router;
-    fn clean_up();
+
+    ///
+    /// 不通过 Hooks::routes 注册的路由，需要自行添加登录和权限中间件
+    ///
+    fn router(_state: AppState) -> Router {
+        Router::new()
+    }
+
+    ///
+    /// 注册应用自定义组件，之后可以通过 AppState::extension 获取
+    ///
+    async fn extensions(_state: &AppState, _extensions: &mut Extensions) -> BuboResult<()> {
+        Ok(())
+    }
+
+    ///
+    /// 服务启动前调用，返回错误时终止启动
+    ///
+    async fn before_run(_state: &AppState) -> BuboResult<()> {
+        Ok(())
+    }
+
+    ///
+    /// 内置中间件添加完成后调用，可以继续添加路由或最外层的中间件
+    ///
+    async fn after_routes(router: Router, _state: &AppState) -> Router {
+        router
+    }
+
+    ///
+    /// 服务停止接收请求并处理完已有连接后调用，超过 deadline 会被中断
+    ///
+    async fn on_shutdown(_state: &AppState, _deadline: Instant) {}
+
+    ///
+    /// 注册后台任务处理器
+    ///
+    fn workers(_processor: &mut Processor, _state: &AppState) {}
+
+    ///
+    /// 注册定时任务
+    ///
+    fn tasks(_scheduler: &mut Scheduler, _state: &AppState) -> BuboResult<()> {
+        Ok(())
+    }
+
+    ///
+    /// 应用路由，按注册的访问控制添加登录和权限中间件，同时用于命令行 `routes`、OpenAPI 文档和菜单权限校验
+    ///
+    fn routes() -> Routes {
+        Routes::new()
+    }
+
+    ///
+    /// 创建管理员，用于命令行 `user create-admin`，password 为明文
+    ///
+    async fn create_admin(_db: &DatabaseConnection, _username: &str, _password: &str) -> BuboResult<()> {
+        Err(BuboError::system_error(SystemErrorCode::NotFound, "create-admin is not supported by this app"))
+    }
+
+    ///
+    /// 添加应用的多语言消息，配置 i18n.dir 中的消息会覆盖这里添加的
+    ///
+    /// ```ignore
+    /// fn i18n(i18n: &mut I18n) {
+    ///     i18n.add_toml(bubo::i18n::EN_US, include_str!("../locales/en-US.toml")).expect("Invalid locale file");
+    /// }
+    /// ```
+    ///
+    fn i18n(_i18n: &mut I18n) {}
+
+    ///
+    /// 注册业务指标的说明和直方图分桶，之后用 `bubo::utils::prometheus::{counter, gauge, histogram}` 记录
+    ///
+    fn metrics(_registry: &mut MetricsRegistry) {}
+
+    ///
+    /// 编译进二进制的前端资源，返回 None 时读取配置 assets.dir
+    ///
+    fn embedded_assets() -> Option<Arc<dyn EmbeddedAssets>> {
+        None
+    }
+
+    fn clean_up() {}
+}
+
+///
+/// 初始化数据库、redis 和各组件，并执行 Hooks::extensions
+///
+pub async fn init_state<H: Hooks, M: MigratorTrait>(config: Config) -> AppState {
+    let db = crate::utils::database::init::<M>(&config.database).await;
+    let redis = crate::utils::redis::init(&config.redis, config.logger.otlp.enable).await;
+    let sessions = Arc::new(RedisSessionStore::new(redis.clone(), H::app_name()));
+    build_state::<H>(config, db, redis, sessions).await
 }
 
-pub async fn main<H: Hooks, M: MigratorTrait>() {
+///
+/// 使用已经连接的数据库、redis 和会话存储创建各组件
+///
+pub(crate) async fn build_state<H: Hooks>(config: Config, db: DatabaseConnection, redis: RedisPool, sessions: Arc<dyn SessionStore>) -> AppState {
+    let mailer = config.mailer.enable
+        .then(|| Mailer::new(&config.mailer).map(Arc::new).unwrap_or_else(|e| panic!("初始化邮件组件失败: {e}")));
+    let cache = Cache::from_config(H::app_name(), &config.cache, &redis, &config.redis)
+        .await
+        .unwrap_or_else(|e| panic!("初始化缓存失败: {e}"));
+    let storage = config.storage.enable
+        .then(|| crate::storage::from_config(&config.storage).unwrap_or_else(|e| panic!("初始化文件存储失败: {e}")));
+    let i18n = crate::i18n::init::<H>(&config.i18n).unwrap_or_else(|e| panic!("初始化多语言消息失败: {e}"));
+    let routes = Arc::new(routes::<H>(&config).registry());
+
+    let mut state = AppState { app_name: H::app_name(), db, redis, config: Arc::new(config), shutdown: Shutdown::new(), 
+        extensions: Arc::new(Extensions::new()), mailer, storage, cache: Arc::new(cache), sessions, routes, i18n };
+    let mut extensions = Extensions::new();
+    H::extensions(&state, &mut extensions).await.unwrap_or_else(|e| panic!("初始化应用组件失败: {e}"));
+    state.extensions = Arc::new(extensions);
+    state
+}
+
+///
+/// 启动服务，收到停止信号后优雅停机
+///
+pub async fn serve<H: Hooks, M: MigratorTrait + 'static>(config: Config) {
     let init_date_time = SystemTime::now();
-    // 初始化环境变量
-    dotenvy::dotenv().ok();
-    // for (key, value) in std::env::vars() {
-    //     println!("{key}: {value}");
-    // }
-    // 初始化日志
-    let (_guard_stdout, _guard_file) = crate::utils::log::init("./logs", "admin-api.log");
-
-    let db = crate::utils::database::init::<M>().await;
-    let redis = crate::utils::redis::init().await;
-    let jwt_secret_access = std::env::var("JWT_SECRET_ACCESS").expect("JWT_SECRET_ACCESS is not set in .env file");
-    let jwt_secret_refresh = std::env::var("JWT_SECRET_REFRESH").expect("JWT_SECRET_REFRESH is not set in .env file");
-
-    let state = AppState { app_name: H::app_name(), db, redis, jwt_secret_access, jwt_secret_refresh };
-    
-    let (_main_server, _metrics_server) = tokio::join!(start_main_server(H::router(state.clone()), state), start_metrics_server());
+    info!("当前环境: {}", config.environment);
+    let state = init_state::<H, M>(config).await;
+    H::before_run(&state).await.unwrap_or_else(|e| panic!("before_run 执行失败: {e}"));
+
+    let shutdown = state.shutdown.clone();
+    let shutdown_timeout = Duration::from_secs(state.config.server.shutdown_timeout);
+    let drain_delay = Duration::from_secs(state.config.server.drain_delay);
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        shutdown.drain();
+        if !drain_delay.is_zero() {
+            info!("就绪检查已返回 503，{} 秒后停止接收新连接", drain_delay.as_secs());
+            // 等待期间再次收到停止信号时立即停机
+            let _ = tokio::time::timeout(drain_delay, shutdown_signal()).await;
+        }
+        info!("开始优雅停机");
+        shutdown.trigger(shutdown_timeout);
+    });
+
+    let app = build_app::<H, M>(&state).await;
+    let mut processor = Processor::new();
+    if let Some(mailer) = &state.mailer {
+        processor.register(MailWorker(mailer.clone()));
+    }
+    H::workers(&mut processor, &state);
+    let mut scheduler = Scheduler::new();
+    H::tasks(&mut scheduler, &state).unwrap_or_else(|e| panic!("注册定时任务失败: {e}"));
+    let (_main_server, _metrics_server, _worker, _scheduler) = tokio::join!(start_main_server(app, state.clone()), 
+        start_metrics_server::<H>(state.clone()), processor.run(state.clone()), scheduler.run(state.clone()));
+    let deadline = state.shutdown.deadline().unwrap_or_else(|| Instant::now() + shutdown_timeout);
+    if tokio::time::timeout_at(deadline, H::on_shutdown(&state, deadline)).await.is_err() {
+        warn!("on_shutdown 执行超时");
+    }
     info!("开始清理资源");
     H::clean_up();
     info!("清理资源完成");
@@ -65,32 +287,54 @@ pub async fn main<H: Hooks, M: MigratorTrait>() {
     info!("本次运行时间: {}", Wrapper(SystemTime::now().duration_since(init_date_time).unwrap_or_default()));
 }
 
-async fn start_main_server(router: Router, state: AppState) {
-    let app = main_app(router, state.clone());
+///
+/// Hooks::routes 加上内置的需要登录的路由
+///
+pub(crate) fn routes<H: Hooks>(config: &Config) -> Routes {
+    H::routes().merge(crate::controllers::log_level::routes(&config.logger.reload))
+}
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:8080")
+///
+/// 应用路由加上内置路由和中间件
+///
+pub(crate) async fn build_app<H: Hooks, M: MigratorTrait + 'static>(state: &AppState) -> Router {
+    let mut router = H::router(state.clone())
+        .merge(routes::<H>(&state.config).into_router(state))
+        .merge(crate::controllers::health::init_routes::<M>(state.clone()));
+    if state.storage.is_some() {
+        router = router.merge(crate::controllers::storage::init_routes(state.clone()));
+    }
+    if state.config.openapi.enable {
+        let document = crate::openapi::document(state.routes.routes(), H::app_name(), &state.config.openapi, state.config.server.error_mode);
+        router = router.merge(crate::controllers::openapi::init_routes(&state.config.openapi, document));
+    }
+    let assets = state.config.assets.enable.then(|| crate::controllers::assets::fallback(&state.config.assets, H::embedded_assets()));
+    let app = main_app(router, state.clone(), assets);
+    H::after_routes(app, state).await
+}
+
+async fn start_main_server(app: Router, state: AppState) {
+    let config = &state.config.server;
+    let tls = config.tls.as_ref().map(|tls| crate::utils::tls::acceptor(tls, &state.shutdown).expect("Failed to load tls certificate"));
+    let listener = Listener::bind(config)
         .await
         .unwrap();
-    tracing::info!("listening on {}", listener.local_addr().unwrap());
-    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
-    .with_graceful_shutdown(shutdown_signal()).await.unwrap();
+    listener::serve(listener, tls, app, state.shutdown.wait()).await;
 }
 
-fn main_app(router: Router, _state: AppState) -> Router {
-    // -- Cors
-    let cors = CorsLayer::new()
-        .allow_methods(Any)
-        .allow_origin(Any)
-        .allow_headers(Any)
-        // .max_age(Duration::from_secs(60) * 10)
-    ;
+fn main_app(router: Router, state: AppState, assets: Option<Router>) -> Router {
+    // -- Cors，配置已在加载时校验
+    let cors = cors::layer(&state.config.cors).expect("Invalid cors config");
     
     let trace_layer = ServiceBuilder::new()
         .set_x_request_id(MakeRequestUuid::default())
         .layer(TraceLayer::new_for_http()
             .make_span_with(|request: &Request<Body>| {
-                let id = request.extensions().get::<RequestId>().unwrap().header_value().to_str().unwrap();
-                tracing::info_span!("request", %id)
+                let request_id = request.extensions().get::<RequestId>().unwrap().header_value().to_str().unwrap();
+                let span = tracing::info_span!("request", %request_id);
+                // 上游的 traceparent
+                crate::utils::log::set_parent_from_headers(&span, request.headers());
+                span
             })
             .on_request(|request: &Request<Body>, _span: &Span| {
                 tracing::debug!("started {} {}", request.method(), request.uri().path())
@@ -99,7 +343,8 @@ fn main_app(router: Router, _state: AppState) -> Router {
                 tracing::debug!("response generated in {:?}", latency)
             })
             .on_body_chunk(|chunk: &Bytes, _latency: Duration, _span: &Span| {
-                tracing::debug!("sending {} bytes: {}", chunk.len(), String::from_utf8(chunk.to_vec()).unwrap_or_default())
+                // 响应内容由 http_log 中间件脱敏后记录
+                tracing::debug!("sending {} bytes", chunk.len())
             })
             .on_eos(|_trailers: Option<&HeaderMap>, stream_duration: Duration, _span: &Span| {
                 tracing::debug!("stream closed after {:?}", stream_duration)
@@ -111,37 +356,97 @@ fn main_app(router: Router, _state: AppState) -> Router {
         .propagate_x_request_id()
     ;
 
-    Router::new().merge(router)
+    let mut router = Router::new().merge(router);
+    // 前端资源和找不到路由的响应放在各层中间件之内，同样会被压缩和转换错误格式
+    if let Some(assets) = assets {
+        router = router.fallback_service(assets);
+    } else {
+        router = router.fallback(json_fallback);
+    }
+    // 全局限流，按 rate_limit.global 对应的分组
+    if let Some(global) = state.config.rate_limit.global.as_deref().filter(|_| state.config.rate_limit.enable) {
+        router = router.layer(middleware::from_fn_with_state(RateLimiter::new(&state, global), rate_limit::limit));
+    }
+
+    let config = &state.config.server;
+    router = router
     .layer(
         ServiceBuilder::new()
             .layer(HandleErrorLayer::new(handle_timeout_error))
             .load_shed()
-            .concurrency_limit(1024)
-            // .rate_limit(100, Duration::from_secs(1))
-            .timeout(Duration::from_secs(60))
-            .layer(TraceLayer::new_for_http()),
+            .concurrency_limit(config.concurrency_limit)
+            .timeout(Duration::from_secs(config.request_timeout))
+            // 只记录路径，query 中可能有 token 等敏感参数
+            .layer(TraceLayer::new_for_http().make_span_with(|request: &Request<Body>| {
+                tracing::debug_span!("request", method = %request.method(), path = %request.uri().path(), version = ?request.version())
+            })),
     )
+    .layer(DefaultBodyLimit::max(usize::try_from(config.body_limit).unwrap_or(usize::MAX)));
+    // 在压缩之内转换错误响应，可以读取 trace_layer 设置的请求 id
+    if config.error_mode != ErrorMode::Legacy {
+        router = router.layer(middleware::from_fn_with_state(config.error_mode, error::error_mode));
+    }
+    // 按 Accept-Language 选择语言，包括超时和找不到路由的错误消息
+    router = router.layer(middleware::from_fn_with_state(state.i18n.clone(), i18n::locale));
+    // 脱敏后记录请求和响应，在解压和压缩之内
+    if state.config.logger.http.enable {
+        let logger = HttpLogger::new(&state.config.logger.http, usize::try_from(config.body_limit).unwrap_or(usize::MAX));
+        router = router.layer(middleware::from_fn_with_state(Arc::new(logger), http_log::log));
+    }
+    if config.request_decompression {
+        router = router.layer(RequestDecompressionLayer::new());
+    }
+    if config.compression.enable {
+        router = router.layer(compression_layer(&config.compression));
+    }
+
+    router = router
     .layer(cors)
     .layer(trace_layer)
-    .layer(middleware::from_fn(crate::utils::prometheus::track_metrics))
-    .fallback(json_fallback)
+    .layer(middleware::from_fn(crate::utils::prometheus::track_metrics));
+    router
+}
+
+///
+/// 响应压缩，不压缩小于 min_size 的响应以及图片、gRPC 和 SSE
+///
+fn compression_layer(config: &CompressionConfig) -> CompressionLayer<impl Predicate> {
+    let predicate = SizeAbove::new(config.min_size)
+        .and(NotForContentType::GRPC)
+        .and(NotForContentType::IMAGES)
+        .and(NotForContentType::SSE);
+    CompressionLayer::new()
+        .gzip(config.gzip)
+        .br(config.br)
+        .zstd(config.zstd)
+        .no_deflate()
+        .compress_when(predicate)
 }
 
-fn metrics_app() -> Router {
-    let recorder_handle = crate::utils::prometheus::setup_metrics_recorder();
-    Router::new().route("/metrics", get(move || ready(recorder_handle.render())))
+fn metrics_app<H: Hooks>(state: AppState) -> Router {
+    let mut registry = MetricsRegistry::builtin();
+    H::metrics(&mut registry);
+    let recorder_handle = crate::utils::prometheus::setup_metrics_recorder(&registry);
+    Router::new().route("/metrics", get(move || {
+        crate::utils::prometheus::collect(&state);
+        ready(recorder_handle.render())
+    }))
 }
 
-async fn start_metrics_server() {
-    let app = metrics_app();
+async fn start_metrics_server<H: Hooks>(state: AppState) {
+    if !state.config.metrics.enable {
+        return;
+    }
+    let app = metrics_app::<H>(state.clone());
 
     // NOTE: expose metrics endpoint on a different port
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3001")
+    let addr = format!("{}:{}", state.config.metrics.host, state.config.metrics.port);
+    let listener = tokio::net::TcpListener::bind(addr)
         .await
         .unwrap();
     tracing::info!("listening on {}", listener.local_addr().unwrap());
     axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
-    .with_graceful_shutdown(shutdown_signal()).await.unwrap();
+    .with_graceful_shutdown(async move { state.shutdown.wait().await; }).await.unwrap();
 }
 
 ///
@@ -176,46 +481,28 @@ async fn shutdown_signal() {
 ///
 /// 处理超时
 ///
-async fn handle_timeout_error(uri: Uri, error: BoxError) -> (StatusCode, Json<serde_json::Value>) {
+async fn handle_timeout_error(uri: Uri, error: BoxError) -> ErrorDetails {
     if error.is::<tower::timeout::error::Elapsed>() {
         debug!("请求超时: {}", uri.path());
-        let error_result = Json(json!({
-            "status": false,
-            "error_code": SystemErrorCode::RequestTimeout as usize,
-            "error_message": "请求超时",
-        }));
-        (StatusCode::REQUEST_TIMEOUT, error_result)
+        metrics::counter!("http_rejections_total", "reason" => "timeout").increment(1);
+        ErrorDetails::new(StatusCode::REQUEST_TIMEOUT, ErrorResponse::new(SystemErrorCode::RequestTimeout, crate::i18n::t(SystemErrorCode::RequestTimeout.key())))
     } else if error.is::<tower::load_shed::error::Overloaded>() {
         debug!("服务过载: {}", uri.path());
-        let error_result = Json(json!({
-            "status": false,
-            "error_code": SystemErrorCode::ServiceUnavailable as usize,
-            "error_message": "服务过载，请稍后再试",
-        }));
-        (StatusCode::SERVICE_UNAVAILABLE, error_result)
+        metrics::counter!("http_rejections_total", "reason" => "overloaded").increment(1);
+        ErrorDetails::new(StatusCode::SERVICE_UNAVAILABLE, ErrorResponse::new(SystemErrorCode::ServiceUnavailable, crate::i18n::t(SystemErrorCode::ServiceUnavailable.key())))
     } else {
         debug!("未处理的内部错误: {}, {}", uri.path(), error);
-        let error_result = Json(json!({
-            "status": false,
-            "error_code": SystemErrorCode::InternalServerError as usize,
-            "error_message": "未处理的内部错误",
-        }));
-        (StatusCode::INTERNAL_SERVER_ERROR, error_result)
+        ErrorDetails::new(StatusCode::INTERNAL_SERVER_ERROR, ErrorResponse::new(SystemErrorCode::InternalServerError, crate::i18n::t(SystemErrorCode::InternalServerError.key())))
     }
 }
 
 ///
 /// 找不到路由地址
 ///
-async fn json_fallback(uri: Uri) -> (StatusCode, Json<serde_json::Value>) {
-    let msg = format!("找不到路由: {}", uri.path());
+pub(crate) async fn json_fallback(uri: Uri) -> ErrorDetails {
+    let msg = crate::i18n::t_args("route_not_found", &[("path", &uri.path())]);
     debug!("{}", &msg);
-    let error_result = Json(json!({
-        "status": false,
-        "error_code": SystemErrorCode::NotFound as usize,
-        "error_message": msg,
-    }));
-    (StatusCode::NOT_FOUND, error_result)
+    ErrorDetails::new(StatusCode::NOT_FOUND, ErrorResponse::new(SystemErrorCode::NotFound, msg))
 }
 
 
@@ -261,3 +548,87 @@ impl std::fmt::Display for Wrapper {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use axum::{http::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE}, routing::post, Json};
+    use flate2::{write::GzEncoder, Compression};
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    use crate::testing::{self, MockRedis};
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    struct TestHooks;
+
+    impl Hooks for TestHooks {
+        fn app_name() -> &'static str {
+            "bubo-test"
+        }
+    }
+
+    async fn test_app() -> Router {
+        let mut config = testing::test_config();
+        config.server.body_limit = 1024;
+        config.server.compression.min_size = 256;
+        let state = testing::mock_state::<TestHooks>(config, &Arc::new(MockRedis::default())).await;
+        let router = Router::new()
+            .route("/small", get(|| async { "a".repeat(255) }))
+            .route("/large", get(|| async { "a".repeat(256) }))
+            .route("/image", get(|| async { ([(CONTENT_TYPE, "image/png")], vec![0u8; 2048]) }))
+            .route("/events", get(|| async { ([(CONTENT_TYPE, "text/event-stream")], "data: a\n\n".repeat(256)) }))
+            .route("/echo", post(|Json(value): Json<Value>| async move { Json(value) }));
+        main_app(router, state, None)
+    }
+
+    async fn content_encoding(app: &Router, uri: &str) -> Option<String> {
+        let request = Request::get(uri).header(ACCEPT_ENCODING, "gzip").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        response.headers().get(CONTENT_ENCODING).map(|value| value.to_str().unwrap().to_owned())
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    async fn post_gzip(app: &Router, value: &Value) -> Response<Body> {
+        let request = Request::post("/echo")
+            .header(CONTENT_TYPE, "application/json")
+            .header(CONTENT_ENCODING, "gzip")
+            .body(Body::from(gzip(&serde_json::to_vec(value).unwrap())))
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_compression() {
+        let app = test_app().await;
+        assert_eq!(content_encoding(&app, "/small").await, None);
+        assert_eq!(content_encoding(&app, "/large").await.as_deref(), Some("gzip"));
+        // 图片和 SSE 不压缩
+        assert_eq!(content_encoding(&app, "/image").await, None);
+        assert_eq!(content_encoding(&app, "/events").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_request_decompression() {
+        let app = test_app().await;
+        let value = json!({"name": "bubo"});
+        let response = post_gzip(&app, &value).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), value);
+
+        // 压缩后小于 body_limit，解压后超过
+        let value = json!({"data": "a".repeat(2048)});
+        assert!(gzip(&serde_json::to_vec(&value).unwrap()).len() < 1024);
+        assert_eq!(post_gzip(&app, &value).await.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
@@ -0,0 +1,88 @@
+use std::{collections::HashMap, sync::Mutex, time::{Duration, Instant}};
+
+use async_trait::async_trait;
+use fred::{prelude::RedisPool, types::Expiration};
+
+use crate::{controllers::middlewares::auth::AuthUser, utils::{error::BuboResult, redis}};
+
+///
+/// 登录会话存储，按用户 id 保存 AuthUser
+///
+#[async_trait]
+pub trait SessionStore: Send + Sync + 'static {
+    async fn get(&self, user_id: i64) -> BuboResult<Option<AuthUser>>;
+
+    async fn set(&self, auth_user: &AuthUser, ttl: Duration) -> BuboResult<()>;
+
+    async fn remove(&self, user_id: i64) -> BuboResult<()>;
+}
+
+///
+/// 保存在 redis，key 为 `{app}:auth-user:{id}`
+///
+pub struct RedisSessionStore {
+    redis: RedisPool,
+    app_name: &'static str,
+}
+
+impl RedisSessionStore {
+    pub fn new(redis: RedisPool, app_name: &'static str) -> Self {
+        Self { redis, app_name }
+    }
+
+    fn key(&self, user_id: i64) -> String {
+        redis::gen_key(self.app_name, "auth-user", user_id)
+    }
+}
+
+#[async_trait]
+impl SessionStore for RedisSessionStore {
+    async fn get(&self, user_id: i64) -> BuboResult<Option<AuthUser>> {
+        redis::get(&self.redis, self.key(user_id)).await
+    }
+
+    async fn set(&self, auth_user: &AuthUser, ttl: Duration) -> BuboResult<()> {
+        let expire = Expiration::EX(ttl.as_secs().max(1) as i64);
+        redis::set(&self.redis, self.key(auth_user.id), auth_user, Some(expire)).await
+    }
+
+    async fn remove(&self, user_id: i64) -> BuboResult<()> {
+        redis::del(&self.redis, self.key(user_id)).await
+    }
+}
+
+///
+/// 进程内存储，用于测试和单实例部署
+///
+#[derive(Default)]
+pub struct MemorySessionStore {
+    sessions: Mutex<HashMap<i64, (AuthUser, Instant)>>,
+}
+
+impl MemorySessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl SessionStore for MemorySessionStore {
+    async fn get(&self, user_id: i64) -> BuboResult<Option<AuthUser>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let expired = sessions.get(&user_id).is_some_and(|(_, expires_at)| *expires_at <= Instant::now());
+        if expired {
+            sessions.remove(&user_id);
+        }
+        Ok(sessions.get(&user_id).map(|(auth_user, _)| auth_user.clone()))
+    }
+
+    async fn set(&self, auth_user: &AuthUser, ttl: Duration) -> BuboResult<()> {
+        self.sessions.lock().unwrap().insert(auth_user.id, (auth_user.clone(), Instant::now() + ttl));
+        Ok(())
+    }
+
+    async fn remove(&self, user_id: i64) -> BuboResult<()> {
+        self.sessions.lock().unwrap().remove(&user_id);
+        Ok(())
+    }
+}
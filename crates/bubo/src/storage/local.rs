@@ -0,0 +1,108 @@
+use std::{io::ErrorKind, path::{Path, PathBuf}};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+
+use crate::utils::error::BuboResult;
+
+use super::{storage_error, validate_key, Storage, StoredObject};
+
+///
+/// 本地文件系统存储，类型保存在同目录的 `.{文件名}.content-type` 中
+///
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> BuboResult<PathBuf> {
+        validate_key(key)?;
+        Ok(self.root.join(key))
+    }
+}
+
+fn content_type_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.content-type", name))
+}
+
+async fn remove_if_exists(path: &Path) -> BuboResult<()> {
+    match tokio::fs::remove_file(path).await {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(storage_error(e)),
+        _ => Ok(()),
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn put(&self, key: &str, data: Bytes, content_type: Option<&str>) -> BuboResult<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await.map_err(storage_error)?;
+        }
+        // 先写类型，下载时不会按扩展名推断出错误的类型
+        match content_type {
+            Some(content_type) => tokio::fs::write(content_type_path(&path), content_type).await.map_err(storage_error)?,
+            None => remove_if_exists(&content_type_path(&path)).await?,
+        }
+        // 先写临时文件再重命名，避免读到写了一半的文件
+        let tmp = path.with_file_name(format!(".{}.tmp", uuid::Uuid::new_v4().simple()));
+        tokio::fs::write(&tmp, &data).await.map_err(storage_error)?;
+        if let Err(e) = tokio::fs::rename(&tmp, &path).await {
+            let _ = tokio::fs::remove_file(&tmp).await;
+            return Err(storage_error(e));
+        }
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> BuboResult<Option<StoredObject>> {
+        let path = self.path(key)?;
+        let data = match tokio::fs::read(&path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(storage_error(e)),
+        };
+        let content_type = match tokio::fs::read_to_string(content_type_path(&path)).await {
+            Ok(content_type) => Some(content_type),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(storage_error(e)),
+        };
+        Ok(Some(StoredObject { data: data.into(), content_type }))
+    }
+
+    async fn delete(&self, key: &str) -> BuboResult<()> {
+        let path = self.path(key)?;
+        remove_if_exists(&path).await?;
+        remove_if_exists(&content_type_path(&path)).await
+    }
+
+    async fn exists(&self, key: &str) -> BuboResult<bool> {
+        tokio::fs::try_exists(self.path(key)?).await.map_err(storage_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn test_local_storage() {
+        let root = std::env::temp_dir().join(format!("bubo-storage-{}", uuid::Uuid::new_v4().simple()));
+        let storage = LocalStorage::new(&root);
+        storage.put("a/b.txt", Bytes::from_static(b"hello"), Some("text/plain")).await.unwrap();
+        assert!(storage.exists("a/b.txt").await.unwrap());
+        assert_eq!(storage.get("a/b.txt").await.unwrap().unwrap(), StoredObject { data: Bytes::from_static(b"hello"), content_type: Some("text/plain".to_owned()) });
+        storage.put("a/b.txt", Bytes::from_static(b"world"), None).await.unwrap();
+        assert_eq!(storage.get("a/b.txt").await.unwrap().unwrap().content_type, None);
+        assert!(storage.get("../b.txt").await.is_err());
+        storage.delete("a/b.txt").await.unwrap();
+        storage.delete("a/b.txt").await.unwrap();
+        assert_eq!(storage.get("a/b.txt").await.unwrap(), None);
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
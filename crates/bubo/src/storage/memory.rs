@@ -0,0 +1,50 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+
+use crate::utils::error::BuboResult;
+
+use super::{validate_key, Storage, StoredObject};
+
+///
+/// 内存存储，用于测试
+///
+#[derive(Default)]
+pub struct MemoryStorage {
+    objects: RwLock<HashMap<String, StoredObject>>,
+}
+
+impl MemoryStorage {
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<_> = self.objects.read().unwrap().keys().cloned().collect();
+        keys.sort_unstable();
+        keys
+    }
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn put(&self, key: &str, data: Bytes, content_type: Option<&str>) -> BuboResult<()> {
+        validate_key(key)?;
+        let object = StoredObject { data, content_type: content_type.map(ToOwned::to_owned) };
+        self.objects.write().unwrap().insert(key.to_owned(), object);
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> BuboResult<Option<StoredObject>> {
+        validate_key(key)?;
+        Ok(self.objects.read().unwrap().get(key).cloned())
+    }
+
+    async fn delete(&self, key: &str) -> BuboResult<()> {
+        validate_key(key)?;
+        self.objects.write().unwrap().remove(key);
+        Ok(())
+    }
+
+    async fn exists(&self, key: &str) -> BuboResult<bool> {
+        validate_key(key)?;
+        Ok(self.objects.read().unwrap().contains_key(key))
+    }
+}
@@ -0,0 +1,185 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::{config::{StorageBackend, StorageConfig}, server::AppState, utils::{error::{BuboError, BuboResult, BusinessErrorCode, SystemErrorCode}, time::current_timestamp_sec}};
+
+pub mod local;
+pub mod memory;
+pub mod s3;
+pub mod upload;
+
+pub use local::LocalStorage;
+pub use memory::MemoryStorage;
+pub use s3::S3Storage;
+
+///
+/// 存储的文件
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredObject {
+    pub data: Bytes,
+    // 存储方式不保存类型时为 None，按扩展名推断
+    pub content_type: Option<String>,
+}
+
+///
+/// 文件存储，key 为 `/` 分隔的相对路径
+///
+#[async_trait]
+pub trait Storage: Send + Sync + 'static {
+    async fn put(&self, key: &str, data: Bytes, content_type: Option<&str>) -> BuboResult<()>;
+
+    ///
+    /// 文件不存在时返回 None
+    ///
+    async fn get(&self, key: &str) -> BuboResult<Option<StoredObject>>;
+
+    ///
+    /// 删除文件，文件不存在时不报错
+    ///
+    async fn delete(&self, key: &str) -> BuboResult<()>;
+
+    async fn exists(&self, key: &str) -> BuboResult<bool>;
+}
+
+///
+/// 按配置创建存储
+///
+pub fn from_config(config: &StorageConfig) -> BuboResult<Arc<dyn Storage>> {
+    Ok(match config.backend {
+        StorageBackend::Local => Arc::new(LocalStorage::new(&config.root)),
+        StorageBackend::Memory => Arc::new(MemoryStorage::default()),
+        StorageBackend::S3 => Arc::new(S3Storage::new(config.s3.clone())?),
+    })
+}
+
+///
+/// 获取存储组件，未启用时返回错误
+///
+pub fn storage(state: &AppState) -> BuboResult<&Arc<dyn Storage>> {
+    state.storage.as_ref().ok_or_else(|| storage_error("storage is not enabled"))
+}
+
+pub(crate) fn storage_error(e: impl ToString) -> BuboError {
+    BuboError::system_error(SystemErrorCode::StorageError, e.to_string())
+}
+
+///
+/// 校验 key，不允许绝对路径和 `..`，避免访问存储目录以外的文件
+///
+pub fn validate_key(key: &str) -> BuboResult<()> {
+    let valid = !key.is_empty()
+        && key.len() <= 1024
+        && !key.starts_with('/')
+        && !key.contains('\\')
+        && !key.contains('\0')
+        && key.split('/').all(|segment| !segment.is_empty() && segment != "." && segment != "..");
+    if valid {
+        Ok(())
+    } else {
+        Err(storage_error(format!("invalid storage key `{}`", key)))
+    }
+}
+
+///
+/// 生成带签名的下载地址，expire 为 None 时使用配置 storage.url_expire
+///
+pub fn signed_url(config: &StorageConfig, key: &str, expire: Option<Duration>) -> String {
+    let expires = current_timestamp_sec() + expire.map_or(config.url_expire, |d| d.as_secs()) as i64;
+    format!("{}/{}?expires={}&signature={}", config.url_prefix.trim_end_matches('/'), encode_key(key), expires,
+        sign(&config.url_secret, key, expires))
+}
+
+///
+/// 校验下载地址的签名和有效期
+///
+pub fn verify_signature(config: &StorageConfig, key: &str, expires: i64, signature: &str) -> BuboResult<()> {
+    if expires < current_timestamp_sec() {
+        return Err(BuboError::business_error(BusinessErrorCode::Forbidden, "storage.link_expired"));
+    }
+    let signature = hex::decode(signature).map_err(|_| BuboError::business_error(BusinessErrorCode::Forbidden, "storage.invalid_signature"))?;
+    mac(&config.url_secret, key, expires)
+        .verify_slice(&signature)
+        .map_err(|_| BuboError::business_error(BusinessErrorCode::Forbidden, "storage.invalid_signature"))
+}
+
+fn mac(secret: &str, key: &str, expires: i64) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(key.as_bytes());
+    mac.update(b"\n");
+    mac.update(expires.to_string().as_bytes());
+    mac
+}
+
+fn sign(secret: &str, key: &str, expires: i64) -> String {
+    hex::encode(mac(secret, key, expires).finalize().into_bytes())
+}
+
+///
+/// 按 RFC 3986 编码 key，保留 `/`
+///
+pub(crate) fn encode_key(key: &str) -> String {
+    let mut encoded = String::with_capacity(key.len());
+    for byte in key.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+///
+/// 按扩展名推断类型
+///
+pub fn guess_content_type(key: &str) -> String {
+    mime_guess::from_path(key).first_or_octet_stream().essence_str().to_owned()
+}
+
+///
+/// 浏览器直接打开也不会执行脚本的图片类型，其余类型（包括 SVG、HTML）下载时按附件返回
+///
+pub fn is_inline_safe(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or_default().trim();
+    ["image/png", "image/jpeg", "image/gif", "image/webp", "image/avif", "image/bmp"]
+        .iter()
+        .any(|safe| essence.eq_ignore_ascii_case(safe))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_validate_key() {
+        assert!(validate_key("avatars/2024/01/a.png").is_ok());
+        for key in ["", "/etc/passwd", "a/../b", "a//b", "./a", "a\\b"] {
+            assert!(validate_key(key).is_err(), "{key}");
+        }
+    }
+
+    #[test]
+    fn test_signed_url() {
+        let config = StorageConfig { url_secret: "secret".to_owned(), ..Default::default() };
+        let url = signed_url(&config, "a b/中.png", None);
+        assert!(url.starts_with("/files/a%20b/%E4%B8%AD.png?expires="));
+        let query = url.split_once('?').unwrap().1;
+        let (expires, signature) = query.split_once('&').unwrap();
+        let expires: i64 = expires.trim_start_matches("expires=").parse().unwrap();
+        let signature = signature.trim_start_matches("signature=");
+        assert!(verify_signature(&config, "a b/中.png", expires, signature).is_ok());
+        assert!(verify_signature(&config, "other.png", expires, signature).is_err());
+        assert!(verify_signature(&config, "a b/中.png", expires + 1, signature).is_err());
+        let expired = sign(&config.url_secret, "a.png", current_timestamp_sec() - 1);
+        assert!(verify_signature(&config, "a.png", current_timestamp_sec() - 1, &expired).is_err());
+        assert_eq!(guess_content_type("a/b.png"), "image/png");
+        assert!(is_inline_safe("Image/PNG; charset=binary"));
+        assert!(!is_inline_safe("image/svg+xml"));
+        assert!(!is_inline_safe("text/html"));
+    }
+}
@@ -0,0 +1,185 @@
+use axum::extract::{multipart::MultipartError, DefaultBodyLimit, Multipart};
+use bytes::BytesMut;
+use mime_guess::mime::Mime;
+use schemars::JsonSchema;
+use serde::Serialize;
+use time::macros::format_description;
+use tracing::warn;
+
+use crate::{config::StorageConfig, i18n, server::AppState, utils::{error::{BuboError, BuboResult, BusinessErrorCode}, time::now_utc}};
+
+use super::{guess_content_type, signed_url, storage, storage_error};
+
+// multipart 边界和表单字段的额外空间
+const MULTIPART_OVERHEAD: u64 = 64 * 1024;
+
+///
+/// 上传限制
+///
+#[derive(Debug, Clone)]
+pub struct UploadPolicy {
+    // 保存路径前缀，例如 `avatars`
+    pub prefix: String,
+    // 单个文件大小上限（字节）
+    pub max_size: u64,
+    // 一次请求最多文件数
+    pub max_files: usize,
+    // 允许的类型，支持 `image/*`，为空时不限制
+    pub allowed_types: Vec<String>,
+}
+
+impl UploadPolicy {
+    ///
+    /// 使用配置 storage.max_upload_size、storage.allowed_types
+    ///
+    pub fn from_config(config: &StorageConfig, prefix: impl Into<String>) -> Self {
+        Self { prefix: prefix.into(), max_size: config.max_upload_size, max_files: 10, allowed_types: config.allowed_types.clone() }
+    }
+
+    pub fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    pub fn max_files(mut self, max_files: usize) -> Self {
+        self.max_files = max_files;
+        self
+    }
+
+    pub fn allowed_types<S: Into<String>>(mut self, allowed_types: impl IntoIterator<Item = S>) -> Self {
+        self.allowed_types = allowed_types.into_iter().map(Into::into).collect();
+        self
+    }
+
+    ///
+    /// 上传路由需要放宽默认 2MB 的请求体限制，`.layer(policy.body_limit())`
+    ///
+    pub fn body_limit(&self) -> DefaultBodyLimit {
+        let limit = self.max_size.saturating_mul(self.max_files as u64).saturating_add(MULTIPART_OVERHEAD);
+        DefaultBodyLimit::max(usize::try_from(limit).unwrap_or(usize::MAX))
+    }
+
+    pub fn allows(&self, content_type: &str) -> bool {
+        let content_type = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        self.allowed_types.is_empty() || self.allowed_types.iter().any(|allowed| {
+            let allowed = allowed.to_ascii_lowercase();
+            match allowed.strip_suffix("/*") {
+                Some(kind) => allowed == "*/*" || content_type.split('/').next() == Some(kind),
+                None => allowed == content_type,
+            }
+        })
+    }
+}
+
+///
+/// 已保存的文件
+///
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct UploadedFile {
+    // 表单字段名
+    pub field: String,
+    pub key: String,
+    pub file_name: String,
+    pub content_type: String,
+    pub size: u64,
+    // 带签名的下载地址
+    pub url: String,
+}
+
+///
+/// 保存 multipart 请求中的文件，非文件字段忽略，任意文件不符合限制时删除已保存的文件并返回错误
+///
+pub async fn save_multipart(state: &AppState, mut multipart: Multipart, policy: &UploadPolicy) -> BuboResult<Vec<UploadedFile>> {
+    let storage = storage(state)?;
+    let mut files: Vec<UploadedFile> = Vec::new();
+    let result: BuboResult<()> = async {
+        while let Some(mut field) = multipart.next_field().await.map_err(multipart_error)? {
+            let Some(file_name) = field.file_name().map(ToOwned::to_owned) else {
+                continue;
+            };
+            if files.len() >= policy.max_files {
+                return Err(BuboError::business_error(BusinessErrorCode::FormRejectionError, i18n::t_args("upload.too_many_files", &[("max", &policy.max_files)])));
+            }
+            let content_type = field.content_type().map(ToOwned::to_owned).unwrap_or_else(|| guess_content_type(&file_name));
+            // 只保存 `type/subtype`，无法解析的类型不允许上传
+            let content_type = match content_type.parse::<Mime>() {
+                Ok(mime) if policy.allows(mime.essence_str()) => mime.essence_str().to_owned(),
+                _ => return Err(BuboError::business_error(BusinessErrorCode::FileTypeNotAllowed, i18n::t_args("upload.file_type_not_allowed", &[("type", &content_type)]))),
+            };
+            let mut data = BytesMut::new();
+            while let Some(chunk) = field.chunk().await.map_err(multipart_error)? {
+                if (data.len() + chunk.len()) as u64 > policy.max_size {
+                    return Err(BuboError::business_error(BusinessErrorCode::FileTooLarge, i18n::t_args("upload.file_too_large", &[("max", &policy.max_size)])));
+                }
+                data.extend_from_slice(&chunk);
+            }
+            let key = new_key(&policy.prefix, &file_name, &content_type)?;
+            let size = data.len() as u64;
+            storage.put(&key, data.freeze(), Some(&content_type)).await?;
+            files.push(UploadedFile {
+                field: field.name().unwrap_or_default().to_owned(),
+                url: signed_url(&state.config.storage, &key, None),
+                key,
+                file_name,
+                content_type,
+                size,
+            });
+        }
+        Ok(())
+    }.await;
+
+    if let Err(e) = result {
+        for file in &files {
+            if let Err(e) = storage.delete(&file.key).await {
+                warn!("delete uploaded file {} error: {}", file.key, e);
+            }
+        }
+        return Err(e);
+    }
+    Ok(files)
+}
+
+fn multipart_error(e: MultipartError) -> BuboError {
+    BuboError::business_error(BusinessErrorCode::FormRejectionError, e.body_text())
+}
+
+///
+/// 生成 `{prefix}/{yyyy}/{mm}/{dd}/{uuid}.{ext}`，不使用原文件名避免冲突和路径注入，
+/// 原扩展名属于校验过的类型时才保留，例如类型为 image/png 的 `a.html` 不带扩展名
+///
+fn new_key(prefix: &str, file_name: &str, content_type: &str) -> BuboResult<String> {
+    let date = now_utc().format(format_description!("[year]/[month]/[day]")).map_err(storage_error)?;
+    let exts = mime_guess::get_mime_extensions_str(content_type).unwrap_or_default();
+    let ext = file_name.rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .filter(|ext| exts.contains(&ext.as_str()));
+    let prefix = prefix.trim_matches('/');
+    let mut key = if prefix.is_empty() { String::new() } else { format!("{}/", prefix) };
+    key.push_str(&format!("{}/{}", date, uuid::Uuid::new_v4().simple()));
+    if let Some(ext) = ext {
+        key.push('.');
+        key.push_str(&ext);
+    }
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upload_policy() {
+        let policy = UploadPolicy::from_config(&StorageConfig::default(), "avatars").allowed_types(["image/*", "application/pdf"]);
+        assert!(policy.allows("image/png"));
+        assert!(policy.allows("Application/PDF; charset=binary"));
+        assert!(!policy.allows("text/html"));
+        assert!(UploadPolicy::from_config(&StorageConfig::default(), "").allows("text/html"));
+
+        let key = new_key("/avatars/", "../../photo.PNG", "image/png").unwrap();
+        assert!(key.starts_with("avatars/") && key.ends_with(".png"));
+        assert!(super::super::validate_key(&key).is_ok());
+        assert!(new_key("", "photo.jpg", "image/jpeg").unwrap().ends_with(".jpg"));
+        assert!(!new_key("", "noext", "image/png").unwrap().contains('.'));
+        assert!(!new_key("", "evil.html", "image/png").unwrap().contains('.'));
+    }
+}
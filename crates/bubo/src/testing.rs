@@ -0,0 +1,456 @@
+use std::{collections::{HashMap, HashSet, VecDeque}, path::PathBuf, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use axum::{body::{to_bytes, Body}, http::{header::{AUTHORIZATION, CONTENT_TYPE}, HeaderMap, Request, StatusCode}, Router};
+use bytes::Bytes;
+use fred::{error::{RedisError, RedisErrorKind}, interfaces::ClientLike, mocks::{MockCommand, Mocks}, prelude::RedisPool, types::{Builder, RedisConfig as FredRedisConfig, RedisValue as FredValue}};
+use sea_orm::{ConnectOptions, ConnectionTrait, Database, DatabaseConnection};
+use sea_orm_migration::MigratorTrait;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use tower::ServiceExt;
+
+use crate::{config::{CacheBackendKind, Config, Environment}, controllers::middlewares::auth::{create_token, AuthUser, TOKEN_TYPE}, server::{build_app, build_state, AppState, Hooks}, session::MemorySessionStore};
+
+// 指定 Postgres 时在其中创建临时 schema
+pub const DATABASE_URL_ENV: &str = "BUBO_TEST_DATABASE_URL";
+
+///
+/// 测试配置：测试环境、固定密钥、内存缓存
+///
+pub fn test_config() -> Config {
+    let mut config = Config { environment: Environment::Test, ..Default::default() };
+    config.auth.access_secret = "bubo-test-access-secret".to_owned();
+    config.auth.refresh_secret = "bubo-test-refresh-secret".to_owned();
+    config.cache.backend = CacheBackendKind::Memory;
+    config.metrics.enable = false;
+    config
+}
+
+///
+/// 登录用户
+///
+pub fn auth_user(id: i64, is_admin: bool, permissions: &[&str]) -> AuthUser {
+    let permissions = permissions.iter().map(|permission| (*permission).to_owned()).collect();
+    AuthUser::new(id, format!("user{id}"), format!("user{id}"), is_admin, 0, 0, HashSet::new(), permissions, HashSet::new())
+}
+
+///
+/// 在进程内运行的完整应用，包括 main_app 中的全部中间件，需要开启 feature `testing`
+///
+/// ```ignore
+/// let mut app = TestApp::new::<App, Migrator>().await;
+/// app.login_as(testing::auth_user(1, false, &["system:user:page"])).await;
+/// app.get("/admin/system/role/page?page=1&page_size=10").await.assert_error_code(BusinessErrorCode::Forbidden);
+/// ```
+///
+/// 默认使用临时 SQLite 文件，设置 `BUBO_TEST_DATABASE_URL` 为 Postgres 地址时在其中创建临时 schema，
+/// 测试结束后删除。登录会话保存在内存中，不需要 redis。
+///
+pub struct TestApp {
+    pub state: AppState,
+    router: Router,
+    token: Option<String>,
+    _database: TestDatabase,
+}
+
+impl TestApp {
+    pub async fn new<H: Hooks, M: MigratorTrait + 'static>() -> Self {
+        Self::with_config::<H, M>(test_config()).await
+    }
+
+    ///
+    /// 使用自定义配置，database.url 会被替换为临时数据库
+    ///
+    pub async fn with_config<H: Hooks, M: MigratorTrait + 'static>(mut config: Config) -> Self {
+        let database = TestDatabase::create().await;
+        config.database.url = database.url.clone();
+        let db = database.connect().await;
+        M::up(&db, None).await.expect("Failed to run migrations");
+
+        // 不连接 redis，会话和缓存都在内存中
+        let redis: RedisPool = Builder::from_config(FredRedisConfig::default()).build_pool(1).expect("Failed to create redis pool");
+        let state = build_state::<H>(config, db, redis, Arc::new(MemorySessionStore::new())).await;
+        let router = build_app::<H, M>(&state).await;
+        Self { state, router, token: None, _database: database }
+    }
+
+    pub fn db(&self) -> &DatabaseConnection {
+        &self.state.db
+    }
+
+    ///
+    /// 以 auth_user 登录，之后的请求都带上 access token
+    ///
+    pub async fn login_as(&mut self, auth_user: AuthUser) -> &mut Self {
+        let (access_token, ..) = create_token(&self.state, auth_user).await.expect("Failed to create token");
+        self.token = Some(access_token);
+        self
+    }
+
+    pub fn logout(&mut self) -> &mut Self {
+        self.token = None;
+        self
+    }
+
+    pub async fn get(&self, uri: &str) -> TestResponse {
+        self.request(Request::get(uri).body(Body::empty()).unwrap()).await
+    }
+
+    pub async fn post_json<T: Serialize + ?Sized>(&self, uri: &str, body: &T) -> TestResponse {
+        let body = serde_json::to_vec(body).expect("Failed to serialize request body");
+        self.request(Request::post(uri).header(CONTENT_TYPE, "application/json").body(Body::from(body)).unwrap()).await
+    }
+
+    ///
+    /// 发送请求，已登录且请求没有 Authorization 头时自动添加
+    ///
+    pub async fn request(&self, mut request: Request<Body>) -> TestResponse {
+        if let Some(token) = &self.token {
+            if !request.headers().contains_key(AUTHORIZATION) {
+                let value = format!("{TOKEN_TYPE} {token}").parse().unwrap();
+                request.headers_mut().insert(AUTHORIZATION, value);
+            }
+        }
+        let response = self.router.clone().oneshot(request).await.unwrap_or_else(|e| match e {});
+        let (parts, body) = response.into_parts();
+        let body = to_bytes(body, usize::MAX).await.expect("Failed to read response body");
+        TestResponse { status: parts.status, headers: parts.headers, body }
+    }
+}
+
+#[derive(Debug)]
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl TestResponse {
+    pub fn json<T: DeserializeOwned>(&self) -> T {
+        serde_json::from_slice(&self.body).unwrap_or_else(|e| panic!("Invalid json response {}: {}", self.text(), e))
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    ///
+    /// 响应为 `"status": true`
+    ///
+    #[track_caller]
+    pub fn assert_ok(&self) -> &Self {
+        let value: Value = self.json();
+        assert_eq!(value["status"], Value::Bool(true), "expected success response, got {}", self.text());
+        self
+    }
+
+    ///
+    /// 响应为指定错误码，参数为 BusinessErrorCode 或 SystemErrorCode，支持各种 server.error_mode
+    ///
+    #[track_caller]
+    pub fn assert_error_code(&self, error_code: impl Into<usize>) -> &Self {
+        let value: Value = self.json();
+        let error_code = error_code.into();
+        // problem+json 中 status 为 HTTP 状态码
+        if value["status"].is_boolean() {
+            assert_eq!(value["status"], Value::Bool(false), "expected error response, got {}", self.text());
+        }
+        assert_eq!(value["error_code"].as_u64(), Some(error_code as u64), "unexpected error code in {}", self.text());
+        self
+    }
+}
+
+///
+/// 临时数据库，drop 时删除
+///
+struct TestDatabase {
+    url: String,
+    kind: TestDatabaseKind,
+}
+
+enum TestDatabaseKind {
+    Sqlite(PathBuf),
+    Postgres { base_url: String, schema: String },
+}
+
+impl TestDatabase {
+    async fn create() -> Self {
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        match std::env::var(DATABASE_URL_ENV) {
+            Ok(base_url) if base_url.starts_with("postgres") => {
+                let schema = format!("test_{id}");
+                let db = Database::connect(base_url.as_str()).await.expect("Failed to connect test database");
+                db.execute_unprepared(&format!("CREATE SCHEMA \"{schema}\"")).await.expect("Failed to create test schema");
+                let _ = db.close().await;
+                Self { url: base_url.clone(), kind: TestDatabaseKind::Postgres { base_url, schema } }
+            }
+            _ => {
+                let path = std::env::temp_dir().join(format!("bubo-test-{id}.db"));
+                Self { url: format!("sqlite://{}?mode=rwc", path.display()), kind: TestDatabaseKind::Sqlite(path) }
+            }
+        }
+    }
+
+    async fn connect(&self) -> DatabaseConnection {
+        let mut opt = ConnectOptions::new(self.url.as_str());
+        opt.max_connections(5).connect_timeout(Duration::from_secs(10)).sqlx_logging(false);
+        if let TestDatabaseKind::Postgres { schema, .. } = &self.kind {
+            opt.set_schema_search_path(schema.as_str());
+        }
+        Database::connect(opt).await.expect("Failed to connect test database")
+    }
+}
+
+impl Drop for TestDatabase {
+    fn drop(&mut self) {
+        match &self.kind {
+            TestDatabaseKind::Sqlite(path) => {
+                let _ = std::fs::remove_file(path);
+            }
+            TestDatabaseKind::Postgres { base_url, schema } => {
+                let (base_url, schema) = (base_url.clone(), schema.clone());
+                // drop 中无法 await，在单独的线程和运行时中删除
+                let _ = std::thread::spawn(move || {
+                    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().ok()?;
+                    runtime.block_on(async {
+                        let db = Database::connect(base_url.as_str()).await.ok()?;
+                        db.execute_unprepared(&format!("DROP SCHEMA IF EXISTS \"{schema}\" CASCADE")).await.ok()
+                    })
+                }).join();
+            }
+        }
+    }
+}
+
+///
+/// 内存中的 Redis，支持字符串、列表、有序集合和发布的常用命令，用于测试队列、租约和缓存。
+/// 不支持的命令（例如 EVAL）返回错误
+///
+/// ```ignore
+/// let mock = Arc::new(MockRedis::default());
+/// let redis = mock.pool().await;
+/// ```
+///
+#[derive(Debug, Default)]
+pub struct MockRedis {
+    data: Mutex<HashMap<String, (MockValue, Option<Instant>)>>,
+    published: Mutex<Vec<(String, String)>>,
+}
+
+#[derive(Debug, Clone)]
+enum MockValue {
+    String(String),
+    List(VecDeque<String>),
+    ZSet(Vec<(f64, String)>),
+}
+
+impl MockRedis {
+    ///
+    /// 连接到该 MockRedis 的连接池
+    ///
+    pub async fn pool(self: &Arc<Self>) -> RedisPool {
+        let config = FredRedisConfig { mocks: Some(self.clone()), ..Default::default() };
+        let pool = Builder::from_config(config).build_pool(1).expect("Failed to create redis pool");
+        pool.init().await.expect("Failed to init mock redis");
+        pool
+    }
+
+    ///
+    /// 列表的全部元素
+    ///
+    pub fn list(&self, key: &str) -> Vec<String> {
+        match self.entry(key) {
+            Some(MockValue::List(list)) => list.into_iter().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    ///
+    /// 有序集合的全部成员，按分数排序
+    ///
+    pub fn zset(&self, key: &str) -> Vec<(f64, String)> {
+        match self.entry(key) {
+            Some(MockValue::ZSet(zset)) => zset,
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn string(&self, key: &str) -> Option<String> {
+        match self.entry(key) {
+            Some(MockValue::String(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    ///
+    /// 已发布的 (channel, message)
+    ///
+    pub fn published(&self) -> Vec<(String, String)> {
+        self.published.lock().unwrap().clone()
+    }
+
+    fn entry(&self, key: &str) -> Option<MockValue> {
+        let data = self.data.lock().unwrap();
+        data.get(key).filter(|(_, expire_at)| expire_at.map_or(true, |at| at > Instant::now())).map(|(value, _)| value.clone())
+    }
+
+    fn execute(&self, cmd: &str, args: &[String]) -> Result<FredValue, String> {
+        let mut data = self.data.lock().unwrap();
+        let now = Instant::now();
+        data.retain(|_, (_, expire_at)| expire_at.map_or(true, |at| at > now));
+        let arg = |i: usize| args.get(i).cloned().ok_or_else(|| format!("wrong number of arguments for {cmd}"));
+        let int = |i: usize| arg(i)?.parse::<i64>().map_err(|e| e.to_string());
+        match cmd {
+            "PING" => Ok("PONG".into()),
+            "GET" => match data.get(&arg(0)?) {
+                Some((MockValue::String(value), _)) => Ok(value.clone().into()),
+                Some(_) => Err("WRONGTYPE".to_owned()),
+                None => Ok(FredValue::Null),
+            },
+            "SET" => {
+                let key = arg(0)?;
+                let mut expire_at = None;
+                let mut i = 2;
+                while i < args.len() {
+                    match args[i].to_ascii_uppercase().as_str() {
+                        "EX" => { expire_at = Some(now + Duration::from_secs(int(i + 1)? as u64)); i += 1; }
+                        "PX" => { expire_at = Some(now + Duration::from_millis(int(i + 1)? as u64)); i += 1; }
+                        "NX" if data.contains_key(&key) => return Ok(FredValue::Null),
+                        "XX" if !data.contains_key(&key) => return Ok(FredValue::Null),
+                        _ => {}
+                    }
+                    i += 1;
+                }
+                data.insert(key, (MockValue::String(arg(1)?), expire_at));
+                Ok("OK".into())
+            }
+            "DEL" => Ok(FredValue::Integer(args.iter().filter(|key| data.remove(*key).is_some()).count() as i64)),
+            "RPUSH" | "LPUSH" => {
+                let list = list_mut(&mut data, arg(0)?)?;
+                for value in &args[1..] {
+                    if cmd == "RPUSH" { list.push_back(value.clone()) } else { list.push_front(value.clone()) }
+                }
+                Ok(FredValue::Integer(list.len() as i64))
+            }
+            "LLEN" => Ok(FredValue::Integer(list_mut(&mut data, arg(0)?)?.len() as i64)),
+            "LRANGE" | "LTRIM" => {
+                let list = list_mut(&mut data, arg(0)?)?;
+                let (start, stop) = range(list.len(), int(1)?, int(2)?);
+                let kept: VecDeque<String> = list.iter().skip(start).take(stop.saturating_sub(start)).cloned().collect();
+                if cmd == "LTRIM" {
+                    *list = kept;
+                    Ok("OK".into())
+                } else {
+                    Ok(FredValue::Array(kept.into_iter().map(Into::into).collect()))
+                }
+            }
+            "LREM" => {
+                let list = list_mut(&mut data, arg(0)?)?;
+                let (mut count, value) = (int(1)?, arg(2)?);
+                let before = list.len();
+                list.retain(|item| {
+                    let remove = *item == value && count != 0;
+                    if remove { count -= count.signum(); }
+                    !remove
+                });
+                Ok(FredValue::Integer((before - list.len()) as i64))
+            }
+            "LMOVE" => {
+                let source = list_mut(&mut data, arg(0)?)?;
+                let item = if arg(2)?.eq_ignore_ascii_case("LEFT") { source.pop_front() } else { source.pop_back() };
+                let Some(item) = item else {
+                    return Ok(FredValue::Null);
+                };
+                let dest = list_mut(&mut data, arg(1)?)?;
+                if arg(3)?.eq_ignore_ascii_case("LEFT") { dest.push_front(item.clone()) } else { dest.push_back(item.clone()) }
+                Ok(item.into())
+            }
+            "ZADD" => {
+                let zset = zset_mut(&mut data, arg(0)?)?;
+                // 跳过 NX、XX、GT、LT、CH 等选项
+                let start = args.iter().skip(1).position(|arg| arg.parse::<f64>().is_ok()).map_or(args.len(), |i| i + 1);
+                let mut added = 0;
+                for pair in args[start..].chunks(2) {
+                    let [score, member] = pair else { return Err("syntax error".to_owned()) };
+                    let score = score.parse::<f64>().map_err(|e| e.to_string())?;
+                    match zset.iter_mut().find(|(_, m)| m == member) {
+                        Some(entry) => entry.0 = score,
+                        None => { zset.push((score, member.clone())); added += 1; }
+                    }
+                }
+                zset.sort_by(|a, b| a.0.total_cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+                Ok(FredValue::Integer(added))
+            }
+            "ZREM" => {
+                let zset = zset_mut(&mut data, arg(0)?)?;
+                let before = zset.len();
+                zset.retain(|(_, member)| !args[1..].contains(member));
+                Ok(FredValue::Integer((before - zset.len()) as i64))
+            }
+            "ZCARD" => Ok(FredValue::Integer(zset_mut(&mut data, arg(0)?)?.len() as i64)),
+            "ZRANGEBYSCORE" => {
+                let zset = zset_mut(&mut data, arg(0)?)?;
+                let (min, max) = (score(&arg(1)?)?, score(&arg(2)?)?);
+                let mut members = zset.iter().filter(|(score, _)| *score >= min && *score <= max).map(|(_, member)| member.clone());
+                let limit = args.iter().position(|arg| arg.eq_ignore_ascii_case("LIMIT"));
+                let members: Vec<String> = match limit {
+                    Some(i) => members.by_ref().skip(int(i + 1)? as usize).take(int(i + 2)? as usize).collect(),
+                    None => members.collect(),
+                };
+                Ok(FredValue::Array(members.into_iter().map(Into::into).collect()))
+            }
+            "PUBLISH" => {
+                self.published.lock().unwrap().push((arg(0)?, arg(1)?));
+                Ok(FredValue::Integer(0))
+            }
+            "SUBSCRIBE" | "UNSUBSCRIBE" => Ok(FredValue::Queued),
+            _ => Err(format!("unsupported command {cmd}")),
+        }
+    }
+}
+
+impl Mocks for MockRedis {
+    fn process_command(&self, command: MockCommand) -> Result<FredValue, RedisError> {
+        let args: Vec<String> = command.args.iter().map(|arg| arg.as_str().map(|arg| arg.into_owned()).unwrap_or_default()).collect();
+        self.execute(&command.cmd.to_ascii_uppercase(), &args).map_err(|e| RedisError::new(RedisErrorKind::Unknown, e))
+    }
+}
+
+fn list_mut(data: &mut HashMap<String, (MockValue, Option<Instant>)>, key: String) -> Result<&mut VecDeque<String>, String> {
+    match &mut data.entry(key).or_insert_with(|| (MockValue::List(VecDeque::new()), None)).0 {
+        MockValue::List(list) => Ok(list),
+        _ => Err("WRONGTYPE".to_owned()),
+    }
+}
+
+fn zset_mut(data: &mut HashMap<String, (MockValue, Option<Instant>)>, key: String) -> Result<&mut Vec<(f64, String)>, String> {
+    match &mut data.entry(key).or_insert_with(|| (MockValue::ZSet(Vec::new()), None)).0 {
+        MockValue::ZSet(zset) => Ok(zset),
+        _ => Err("WRONGTYPE".to_owned()),
+    }
+}
+
+///
+/// LRANGE 的闭区间下标转为 [start, end)，支持负数
+///
+fn range(len: usize, start: i64, stop: i64) -> (usize, usize) {
+    let index = |i: i64| if i < 0 { (len as i64 + i).max(0) } else { i.min(len as i64) };
+    let (start, stop) = (index(start), (index(stop) + 1).min(len as i64));
+    (start as usize, stop.max(start) as usize)
+}
+
+fn score(value: &str) -> Result<f64, String> {
+    match value {
+        "-inf" => Ok(f64::NEG_INFINITY),
+        "+inf" | "inf" => Ok(f64::INFINITY),
+        _ => value.parse::<f64>().map_err(|e| e.to_string()),
+    }
+}
+
+///
+/// 使用 MockRedis 和内存 SQLite 创建 AppState，用于测试依赖 redis 的组件
+///
+pub async fn mock_state<H: Hooks>(config: Config, redis: &Arc<MockRedis>) -> AppState {
+    let db = Database::connect("sqlite::memory:").await.expect("Failed to connect test database");
+    build_state::<H>(config, db, redis.pool().await, Arc::new(MemorySessionStore::new())).await
+}
@@ -1,15 +1,16 @@
-use fred::{prelude::RedisPool, types::Expiration};
-use sea_orm::{entity::*, query::*, sea_query::SimpleExpr, Condition, Database, DatabaseConnection, QueryFilter};
+use std::time::Duration;
+
+use sea_orm::{entity::*, query::*, sea_query::SimpleExpr, Condition, ConnectOptions, Database, DatabaseConnection, ItemsAndPagesNumber, QueryFilter};
 use sea_orm_migration::MigratorTrait;
 use serde::{Deserialize, Serialize};
 use tracing::info;
 use async_trait::async_trait;
 
-use crate::{server::AppState, utils::redis};
+use crate::{cache::Cache, config::DatabaseConfig, server::AppState};
 
 use super::error::BuboResult;
 
-const EXPIRE: i64 = 60 * 5;
+const EXPIRE: Duration = Duration::from_secs(60 * 5);
 
 pub struct ColOrd {
     col: SimpleExpr,
@@ -28,16 +29,63 @@ impl ColOrd {
 ///
 /// 初始化数据库
 /// 
-pub async fn init<M: MigratorTrait>() -> DatabaseConnection {
-    let db_url = dotenvy::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-    let db: DatabaseConnection = Database::connect(db_url)
+pub async fn init<M: MigratorTrait>(config: &DatabaseConfig) -> DatabaseConnection {
+    let db = connect(config).await;
+    if config.auto_migrate {
+        M::up(&db, None).await.unwrap();
+    }
+    db
+}
+
+///
+/// 连接数据库，不执行迁移
+///
+pub async fn connect(config: &DatabaseConfig) -> DatabaseConnection {
+    let mut opt = ConnectOptions::new(config.url.as_str());
+    opt.max_connections(config.max_connections)
+        .min_connections(config.min_connections)
+        .connect_timeout(Duration::from_secs(config.connect_timeout))
+        .idle_timeout(Duration::from_secs(config.idle_timeout));
+    let mut db: DatabaseConnection = Database::connect(opt)
         .await
         .expect("Database connection failed");
+    // 记录查询耗时到 db_query_duration_seconds
+    db.set_metric_callback(super::prometheus::record_query);
     info!("Connected to Database");
-    M::up(&db, None).await.unwrap();
-    db 
+    db
+}
+
+
+///
+/// 按主键缓存的 key
+///
+fn model_cache_key(table_name: &str, id: i64) -> String {
+    format!("model:{}:{}", table_name, id)
 }
 
+///
+/// 分页查询结果
+///
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    // 当前页，从 1 开始
+    pub page: u64,
+    pub page_size: u64,
+    // 总条数
+    pub total: u64,
+    // 总页数
+    pub num_pages: u64,
+}
+
+impl<T> Page<T> {
+    ///
+    /// 转换数据，例如 model 转为返回对象
+    ///
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page { items: self.items.into_iter().map(f).collect(), page: self.page, page_size: self.page_size, total: self.total, num_pages: self.num_pages }
+    }
+}
 
 #[async_trait]
 pub trait EntityExtension: EntityTrait{
@@ -67,7 +115,7 @@ pub trait EntityExtension: EntityTrait{
         page_size: u64,
         condition: Condition,
         col_ord_vec: Vec<ColOrd>,
-    ) -> BuboResult<(Vec<Self::Model>, u64)>
+    ) -> BuboResult<Page<Self::Model>>
         where
             <Self as EntityTrait>::Model: Send + Unpin + Sync,
     {
@@ -76,15 +124,16 @@ pub trait EntityExtension: EntityTrait{
             query = query.order_by(v.col, v.ord);
         }
         let paginator = query.paginate(db, page_size);
-        let num_pages = paginator.num_pages().await?;
+        let ItemsAndPagesNumber { number_of_items, number_of_pages } = paginator.num_items_and_pages().await?;
 
-        let models = if num_pages < page {
+        // page 从 1 开始，超出范围时返回空列表
+        let items = if page == 0 || number_of_pages < page {
             vec![]
         } else {
             paginator.fetch_page(page - 1).await?
         };
 
-        Ok((models, num_pages))
+        Ok(Page { items, page, page_size, total: number_of_items, num_pages: number_of_pages })
     }
 
     #[allow(dead_code)]
@@ -96,20 +145,15 @@ pub trait EntityExtension: EntityTrait{
 
     {
         let entity = Self::default();
-        let cache_key = redis::gen_key(state.app_name, format!("cache-{}", Self::table_name(&entity)), id);
+        let cache_key = model_cache_key(Self::table_name(&entity), id);
         tracing::debug!("get: {}", cache_key);
-        let mut model = redis::get(&state.redis, &cache_key).await?;
-        if model.is_none() {
-            model = match Self::find_by_id(id).one(&state.db).await {
-                Ok(v) => match v {
-                    Some(m) => {
-                        redis::set(&state.redis, &cache_key, &m, Some(fred::types::Expiration::EX(EXPIRE))).await?;
-                        Some(m)
-                    }
-                    None => None,
-                },
-                Err(e) => return Err(e.into()),
-            };
+        if let Some(model) = state.cache.get::<Self::Model>(&cache_key).await? {
+            return Ok(Some(model));
+        }
+        // 不存在的记录不缓存，否则记录创建后在过期前一直查不到
+        let model = Self::find_by_id(id).one(&state.db).await?;
+        if let Some(model) = &model {
+            state.cache.set_with_ttl(&cache_key, model, Some(EXPIRE)).await?;
         }
         Ok(model)
     }
@@ -119,10 +163,9 @@ pub trait EntityExtension: EntityTrait{
     async fn clear_cache(state: AppState, id: i64) -> BuboResult<()> 
     {
         let entity = Self::default();
-        let cache_key = redis::gen_key(state.app_name, format!("cache-{}", Self::table_name(&entity)), id);
+        let cache_key = model_cache_key(Self::table_name(&entity), id);
         tracing::debug!("clear_cache: {}", cache_key);
-        redis::del(&state.redis, &cache_key).await?;
-        Ok(())
+        state.cache.delete(&cache_key).await
     }
 
     async fn count<'a, C: ConnectionTrait>(db: &'a C, condition: Condition) -> BuboResult<u64>
@@ -166,8 +209,7 @@ pub trait ActiveModelExtension: ActiveModelTrait{
     }
 
     #[allow(unused)]
-    async fn persist_cache<C: ConnectionTrait>(mut self, app_name: &str, db: &C, redis: &RedisPool, 
-        is_update: bool) -> BuboResult<<Self::Entity as EntityTrait>::Model>
+    async fn persist_cache<C: ConnectionTrait>(mut self, cache: &Cache, db: &C, is_update: bool) -> BuboResult<<Self::Entity as EntityTrait>::Model>
     where
         Self: ActiveModelBehavior
         + TryIntoModel<<Self::Entity as EntityTrait>::Model>
@@ -193,10 +235,10 @@ pub trait ActiveModelExtension: ActiveModelTrait{
             id = model.get(col).unwrap();
         }
 
-        let cache_key = redis::gen_key(app_name, format!("cache-{}", entity.table_name()), id);
+        let cache_key = model_cache_key(entity.table_name(), id);
         tracing::debug!("set: {}", cache_key);
         // 数据库更新成功后才更新缓存，如果缓存更新成功数据库更新失败会有数据不一致的情况
-        let _ = redis::set(redis, &cache_key, &model, Some(Expiration::EX(EXPIRE))).await;
+        let _ = cache.set_with_ttl(&cache_key, &Some(&model), Some(EXPIRE)).await;
         Ok(model)
     }
 }
@@ -2,10 +2,11 @@ use axum::{extract::rejection::{FormRejection, JsonRejection, PathRejection, Que
 use axum_extra::extract::JsonDeserializerRejection;
 use fred::error::RedisError;
 use sea_orm::DbErr;
-use serde_json::{json, Value};
 use thiserror::Error;
 use tracing::{error, warn};
 
+use crate::{i18n, views::response::ErrorResponse};
+
 pub type BuboResult<T> = Result<T, BuboError>;
 
 #[derive(Debug, Error)]
@@ -23,7 +24,7 @@ pub enum BuboError {
     #[error(transparent)]
     DatabaseError(#[from] DbErr),
     #[error(transparent)]
-    RedisError(#[from] RedisError),
+    RedisError(RedisError),
     #[error(transparent)]
     SerdeJsonError(#[from] serde_json::Error),
     #[error(transparent)]
@@ -40,6 +41,9 @@ pub enum BuboError {
 }
 
 impl BuboError {
+    ///
+    /// error_message 可以是 i18n 的 key，返回时翻译为请求的语言
+    ///
     pub fn system_error(error_code: SystemErrorCode, error_message: impl Into<String>) -> BuboError {
         BuboError::SystemError(error_code, error_message.into())
     }
@@ -48,7 +52,7 @@ impl BuboError {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SystemErrorCode {
     UnknownError = 20000,
     InternalServerError,
@@ -60,10 +64,12 @@ pub enum SystemErrorCode {
     SerdeJsonError,
     JwtEncodeError,
     Argon2HashError,
+    MailError,
+    StorageError,
 }
     
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BusinessErrorCode {
     UnknownError = 10000,
     ValidationError,
@@ -79,83 +85,283 @@ pub enum BusinessErrorCode {
     NotFound,
     PasswordNotMatch,
     UserOrPasswordNotMatch,
+    FileTooLarge,
+    FileTypeNotAllowed,
+    TooManyRequests,
+    PayloadTooLarge,
+}
+
+impl SystemErrorCode {
+    ///
+    /// 默认消息在 i18n 中的 key
+    ///
+    pub fn key(&self) -> &'static str {
+        match self {
+            SystemErrorCode::UnknownError => "system.unknown_error",
+            SystemErrorCode::InternalServerError => "system.internal_server_error",
+            SystemErrorCode::NotFound => "system.not_found",
+            SystemErrorCode::RequestTimeout => "system.request_timeout",
+            SystemErrorCode::ServiceUnavailable => "system.service_unavailable",
+            SystemErrorCode::DatabaseError => "system.database_error",
+            SystemErrorCode::RedisError => "system.redis_error",
+            SystemErrorCode::SerdeJsonError => "system.serde_json_error",
+            SystemErrorCode::JwtEncodeError => "system.jwt_encode_error",
+            SystemErrorCode::Argon2HashError => "system.argon2_hash_error",
+            SystemErrorCode::MailError => "system.mail_error",
+            SystemErrorCode::StorageError => "system.storage_error",
+        }
+    }
+
+    ///
+    /// 对应的 HTTP 状态码，用于 status 和 problem 错误模式
+    ///
+    pub fn status(&self) -> StatusCode {
+        match self {
+            SystemErrorCode::NotFound => StatusCode::NOT_FOUND,
+            SystemErrorCode::RequestTimeout => StatusCode::REQUEST_TIMEOUT,
+            SystemErrorCode::ServiceUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl BusinessErrorCode {
+    ///
+    /// 默认消息在 i18n 中的 key
+    ///
+    pub fn key(&self) -> &'static str {
+        match self {
+            BusinessErrorCode::UnknownError => "business.unknown_error",
+            BusinessErrorCode::ValidationError => "business.validation_error",
+            BusinessErrorCode::FormRejectionError => "business.form_rejection_error",
+            BusinessErrorCode::JsonRejectionError => "business.json_rejection_error",
+            BusinessErrorCode::QueryRejectionError => "business.query_rejection_error",
+            BusinessErrorCode::PathRejectionError => "business.path_rejection_error",
+            BusinessErrorCode::PasswordHashError => "business.password_hash_error",
+            BusinessErrorCode::AuthFailed => "business.auth_failed",
+            BusinessErrorCode::Unauthorized => "business.unauthorized",
+            BusinessErrorCode::Forbidden => "business.forbidden",
+            BusinessErrorCode::AlreadyExists => "business.already_exists",
+            BusinessErrorCode::NotFound => "business.not_found",
+            BusinessErrorCode::PasswordNotMatch => "business.password_not_match",
+            BusinessErrorCode::UserOrPasswordNotMatch => "business.user_or_password_not_match",
+            BusinessErrorCode::FileTooLarge => "business.file_too_large",
+            BusinessErrorCode::FileTypeNotAllowed => "business.file_type_not_allowed",
+            BusinessErrorCode::TooManyRequests => "business.too_many_requests",
+            BusinessErrorCode::PayloadTooLarge => "business.payload_too_large",
+        }
+    }
+
+    ///
+    /// 对应的 HTTP 状态码，用于 status 和 problem 错误模式
+    ///
+    pub fn status(&self) -> StatusCode {
+        match self {
+            BusinessErrorCode::AuthFailed | BusinessErrorCode::Unauthorized | BusinessErrorCode::UserOrPasswordNotMatch => StatusCode::UNAUTHORIZED,
+            BusinessErrorCode::Forbidden => StatusCode::FORBIDDEN,
+            BusinessErrorCode::NotFound => StatusCode::NOT_FOUND,
+            BusinessErrorCode::AlreadyExists => StatusCode::CONFLICT,
+            BusinessErrorCode::ValidationError => StatusCode::UNPROCESSABLE_ENTITY,
+            BusinessErrorCode::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+            BusinessErrorCode::FileTooLarge | BusinessErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            BusinessErrorCode::FileTypeNotAllowed => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            BusinessErrorCode::PasswordHashError => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl From<SystemErrorCode> for usize {
+    fn from(error_code: SystemErrorCode) -> Self {
+        error_code as usize
+    }
+}
+
+impl From<BusinessErrorCode> for usize {
+    fn from(error_code: BusinessErrorCode) -> Self {
+        error_code as usize
+    }
+}
+
+///
+/// 使用错误码的默认消息
+///
+impl From<SystemErrorCode> for BuboError {
+    fn from(error_code: SystemErrorCode) -> Self {
+        BuboError::SystemError(error_code, error_code.key().to_owned())
+    }
+}
+
+///
+/// 使用错误码的默认消息，例如 `Err(BusinessErrorCode::Forbidden.into())`
+///
+impl From<BusinessErrorCode> for BuboError {
+    fn from(error_code: BusinessErrorCode) -> Self {
+        BuboError::BusinessError(error_code, error_code.key().to_owned())
+    }
+}
+
+///
+/// 转换时按错误类型计数到 redis_errors_total
+///
+impl From<RedisError> for BuboError {
+    fn from(error: RedisError) -> Self {
+        super::prometheus::record_redis_error(&error);
+        BuboError::RedisError(error)
+    }
+}
+
+///
+/// 错误响应的状态码和内容。转为响应时使用 legacy_status，并附加在响应的 extensions 中，
+/// 由 error_mode 中间件按 server.error_mode 转为对应的状态码和格式
+///
+#[derive(Debug, Clone)]
+pub struct ErrorDetails {
+    // 错误对应的 HTTP 状态码
+    pub status: StatusCode,
+    // legacy 模式的状态码，业务错误为 200
+    pub legacy_status: StatusCode,
+    pub body: ErrorResponse,
+}
+
+impl ErrorDetails {
+    ///
+    /// 各模式使用相同的状态码，例如请求超时、找不到路由
+    ///
+    pub fn new(status: StatusCode, body: ErrorResponse) -> Self {
+        Self { status, legacy_status: status, body }
+    }
+}
+
+impl IntoResponse for ErrorDetails {
+    fn into_response(self) -> Response {
+        let mut response = (self.legacy_status, Json(&self.body)).into_response();
+        response.extensions_mut().insert(self);
+        response
+    }
 }
 
 impl BuboError {
-    pub fn into_error_response(self) -> (StatusCode, Value) {
-        let (status, error_code, error_message) = match self {
+    fn is_payload_too_large(&self) -> bool {
+        let status = match self {
+            BuboError::FormRejectionError(r) => r.status(),
+            BuboError::JsonRejectionError(r) => r.status(),
+            BuboError::JsonDeserializerRejectionError(r) => r.status(),
+            _ => return false,
+        };
+        status == StatusCode::PAYLOAD_TOO_LARGE
+    }
+
+    pub fn into_error_response(self) -> ErrorDetails {
+        // 请求体超过 server.body_limit 时提取器的拒绝统一为 PayloadTooLarge
+        let error = if self.is_payload_too_large() {
+            BuboError::from(BusinessErrorCode::PayloadTooLarge)
+        } else {
+            self
+        };
+        let internal = |status: StatusCode, error_code: usize| ErrorDetails {
+            status,
+            legacy_status: StatusCode::INTERNAL_SERVER_ERROR,
+            body: ErrorResponse::new(error_code, i18n::t(SystemErrorCode::InternalServerError.key())),
+        };
+        // 业务错误在 legacy 模式下返回 200
+        let business = |status: StatusCode, error_code: BusinessErrorCode, error_message: String| ErrorDetails {
+            status,
+            legacy_status: StatusCode::OK,
+            body: ErrorResponse::new(error_code, error_message),
+        };
+        match error {
             BuboError::OtherError(_) => {
-                error!("Other error: {:?}", self);
-                (StatusCode::INTERNAL_SERVER_ERROR, SystemErrorCode::UnknownError as usize, "Internal Server Error".to_owned())
+                error!("Other error: {:?}", error);
+                internal(StatusCode::INTERNAL_SERVER_ERROR, SystemErrorCode::UnknownError as usize)
             },
             BuboError::SystemError(error_code, error_message) => {
                 error!("System error: {}", error_message.as_str());
-                (StatusCode::INTERNAL_SERVER_ERROR, error_code as usize, "Internal Server Error".to_owned())
+                internal(error_code.status(), error_code as usize)
             },
             BuboError::BusinessError(error_code, error_message) => {
                 // warn!("Business error: {}", error_message.as_str());
-                (StatusCode::OK, error_code as usize, error_message)
+                business(error_code.status(), error_code, i18n::t(&error_message))
             },
-            BuboError::ValidationError(_) => {
-                // let message = format!("Input validation error: [{self}]").replace('\n', ", ");
-                let message = format!("{self}").replace('\n', ", ");
-                // let message = "Input validation error".to_owned();
-                (StatusCode::OK, BusinessErrorCode::ValidationError as usize, message)
+            BuboError::ValidationError(ref errors) => {
+                business(StatusCode::UNPROCESSABLE_ENTITY, BusinessErrorCode::ValidationError, i18n::validation_message(errors))
             },
             BuboError::FormRejectionError(r) => {
                 // warn!("Form rejection error: {}", r.body_text());
-                (StatusCode::OK, BusinessErrorCode::FormRejectionError as usize, r.body_text())
+                business(r.status(), BusinessErrorCode::FormRejectionError, r.body_text())
             },
             BuboError::JsonRejectionError(r) => {
                 // warn!("Json rejection error: {}", r.body_text());
-                (StatusCode::OK, BusinessErrorCode::JsonRejectionError as usize, r.body_text())
+                business(r.status(), BusinessErrorCode::JsonRejectionError, r.body_text())
             },
             BuboError::QueryRejectionError(r) => {
                 // warn!("Query rejection error: {}", r.body_text());
-                (StatusCode::OK, BusinessErrorCode::QueryRejectionError as usize, r.body_text())
+                business(r.status(), BusinessErrorCode::QueryRejectionError, r.body_text())
             },
             BuboError::PathRejectionError(r) => {
                 // warn!("Path rejection error: {}", r.body_text());
-                (StatusCode::OK, BusinessErrorCode::PathRejectionError as usize, r.body_text())
+                business(r.status(), BusinessErrorCode::PathRejectionError, r.body_text())
             },
             BuboError::JsonDeserializerRejectionError(r) => {
                 // warn!("Json deserializer rejection error: {}", r.body_text());
-                (StatusCode::OK, BusinessErrorCode::PathRejectionError as usize, r.body_text())
+                business(r.status(), BusinessErrorCode::PathRejectionError, r.body_text())
             },
             BuboError::PasswordHashError(_) => {
-                warn!("Password hash  error: {:?}", self);
-                (StatusCode::INTERNAL_SERVER_ERROR, BusinessErrorCode::PasswordHashError as usize, "Internal Server Error".to_owned())
+                warn!("Password hash  error: {:?}", error);
+                internal(StatusCode::INTERNAL_SERVER_ERROR, BusinessErrorCode::PasswordHashError as usize)
             },
             BuboError::DatabaseError(_) => {
-                error!("Database error: {:?}", self);
-                (StatusCode::INTERNAL_SERVER_ERROR, SystemErrorCode::DatabaseError as usize, "Internal Server Error".to_owned())
+                error!("Database error: {:?}", error);
+                internal(StatusCode::INTERNAL_SERVER_ERROR, SystemErrorCode::DatabaseError as usize)
             },
             BuboError::RedisError(_) => {
-                error!("Redis error: {:?}", self);
-                (StatusCode::INTERNAL_SERVER_ERROR, SystemErrorCode::RedisError as usize, "Internal Server Error".to_owned())
+                error!("Redis error: {:?}", error);
+                internal(StatusCode::INTERNAL_SERVER_ERROR, SystemErrorCode::RedisError as usize)
             },
             BuboError::SerdeJsonError(_) => {
-                error!("Serde json error: {:?}", self);
-                (StatusCode::INTERNAL_SERVER_ERROR, SystemErrorCode::SerdeJsonError as usize, "Internal Server Error".to_owned())
+                error!("Serde json error: {:?}", error);
+                internal(StatusCode::INTERNAL_SERVER_ERROR, SystemErrorCode::SerdeJsonError as usize)
             },
             
-        };
-        (
-            status,
-            json!({
-                "status": false,
-                "error_code": error_code,
-                "error_message": error_message,
-            }),
-        )
+        }
     }
 }
 
 impl IntoResponse for BuboError {
     fn into_response(self) -> Response {
-        let (status, value) = self.into_error_response();
-        let body = Json(value);
-        (status, body).into_response()
+        self.into_error_response().into_response()
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::{to_bytes, Body}, extract::DefaultBodyLimit, http::Request, routing::post, Router};
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::utils::validator::JsonValid;
+    use pretty_assertions::assert_eq;
+
+    #[derive(serde::Deserialize, validator::Validate)]
+    struct Params {
+        #[allow(dead_code)]
+        name: String,
+    }
+
+    #[tokio::test]
+    async fn test_payload_too_large() {
+        let app = Router::new()
+            .route("/", post(|JsonValid(_): JsonValid<Params>| async { "ok" }))
+            .layer(DefaultBodyLimit::max(16));
+        let request = |body: &'static str| Request::post("/").header("content-type", "application/json").body(Body::from(body)).unwrap();
+
+        let response = app.clone().oneshot(request(r#"{"name":"a"}"#)).await.unwrap();
+        assert_eq!(to_bytes(response.into_body(), usize::MAX).await.unwrap(), "ok");
+
+        let response = app.oneshot(request(r#"{"name":"0123456789"}"#)).await.unwrap();
+        let body: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(body["error_code"], BusinessErrorCode::PayloadTooLarge as usize);
+    }
+}
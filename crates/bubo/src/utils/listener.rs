@@ -0,0 +1,200 @@
+use std::{fmt, future::Future, io, net::SocketAddr, time::Duration};
+
+use axum::{extract::{connect_info::ConnectInfo, Request}, Router};
+use hyper::body::Incoming;
+use hyper_util::{rt::{TokioExecutor, TokioIo}, server::{conn::auto::Builder, graceful::{GracefulShutdown, Watcher}}, service::TowerToHyperService};
+use tokio::{io::{AsyncRead, AsyncWrite}, net::{TcpListener, TcpStream}, time::Instant};
+use tokio_rustls::TlsAcceptor;
+use tower::ServiceExt;
+use tracing::{debug, info, warn};
+
+use crate::config::ServerConfig;
+
+///
+/// 服务监听，TCP 或 Unix domain socket
+///
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener, std::path::PathBuf),
+}
+
+enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixStream),
+}
+
+impl Listener {
+    ///
+    /// 按配置监听，设置了 unix_socket 时监听 Unix domain socket
+    ///
+    pub async fn bind(config: &ServerConfig) -> io::Result<Self> {
+        match &config.unix_socket {
+            #[cfg(unix)]
+            Some(path) => {
+                use std::os::unix::fs::FileTypeExt;
+
+                let path = std::path::PathBuf::from(path);
+                // 清理上次未正常退出留下的 socket 文件，其他类型的文件可能是配置错误，不删除
+                match std::fs::symlink_metadata(&path) {
+                    Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(&path)?,
+                    Ok(_) => return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} exists and is not a socket", path.display()))),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e),
+                }
+                Ok(Listener::Unix(tokio::net::UnixListener::bind(&path)?, path))
+            }
+            #[cfg(not(unix))]
+            Some(_) => Err(io::Error::new(io::ErrorKind::Unsupported, "unix socket is not supported on this platform")),
+            None => Ok(Listener::Tcp(TcpListener::bind(format!("{}:{}", config.host, config.port)).await?)),
+        }
+    }
+
+    async fn accept(&self) -> io::Result<(Stream, Option<SocketAddr>)> {
+        match self {
+            Listener::Tcp(listener) => {
+                let (stream, addr) = listener.accept().await?;
+                Ok((Stream::Tcp(stream), Some(addr)))
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener, _) => {
+                let (stream, _) = listener.accept().await?;
+                Ok((Stream::Unix(stream), None))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Listener::Tcp(listener) => match listener.local_addr() {
+                Ok(addr) => write!(f, "{}", addr),
+                Err(_) => write!(f, "tcp"),
+            },
+            #[cfg(unix)]
+            Listener::Unix(_, path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Listener::Unix(_, path) = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+///
+/// 启动服务，可选 TLS，收到 signal 后停止接收新连接，并在 signal 返回的截止时间前等待已有连接处理完成
+///
+pub async fn serve(listener: Listener, tls: Option<TlsAcceptor>, app: Router, signal: impl Future<Output = Instant>) {
+    info!("listening on {}{}", if tls.is_some() { "https://" } else { "" }, listener);
+    let graceful = GracefulShutdown::new();
+    tokio::pin!(signal);
+    let deadline = loop {
+        let (stream, remote_addr) = tokio::select! {
+            result = listener.accept() => match result {
+                Ok(v) => v,
+                Err(e) => {
+                    // 文件句柄耗尽等错误，稍后重试
+                    warn!("accept error: {}", e);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            },
+            deadline = &mut signal => break deadline,
+        };
+        let app = app.clone();
+        let tls = tls.clone();
+        let watcher = graceful.watcher();
+        match stream {
+            Stream::Tcp(stream) => {
+                let _ = stream.set_nodelay(true);
+                tokio::spawn(handle_connection(stream, remote_addr, tls, app, watcher));
+            }
+            #[cfg(unix)]
+            Stream::Unix(stream) => {
+                tokio::spawn(handle_connection(stream, remote_addr, tls, app, watcher));
+            }
+        }
+    };
+    drop(listener);
+    if tokio::time::timeout_at(deadline, graceful.shutdown()).await.is_err() {
+        warn!("等待连接关闭超时，强制退出");
+    }
+}
+
+async fn handle_connection<S>(stream: S, remote_addr: Option<SocketAddr>, tls: Option<TlsAcceptor>, app: Router, watcher: Watcher)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    match tls {
+        Some(acceptor) => match acceptor.accept(stream).await {
+            Ok(stream) => serve_connection(stream, remote_addr, app, watcher).await,
+            Err(e) => debug!("tls handshake error: {}", e),
+        },
+        None => serve_connection(stream, remote_addr, app, watcher).await,
+    }
+}
+
+async fn serve_connection<S>(stream: S, remote_addr: Option<SocketAddr>, app: Router, watcher: Watcher)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    // 与 into_make_service_with_connect_info 一致，handler 可以通过 ConnectInfo 获取客户端地址
+    let service = app.map_request(move |mut req: Request<Incoming>| {
+        if let Some(addr) = remote_addr {
+            req.extensions_mut().insert(ConnectInfo(addr));
+        }
+        req
+    });
+    let service = TowerToHyperService::new(service);
+    let conn = Builder::new(TokioExecutor::new()).serve_connection_with_upgrades(TokioIo::new(stream), service).into_owned();
+    if let Err(e) = watcher.watch(conn).await {
+        debug!("connection error: {}", e);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use axum::routing::get;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_serve_unix_socket() {
+        let path = std::env::temp_dir().join(format!("bubo-test-{}.sock", std::process::id()));
+        let config = ServerConfig { unix_socket: Some(path.display().to_string()), ..Default::default() };
+        let listener = Listener::bind(&config).await.unwrap();
+        let app = Router::new().route("/ping", get(|| async { "pong" }));
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(serve(listener, None, app, async { rx.await.unwrap_or_default(); Instant::now() + Duration::from_secs(1) }));
+
+        let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+        stream.write_all(b"GET /ping HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("pong"));
+
+        tx.send(()).unwrap();
+        server.await.unwrap();
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_bind_existing_file() {
+        let path = std::env::temp_dir().join(format!("bubo-test-{}.txt", std::process::id()));
+        std::fs::write(&path, "data").unwrap();
+        let config = ServerConfig { unix_socket: Some(path.display().to_string()), ..Default::default() };
+        let error = Listener::bind(&config).await.err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "data");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,22 +1,528 @@
-use tracing::info;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
-use tracing_appender::non_blocking::{NonBlockingBuilder, WorkerGuard};
-
-pub fn init(dir: &str, file: &str) -> (WorkerGuard, WorkerGuard) {
-    // 控制台非阻塞不丢失日志配置
-    let (non_blocking_stdout, guard_stdout) = NonBlockingBuilder::default().lossy(false).finish(std::io::stdout());
-    let layer_stdout = tracing_subscriber::fmt::layer().with_ansi(true).with_writer(non_blocking_stdout);
-    // 文件非阻塞不丢失日志配置
-    let file_appender = tracing_appender::rolling::daily(dir, file);
-    let (non_blocking_file, guard_file) = NonBlockingBuilder::default().lossy(false).finish(file_appender);
-    let layer_file = tracing_subscriber::fmt::layer().with_ansi(false).with_writer(non_blocking_file);
+use std::{fs::{self, File, OpenOptions}, io::{self, Write}, path::{Path, PathBuf}, sync::{Arc, Mutex}, time::Duration};
+
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use opentelemetry::{global, propagation::{Extractor, Injector}, trace::TracerProvider as _};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use once_cell::sync::OnceCell;
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::{Sampler, SdkTracerProvider}, Resource};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use time::{macros::format_description, OffsetDateTime};
+use tracing::{info, warn, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Layer, Registry};
+use tracing_appender::{non_blocking::{NonBlockingBuilder, WorkerGuard}, rolling::{Builder, Rotation}};
+
+use crate::{config::{LogFormat, LogRotation, LoggerConfig, OtlpConfig}, i18n, utils::error::{BuboError, BuboResult, BusinessErrorCode, SystemErrorCode}};
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+// init 创建，用于运行时修改过滤指令
+static LOG_LEVEL: OnceCell<Arc<LogLevel>> = OnceCell::new();
+
+// SeaORM 在 trace 级别为每个查询创建 span，导出到 OpenTelemetry 时总是启用
+const OTLP_DB_DIRECTIVE: &str = "sea_orm::database=trace";
+
+///
+/// 日志的后台写入线程和 OpenTelemetry 导出，drop 时写完剩余的日志和 span，需要一直持有到程序退出
+///
+pub struct LogGuard {
+    _guards: Vec<WorkerGuard>,
+    tracer_provider: Option<SdkTracerProvider>,
+}
+
+impl Drop for LogGuard {
+    fn drop(&mut self) {
+        if let Some(provider) = self.tracer_provider.take() {
+            if let Err(e) = provider.shutdown() {
+                warn!("关闭 OpenTelemetry 导出失败: {e}");
+            }
+        }
+    }
+}
+
+///
+/// 按配置初始化控制台、文件日志和 OpenTelemetry 导出，文件名前缀为 logger.file_name，为空时使用 app_name。
+/// 每个输出使用自己的 level，为空时使用全局的 level，设置了 RUST_LOG 时全局的 level 使用 RUST_LOG
+///
+pub fn init(config: &LoggerConfig, app_name: &str) -> BuboResult<LogGuard> {
+    let level = std::env::var(EnvFilter::DEFAULT_ENV).unwrap_or_else(|_| config.level.clone());
+    let sink_level = |sink: &str| if sink.is_empty() { level.clone() } else { sink.to_owned() };
+    let mut layers: Vec<BoxedLayer> = Vec::new();
+    let mut guards = Vec::new();
+    let mut sinks = Vec::new();
+    if config.console.enable {
+        // 控制台非阻塞不丢失日志配置
+        let (non_blocking, guard) = NonBlockingBuilder::default().lossy(false).finish(io::stdout());
+        let filter = reloadable(&mut sinks, sink_level(&config.console.level), None);
+        layers.push(sink_layer(config.console.format, config.console.ansi, non_blocking).with_filter(filter).boxed());
+        guards.push(guard);
+    }
+    if config.file.enable {
+        let name = file_name(config, app_name);
+        // 文件非阻塞不丢失日志配置
+        let (non_blocking, guard) = match config.file.rotation {
+            LogRotation::Size => {
+                let appender = SizeRollingAppender::new(&config.dir, name, config.file.max_size, config.file.max_files)
+                    .map_err(|e| log_error(&config.dir, e))?;
+                NonBlockingBuilder::default().lossy(false).finish(appender)
+            }
+            rotation => {
+                let rotation = match rotation {
+                    LogRotation::Minutely => Rotation::MINUTELY,
+                    LogRotation::Hourly => Rotation::HOURLY,
+                    LogRotation::Never => Rotation::NEVER,
+                    _ => Rotation::DAILY,
+                };
+                let mut builder = Builder::new().rotation(rotation).filename_prefix(name).filename_suffix("log");
+                if config.file.max_files > 0 {
+                    builder = builder.max_log_files(config.file.max_files);
+                }
+                let appender = builder.build(&config.dir).map_err(|e| log_error(&config.dir, e))?;
+                NonBlockingBuilder::default().lossy(false).finish(appender)
+            }
+        };
+        let filter = reloadable(&mut sinks, sink_level(&config.file.level), None);
+        layers.push(sink_layer(config.file.format, false, non_blocking).with_filter(filter).boxed());
+        guards.push(guard);
+    }
+    let mut tracer_provider = None;
+    if config.otlp.enable {
+        let provider = otlp_provider(&config.otlp, app_name)?;
+        let filter = reloadable(&mut sinks, sink_level(&config.otlp.level), Some(OTLP_DB_DIRECTIVE));
+        layers.push(tracing_opentelemetry::layer().with_tracer(provider.tracer("bubo")).with_filter(filter).boxed());
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        global::set_tracer_provider(provider.clone());
+        tracer_provider = Some(provider);
+    }
 
     // 日志订阅
     tracing_subscriber::registry()
-        .with(EnvFilter::from_default_env())
-        .with(layer_stdout)
-        .with(layer_file)
+        .with(layers)
         .init();
+    let _ = LOG_LEVEL.set(Arc::new(LogLevel::new(sinks, level)));
     info!("日志初始化完成");
-    (guard_stdout, guard_file)
-}
\ No newline at end of file
+    Ok(LogGuard { _guards: guards, tracer_provider })
+}
+
+///
+/// OTLP/HTTP 导出，批量发送，没有上游时按 sample_ratio 采样
+///
+fn otlp_provider(config: &OtlpConfig, app_name: &str) -> BuboResult<SdkTracerProvider> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(&config.endpoint)
+        .with_timeout(Duration::from_secs(config.timeout))
+        .build()
+        .map_err(|e| BuboError::system_error(SystemErrorCode::InternalServerError, format!("创建 OTLP 导出失败 {}: {e}", config.endpoint)))?;
+    let service_name = if config.service_name.is_empty() { app_name } else { config.service_name.as_str() };
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(config.sample_ratio))))
+        .with_resource(Resource::builder().with_service_name(service_name.to_owned()).build())
+        .build())
+}
+
+fn filter(directives: &str) -> EnvFilter {
+    EnvFilter::try_new(directives).unwrap_or_else(|_| EnvFilter::new("info"))
+}
+
+///
+/// 可以运行时替换的过滤器，extra 为总是附加的指令
+///
+fn reloadable(sinks: &mut Vec<ReloadSink>, directive: String, extra: Option<&'static str>) -> reload::Layer<EnvFilter, Registry> {
+    let (layer, handle) = reload::Layer::new(filter(&with_extra(&directive, extra)));
+    sinks.push(ReloadSink { handle, directive, extra });
+    layer
+}
+
+fn with_extra(directive: &str, extra: Option<&str>) -> String {
+    match extra {
+        Some(extra) => format!("{directive},{extra}"),
+        None => directive.to_owned(),
+    }
+}
+
+struct ReloadSink {
+    handle: reload::Handle<EnvFilter, Registry>,
+    // 配置的过滤指令，恢复时使用
+    directive: String,
+    extra: Option<&'static str>,
+}
+
+///
+/// 运行时修改的过滤指令
+///
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct LogLevelStatus {
+    // 当前的过滤指令，没有修改时为配置的 level
+    pub directive: String,
+    // 是否为运行时修改的
+    pub overridden: bool,
+    // 自动恢复为配置的时间
+    #[serde(with = "time::serde::rfc3339::option")]
+    #[schemars(with = "Option<crate::openapi::DateTime>")]
+    pub revert_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Default)]
+struct LevelOverride {
+    directive: Option<String>,
+    revert_at: Option<OffsetDateTime>,
+    // 每次修改加 1，自动恢复时只恢复自己设置的指令
+    generation: u64,
+}
+
+///
+/// 运行时修改所有输出的过滤指令，reset 或到期后恢复为每个输出配置的 level
+///
+pub struct LogLevel {
+    sinks: Vec<ReloadSink>,
+    // 配置的全局 level
+    default: String,
+    state: Mutex<LevelOverride>,
+}
+
+///
+/// init 之后可用，测试中没有初始化日志时为 None
+///
+pub fn log_level() -> Option<Arc<LogLevel>> {
+    LOG_LEVEL.get().cloned()
+}
+
+impl LogLevel {
+    fn new(sinks: Vec<ReloadSink>, default: String) -> Self {
+        Self { sinks, default, state: Mutex::new(LevelOverride::default()) }
+    }
+
+    pub fn status(&self) -> LogLevelStatus {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        LogLevelStatus {
+            directive: state.directive.clone().unwrap_or_else(|| self.default.clone()),
+            overridden: state.directive.is_some(),
+            revert_at: state.revert_at,
+        }
+    }
+
+    ///
+    /// 所有输出使用 directive，revert_after 之后自动恢复，需要在 tokio 运行时中调用
+    ///
+    pub fn set(self: &Arc<Self>, directive: &str, revert_after: Option<Duration>) -> BuboResult<LogLevelStatus> {
+        if let Err(e) = EnvFilter::try_new(directive) {
+            return Err(BuboError::business_error(BusinessErrorCode::ValidationError, i18n::t_args("log.invalid_directive", &[("error", &e)])));
+        }
+        // 在修改过滤指令之前检查，超出范围时不会留下没有自动恢复的修改
+        let revert_at = match revert_after {
+            Some(after) => match time::Duration::try_from(after).ok().and_then(|after| OffsetDateTime::now_utc().checked_add(after)) {
+                Some(revert_at) => Some(revert_at),
+                None => return Err(BuboError::business_error(BusinessErrorCode::ValidationError, i18n::t("log.invalid_revert_after"))),
+            },
+            None => None,
+        };
+        let generation = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            self.reload(Some(directive))?;
+            state.generation += 1;
+            state.directive = Some(directive.to_owned());
+            state.revert_at = revert_at;
+            state.generation
+        };
+        warn!("日志过滤指令修改为 {directive}，自动恢复时间 {revert_after:?}");
+        if let Some(after) = revert_after {
+            let level = self.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(after).await;
+                level.revert(generation);
+            });
+        }
+        Ok(self.status())
+    }
+
+    ///
+    /// 恢复为配置的 level
+    ///
+    pub fn reset(&self) -> BuboResult<LogLevelStatus> {
+        {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            self.reset_locked(&mut state)?;
+        }
+        warn!("日志过滤指令恢复为配置");
+        Ok(self.status())
+    }
+
+    ///
+    /// 到期时仍是 generation 对应的修改才恢复，检查和恢复在同一次加锁中完成，期间的 set 不会被覆盖
+    ///
+    fn revert(&self, generation: u64) {
+        let result = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            if state.generation != generation {
+                return;
+            }
+            self.reset_locked(&mut state)
+        };
+        match result {
+            Ok(()) => warn!("日志过滤指令到期，恢复为配置"),
+            Err(e) => warn!("自动恢复日志过滤指令失败: {e}"),
+        }
+    }
+
+    fn reset_locked(&self, state: &mut LevelOverride) -> BuboResult<()> {
+        self.reload(None)?;
+        *state = LevelOverride { generation: state.generation + 1, ..Default::default() };
+        Ok(())
+    }
+
+    fn reload(&self, directive: Option<&str>) -> BuboResult<()> {
+        for sink in &self.sinks {
+            let directive = with_extra(directive.unwrap_or(&sink.directive), sink.extra);
+            sink.handle.reload(filter(&directive))
+                .map_err(|e| BuboError::system_error(SystemErrorCode::InternalServerError, format!("修改日志过滤指令失败: {e}")))?;
+        }
+        Ok(())
+    }
+}
+
+///
+/// 从请求头的 W3C `traceparent`/`tracestate` 中取出上游的 trace，作为 span 的父级
+///
+pub fn set_parent_from_headers(span: &Span, headers: &HeaderMap) {
+    let parent = global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)));
+    let _ = span.set_parent(parent);
+}
+
+///
+/// 把当前 span 的 trace 写入请求头 `traceparent`，用于调用下游服务
+///
+pub fn inject_context(headers: &mut HeaderMap) {
+    let context = Span::current().context();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&context, &mut HeaderInjector(headers)));
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(HeaderName::as_str).collect()
+    }
+}
+
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(key), HeaderValue::try_from(value)) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+///
+/// 一个输出的格式化层
+///
+fn sink_layer<W>(format: LogFormat, ansi: bool, writer: W) -> BoxedLayer
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer().with_ansi(ansi).with_writer(writer);
+    match format {
+        LogFormat::Full => layer.boxed(),
+        LogFormat::Pretty => layer.pretty().boxed(),
+        LogFormat::Compact => layer.compact().boxed(),
+        LogFormat::Json => layer.json().flatten_event(true).with_current_span(true).with_span_list(true).boxed(),
+    }
+}
+
+///
+/// 日志文件名前缀，兼容带 `.log` 后缀的旧配置
+///
+fn file_name<'a>(config: &'a LoggerConfig, app_name: &'a str) -> &'a str {
+    if config.file_name.is_empty() {
+        app_name
+    } else {
+        config.file_name.strip_suffix(".log").unwrap_or(&config.file_name)
+    }
+}
+
+fn log_error(dir: &str, e: impl std::fmt::Display) -> BuboError {
+    BuboError::system_error(SystemErrorCode::InternalServerError, format!("创建日志文件失败 {dir}: {e}"))
+}
+
+///
+/// 按大小轮转的日志文件，当前文件为 `{dir}/{name}.log`，写入后超过 max_size 时改名为 `{name}.{UTC 时间}.log`，
+/// max_files 大于 0 时只保留最新的 max_files - 1 个历史文件
+///
+pub struct SizeRollingAppender {
+    dir: PathBuf,
+    name: String,
+    max_size: u64,
+    max_files: usize,
+    file: File,
+    size: u64,
+}
+
+impl SizeRollingAppender {
+    pub fn new(dir: impl AsRef<Path>, name: &str, max_size: u64, max_files: usize) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{name}.log"));
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { dir, name: name.to_owned(), max_size, max_files, file, size })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let timestamp = OffsetDateTime::now_utc()
+            .format(format_description!("[year]-[month]-[day]T[hour]-[minute]-[second]-[subsecond digits:6]"))
+            .map_err(io::Error::other)?;
+        let current = self.dir.join(format!("{}.log", self.name));
+        fs::rename(&current, self.dir.join(format!("{}.{timestamp}.log", self.name)))?;
+        self.file = OpenOptions::new().create(true).append(true).open(&current)?;
+        self.size = 0;
+        self.remove_old_files()
+    }
+
+    ///
+    /// 删除超出 max_files 的历史文件，文件名中的时间可以按字符串排序
+    ///
+    fn remove_old_files(&self) -> io::Result<()> {
+        if self.max_files == 0 {
+            return Ok(());
+        }
+        let prefix = format!("{}.", self.name);
+        let current = format!("{}.log", self.name);
+        let mut files: Vec<String> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|name| name.starts_with(&prefix) && name.ends_with(".log") && *name != current)
+            .collect();
+        files.sort();
+        let keep = self.max_files.saturating_sub(1);
+        if files.len() > keep {
+            for name in &files[..files.len() - keep] {
+                fs::remove_file(self.dir.join(name))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for SizeRollingAppender {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        let n = self.file.write(buf)?;
+        self.size += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_size_rolling() {
+        let dir = std::env::temp_dir().join(format!("bubo-log-{}", std::process::id()));
+        let mut appender = SizeRollingAppender::new(&dir, "app", 10, 3).unwrap();
+        for line in ["12345\n", "67890\n", "abcde\n", "fghij\n"] {
+            appender.write_all(line.as_bytes()).unwrap();
+        }
+        appender.flush().unwrap();
+
+        let mut files: Vec<String> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name().into_string().unwrap()).collect();
+        files.sort();
+        // 当前文件和最新的两个历史文件
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[2], "app.log");
+        assert_eq!(fs::read_to_string(dir.join("app.log")).unwrap(), "fghij\n");
+        assert_eq!(fs::read_to_string(dir.join(&files[1])).unwrap(), "abcde\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_trace_context() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let provider = SdkTracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        tracing::subscriber::with_default(subscriber, || {
+            let mut headers = HeaderMap::new();
+            headers.insert("traceparent", HeaderValue::from_static("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"));
+            let span = tracing::info_span!("request");
+            set_parent_from_headers(&span, &headers);
+            let _entered = span.enter();
+
+            // 下游请求使用同一个 trace id，父级为当前 span
+            let mut downstream = HeaderMap::new();
+            inject_context(&mut downstream);
+            let traceparent = downstream["traceparent"].to_str().unwrap();
+            assert!(traceparent.starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"));
+            assert!(traceparent.ends_with("-01"));
+            assert!(!traceparent.contains("00f067aa0ba902b7"));
+        });
+    }
+
+    #[tokio::test]
+    async fn test_otlp_provider() {
+        // 在 tokio 运行时中创建和关闭导出，不需要 collector
+        let config = OtlpConfig { enable: true, ..Default::default() };
+        let provider = otlp_provider(&config, "bubo").unwrap();
+        provider.shutdown().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_log_level() {
+        let mut sinks = Vec::new();
+        let filter = reloadable(&mut sinks, "info".to_owned(), None);
+        let subscriber = tracing_subscriber::registry().with(tracing_subscriber::fmt::layer().with_writer(io::sink).with_filter(filter));
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let level = Arc::new(LogLevel::new(sinks, "info".to_owned()));
+        assert!(!tracing::enabled!(tracing::Level::DEBUG));
+
+        let status = level.set("debug", Some(Duration::from_millis(50))).unwrap();
+        assert!(status.overridden);
+        assert!(status.revert_at.is_some());
+        assert!(tracing::enabled!(tracing::Level::DEBUG));
+        assert!(level.set("debug,[", None).is_err());
+
+        // 到期后恢复为配置
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(level.status().directive, "info");
+        assert!(!level.status().overridden);
+        assert!(!tracing::enabled!(tracing::Level::DEBUG));
+
+        // 再次修改后旧的定时器不会恢复
+        level.set("debug", Some(Duration::from_millis(50))).unwrap();
+        level.set("trace", None).unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(level.status().directive, "trace");
+        level.revert(1);
+        assert_eq!(level.status().directive, "trace");
+        let generation = level.state.lock().unwrap().generation;
+        level.revert(generation);
+        assert!(!level.status().overridden);
+
+        // 恢复时间溢出时不修改
+        assert!(level.set("debug", Some(Duration::from_secs(u64::MAX))).is_err());
+        assert!(!level.status().overridden);
+        assert!(!tracing::enabled!(tracing::Level::DEBUG));
+    }
+
+    #[test]
+    fn test_file_name() {
+        let mut config = LoggerConfig::default();
+        assert_eq!(file_name(&config, "admin-api"), "admin-api");
+        config.file_name = "bubo.log".to_owned();
+        assert_eq!(file_name(&config, "admin-api"), "bubo");
+    }
+}
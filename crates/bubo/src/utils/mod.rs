@@ -9,6 +9,8 @@ pub mod error;
 pub mod redis;
 pub mod database;
 pub mod serde;
+pub mod listener;
+pub mod tls;
 
 pub fn sha256_hash(input: &str) -> String {
     let mut hasher = Sha256::new();
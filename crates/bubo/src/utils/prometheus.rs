@@ -1,23 +1,118 @@
 use std::time::Instant;
 
 use axum::{extract::{MatchedPath, Request}, middleware::Next, response::IntoResponse};
-use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use fred::{error::RedisError, interfaces::MetricsInterface};
+use metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder, PrometheusHandle};
+use sea_orm::{metric::Info, DatabaseConnection};
 
+use crate::server::AppState;
 
+// 应用记录业务指标：`bubo::utils::prometheus::counter!("orders_total", "channel" => "web").increment(1)`
+pub use metrics::{counter, gauge, histogram};
 
-pub(crate) fn setup_metrics_recorder() -> PrometheusHandle {
-    const EXPONENTIAL_SECONDS: &[f64] = &[
-        0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
-    ];
+const EXPONENTIAL_SECONDS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Debug, Clone)]
+enum MetricKind {
+    Counter,
+    Gauge,
+    // 为空时导出为 summary
+    Histogram(Vec<f64>),
+}
+
+///
+/// 指标的说明和直方图分桶，内置指标之外的业务指标通过 Hooks::metrics 注册，
+/// 注册后用 counter!、gauge!、histogram! 记录
+///
+/// ```ignore
+/// fn metrics(registry: &mut MetricsRegistry) {
+///     registry.counter("orders_total", "订单数")
+///         .histogram("order_amount", "订单金额", &[10.0, 100.0, 1000.0]);
+/// }
+/// ```
+///
+#[derive(Debug, Clone, Default)]
+pub struct MetricsRegistry {
+    metrics: Vec<(String, String, MetricKind)>,
+}
+
+impl MetricsRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn counter(&mut self, name: impl Into<String>, help: impl Into<String>) -> &mut Self {
+        self.metrics.push((name.into(), help.into(), MetricKind::Counter));
+        self
+    }
+
+    pub fn gauge(&mut self, name: impl Into<String>, help: impl Into<String>) -> &mut Self {
+        self.metrics.push((name.into(), help.into(), MetricKind::Gauge));
+        self
+    }
+
+    pub fn histogram(&mut self, name: impl Into<String>, help: impl Into<String>, buckets: &[f64]) -> &mut Self {
+        self.metrics.push((name.into(), help.into(), MetricKind::Histogram(buckets.to_vec())));
+        self
+    }
+
+    ///
+    /// 框架内置的指标
+    ///
+    pub(crate) fn builtin() -> Self {
+        let mut registry = Self::new();
+        registry.counter("http_requests_total", "HTTP 请求数")
+            .histogram("http_requests_duration_seconds", "HTTP 请求耗时", EXPONENTIAL_SECONDS)
+            .counter("http_rejections_total", "超时或过载被拒绝的请求数，reason 为 timeout 或 overloaded")
+            .histogram("db_query_duration_seconds", "数据库查询耗时，operation 为 SQL 类型", EXPONENTIAL_SECONDS)
+            .gauge("db_pool_connections", "数据库连接池连接数，state 为 idle 或 active")
+            .gauge("db_pool_max_connections", "数据库连接池最大连接数")
+            .counter("redis_commands_total", "Redis 命令数")
+            .counter("redis_command_duration_milliseconds_total", "Redis 命令累计耗时（毫秒）")
+            .gauge("redis_command_duration_milliseconds_max", "两次采集之间 Redis 命令的最大耗时（毫秒）")
+            .counter("redis_errors_total", "Redis 错误数，kind 为错误类型")
+            .counter("auth_logins_total", "登录次数，result 为 success 或 failure")
+            .counter("auth_token_refreshes_total", "刷新令牌次数，result 为 success 或 failure")
+            .counter("snowflake_sequence_overflow_total", "雪花 id 序列号用完后等待下一毫秒的次数")
+            .counter("snowflake_clock_rollback_total", "生成雪花 id 时检测到时钟回拨的次数");
+        registry
+    }
 
-    PrometheusBuilder::new()
-        .set_buckets_for_metric(
-            Matcher::Full("http_requests_duration_seconds".to_string()),
-            EXPONENTIAL_SECONDS,
-        )
+    fn builder(&self) -> Result<PrometheusBuilder, BuildError> {
+        let mut builder = PrometheusBuilder::new();
+        for (name, _, kind) in &self.metrics {
+            if let MetricKind::Histogram(buckets) = kind {
+                if !buckets.is_empty() {
+                    builder = builder.set_buckets_for_metric(Matcher::Full(name.clone()), buckets)?;
+                }
+            }
+        }
+        Ok(builder)
+    }
+
+    ///
+    /// 写入说明，需要在 recorder 安装之后调用
+    ///
+    fn describe(&self) {
+        for (name, help, kind) in &self.metrics {
+            match kind {
+                MetricKind::Counter => metrics::describe_counter!(name.clone(), help.clone()),
+                MetricKind::Gauge => metrics::describe_gauge!(name.clone(), help.clone()),
+                MetricKind::Histogram(_) => metrics::describe_histogram!(name.clone(), help.clone()),
+            }
+        }
+    }
+}
+
+pub(crate) fn setup_metrics_recorder(registry: &MetricsRegistry) -> PrometheusHandle {
+    let handle = registry.builder()
         .unwrap()
         .install_recorder()
-        .unwrap()
+        .unwrap();
+    registry.describe();
+    handle
 }
 
 pub(crate) async fn track_metrics(req: Request, next: Next) -> impl IntoResponse {
@@ -45,3 +140,122 @@ pub(crate) async fn track_metrics(req: Request, next: Next) -> impl IntoResponse
 
     response
 }
+
+///
+/// 采集时读取连接池状态和 Redis 命令耗时
+///
+pub(crate) fn collect(state: &AppState) {
+    let pool = match &state.db {
+        DatabaseConnection::SqlxPostgresPoolConnection(_) => {
+            let pool = state.db.get_postgres_connection_pool();
+            Some((pool.size(), pool.num_idle()))
+        }
+        DatabaseConnection::SqlxSqlitePoolConnection(_) => {
+            let pool = state.db.get_sqlite_connection_pool();
+            Some((pool.size(), pool.num_idle()))
+        }
+        _ => None,
+    };
+    if let Some((size, idle)) = pool {
+        let idle = u32::try_from(idle).unwrap_or(size);
+        metrics::gauge!("db_pool_connections", "state" => "idle").set(idle);
+        metrics::gauge!("db_pool_connections", "state" => "active").set(size.saturating_sub(idle));
+        metrics::gauge!("db_pool_max_connections").set(state.config.database.max_connections);
+    }
+
+    let mut max = 0;
+    for client in state.redis.clients() {
+        // 耗时单位为毫秒，读取后清零
+        let stats = client.take_latency_metrics();
+        metrics::counter!("redis_commands_total").increment(stats.samples);
+        metrics::counter!("redis_command_duration_milliseconds_total").increment(u64::try_from(stats.sum).unwrap_or_default());
+        max = max.max(stats.max);
+    }
+    metrics::gauge!("redis_command_duration_milliseconds_max").set(max as f64);
+}
+
+///
+/// SeaORM 的查询回调，由 database::connect 注册
+///
+pub(crate) fn record_query(info: &Info<'_>) {
+    let labels = [
+        ("operation", sql_operation(&info.statement.sql)),
+        ("failed", if info.failed { "true" } else { "false" }),
+    ];
+    metrics::histogram!("db_query_duration_seconds", &labels).record(info.elapsed.as_secs_f64());
+}
+
+fn sql_operation(sql: &str) -> &'static str {
+    let keyword = sql.split_whitespace().next().unwrap_or_default();
+    ["SELECT", "INSERT", "UPDATE", "DELETE", "BEGIN", "COMMIT", "ROLLBACK"]
+        .into_iter()
+        .find(|operation| keyword.eq_ignore_ascii_case(operation))
+        .unwrap_or("OTHER")
+}
+
+///
+/// 按错误类型记录一次 redis 错误，RedisError 转为 BuboError 时自动记录，
+/// 直接处理 RedisError 的地方（例如只打印日志后继续）需要手动调用
+///
+pub fn record_redis_error(error: &RedisError) {
+    metrics::counter!("redis_errors_total", "kind" => error.kind().to_str()).increment(1);
+}
+
+///
+/// 记录一次登录结果，登录成功由 create_token 记录，登录失败由应用在校验失败时调用
+///
+pub fn record_login(success: bool) {
+    metrics::counter!("auth_logins_total", "result" => result(success)).increment(1);
+}
+
+///
+/// 记录一次刷新令牌结果
+///
+pub fn record_token_refresh(success: bool) {
+    metrics::counter!("auth_token_refreshes_total", "result" => result(success)).increment(1);
+}
+
+fn result(success: bool) -> &'static str {
+    if success { "success" } else { "failure" }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use sea_orm::{DbBackend, Statement};
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_sql_operation() {
+        assert_eq!(sql_operation("SELECT \"id\" FROM \"admin_user\""), "SELECT");
+        assert_eq!(sql_operation("  insert into t values (1)"), "INSERT");
+        assert_eq!(sql_operation("WITH t AS (SELECT 1) SELECT * FROM t"), "OTHER");
+        assert_eq!(sql_operation(""), "OTHER");
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut registry = MetricsRegistry::builtin();
+        registry.counter("orders_total", "订单数").histogram("order_amount", "订单金额", &[10.0, 100.0]);
+        let recorder = registry.builder().unwrap().build_recorder();
+        let handle = recorder.handle();
+        metrics::with_local_recorder(&recorder, || {
+            registry.describe();
+            counter!("orders_total", "channel" => "web").increment(2);
+            histogram!("order_amount").record(50.0);
+            record_login(false);
+            let statement = Statement::from_string(DbBackend::Postgres, "SELECT 1");
+            record_query(&Info { elapsed: Duration::from_millis(3), statement: &statement, failed: false });
+        });
+
+        let output = handle.render();
+        assert!(output.contains("# HELP orders_total 订单数"), "{output}");
+        assert!(output.contains("orders_total{channel=\"web\"} 2"), "{output}");
+        assert!(output.contains("order_amount_bucket{le=\"100\"} 1"), "{output}");
+        assert!(output.contains("auth_logins_total{result=\"failure\"} 1"), "{output}");
+        assert!(output.contains("db_query_duration_seconds_bucket{operation=\"SELECT\",failed=\"false\",le=\"0.005\"} 1"), "{output}");
+    }
+}
@@ -1,6 +1,6 @@
 use std::time::Duration;
 
-use fred::{prelude::{ClientLike, KeysInterface, RedisPool}, types::{Builder, Expiration, ReconnectPolicy, RedisConfig}};
+use fred::{prelude::{ClientLike, KeysInterface, RedisPool}, types::{Builder, Expiration, ReconnectPolicy, RedisConfig, TracingConfig}};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tracing::info;
@@ -9,22 +9,19 @@ use super::error::BuboResult;
 
 
 ///
-/// 初始化redis
+/// 初始化redis，tracing 为 true 时每个命令创建 `fred.command` span，用于导出到 OpenTelemetry
 /// 
-pub async fn init() -> RedisPool {
-    let redis_url = std::env::var("REDIS_URL").expect("REDIS_URL is not set in .env file");
-    let pool_size = std::env::var("REDIS_POOL_SIZE")
-    .ok()
-    .and_then(|v| v.parse::<usize>().ok())
-    .unwrap_or(8);
-    let config = RedisConfig::from_url(&redis_url).expect("Failed to create redis config from url");
-    let pool = Builder::from_config(config)
+pub async fn init(config: &crate::config::RedisConfig, tracing: bool) -> RedisPool {
+    let mut redis_config = RedisConfig::from_url(&config.url).expect("Failed to create redis config from url");
+    redis_config.tracing = TracingConfig::new(tracing);
+    let connection_timeout = Duration::from_secs(config.connection_timeout);
+    let pool = Builder::from_config(redis_config)
     .with_connection_config(|config| {
-      config.connection_timeout = Duration::from_secs(10);
+      config.connection_timeout = connection_timeout;
     })
     // use exponential backoff, starting at 100 ms and doubling on each failed attempt up to 30 sec
     .set_policy(ReconnectPolicy::new_exponential(0, 100, 30_000, 2))
-    .build_pool(pool_size)
+    .build_pool(config.pool_size)
     .expect("Failed to create redis pool");
 
     pool.init().await.expect("Failed to connect to redis");
@@ -59,11 +56,16 @@ pub async fn set<T>(redis: &RedisPool, key: impl AsRef<str>, t: &T, expire: Opti
 where 
     T: ?Sized + Serialize,
 {
-    redis.set(key.as_ref(), serde_json::to_string(t)?, expire, None, false).await?;
+    redis.set::<(), _, _>(key.as_ref(), serde_json::to_string(t)?, expire, None, false).await?;
+    Ok(())
+}
+
+pub async fn set_string(redis: &RedisPool, key: impl AsRef<str>, value: String, expire: Option<Expiration>) -> BuboResult<()> {
+    redis.set::<(), _, _>(key.as_ref(), value, expire, None, false).await?;
     Ok(())
 }
 
 pub async fn del(redis: &RedisPool, key: impl AsRef<str>) -> BuboResult<()> {
-    redis.del(key.as_ref()).await?;
+    redis.del::<(), _>(key.as_ref()).await?;
     Ok(())
 }
\ No newline at end of file
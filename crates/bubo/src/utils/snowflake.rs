@@ -39,7 +39,10 @@ impl Snowflake {
 
             // 获取当前时间戳
             let mut timestamp = Self::current_timestamp();
-            if timestamp < last_timestamp {
+            // CAS 成功后才计数，并发冲突重试时不重复计数
+            let rolled_back = timestamp < last_timestamp;
+            let mut overflowed = false;
+            if rolled_back {
                 // 时钟回退，直接返回到未来时间
                 timestamp = last_timestamp; // 强制等待当前逻辑时钟消化
             }
@@ -50,6 +53,7 @@ impl Snowflake {
                 new_sequence = (sequence + 1) & SEQUENCE_MASK;
                 if new_sequence == 0 {
                     // 如果序列号溢出，等待下一毫秒
+                    overflowed = true;
                     timestamp = self.wait_for_next_millis(last_timestamp);
                 }
             } else {
@@ -62,6 +66,12 @@ impl Snowflake {
 
             // CAS 操作尝试更新状态
             if self.state.compare_exchange(current_state, new_state).is_ok() {
+                if rolled_back {
+                    metrics::counter!("snowflake_clock_rollback_total").increment(1);
+                }
+                if overflowed {
+                    metrics::counter!("snowflake_sequence_overflow_total").increment(1);
+                }
                 // 更新成功，生成 ID 并返回
                 return self.compose_id(timestamp, new_sequence);
             }
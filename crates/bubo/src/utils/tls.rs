@@ -0,0 +1,171 @@
+use std::{fs::File, io::{self, BufReader}, path::{Path, PathBuf}, sync::{Arc, RwLock}, time::{Duration, SystemTime}};
+
+use tokio_rustls::{rustls::{crypto::aws_lc_rs, server::{ClientHello, ResolvesServerCert}, sign::CertifiedKey, Error, InconsistentKeys, ServerConfig}, TlsAcceptor};
+use tracing::{info, warn};
+
+use crate::{config::TlsConfig, server::Shutdown};
+
+///
+/// 证书解析器，证书文件变化后无需重启即可生效
+///
+#[derive(Debug)]
+pub struct CertResolver {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    certified_key: RwLock<Arc<CertifiedKey>>,
+    modified: RwLock<Option<SystemTime>>,
+}
+
+impl CertResolver {
+    pub fn new(cert_path: impl Into<PathBuf>, key_path: impl Into<PathBuf>) -> io::Result<Self> {
+        let cert_path = cert_path.into();
+        let key_path = key_path.into();
+        let certified_key = load_certified_key(&cert_path, &key_path)?;
+        let modified = last_modified(&cert_path, &key_path);
+        Ok(Self { cert_path, key_path, certified_key: RwLock::new(Arc::new(certified_key)), modified: RwLock::new(modified) })
+    }
+
+    ///
+    /// 证书或私钥文件有变化时重新加载，返回是否重新加载。
+    /// 证书和私钥不匹配时（例如轮换时只写入了其中一个）返回错误并继续使用旧证书，下次检查时重试
+    ///
+    pub fn reload_if_changed(&self) -> io::Result<bool> {
+        let modified = last_modified(&self.cert_path, &self.key_path);
+        if modified == *self.modified.read().unwrap() {
+            return Ok(false);
+        }
+        let certified_key = load_certified_key(&self.cert_path, &self.key_path)?;
+        *self.certified_key.write().unwrap() = Arc::new(certified_key);
+        *self.modified.write().unwrap() = modified;
+        Ok(true)
+    }
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.certified_key.read().unwrap().clone())
+    }
+}
+
+///
+/// 根据配置创建 TLS acceptor，reload_interval 大于 0 时定时检查证书变化，停机开始后停止检查
+///
+pub fn acceptor(config: &TlsConfig, shutdown: &Shutdown) -> io::Result<TlsAcceptor> {
+    let resolver = Arc::new(CertResolver::new(&config.cert, &config.key)?);
+    let mut server_config = ServerConfig::builder_with_provider(Arc::new(aws_lc_rs::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(io::Error::other)?
+        .with_no_client_auth()
+        .with_cert_resolver(resolver.clone());
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    if config.reload_interval > 0 {
+        tokio::spawn(reload_loop(resolver, Duration::from_secs(config.reload_interval), shutdown.clone()));
+    }
+    Ok(TlsAcceptor::from(Arc::new(server_config)))
+}
+
+async fn reload_loop(resolver: Arc<CertResolver>, interval: Duration, shutdown: Shutdown) {
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {},
+            _ = shutdown.wait() => return,
+        }
+        match resolver.reload_if_changed() {
+            Ok(true) => info!("TLS证书已重新加载: {}", resolver.cert_path.display()),
+            Ok(false) => {}
+            // 新证书有问题时继续使用旧证书
+            Err(e) => warn!("TLS证书重新加载失败，继续使用旧证书: {}", e),
+        }
+    }
+}
+
+fn load_certified_key(cert_path: &Path, key_path: &Path) -> io::Result<CertifiedKey> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(cert_path)?)).collect::<io::Result<Vec<_>>>()?;
+    if certs.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("no certificate found in {}", cert_path.display())));
+    }
+    let key = rustls_pemfile::private_key(&mut BufReader::new(File::open(key_path)?))?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("no private key found in {}", key_path.display())))?;
+    let signing_key = aws_lc_rs::sign::any_supported_type(&key).map_err(io::Error::other)?;
+    let certified_key = CertifiedKey::new(certs, signing_key);
+    match certified_key.keys_match() {
+        // 无法比较公钥时不作为错误
+        Ok(()) | Err(Error::InconsistentKeys(InconsistentKeys::Unknown)) => Ok(certified_key),
+        Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, 
+            format!("certificate {} does not match private key {}: {}", cert_path.display(), key_path.display(), e))),
+    }
+}
+
+fn last_modified(cert_path: &Path, key_path: &Path) -> Option<SystemTime> {
+    let cert = std::fs::metadata(cert_path).and_then(|m| m.modified()).ok()?;
+    let key = std::fs::metadata(key_path).and_then(|m| m.modified()).ok()?;
+    Some(cert.max(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, FileTimes};
+
+    use rcgen::{CertifiedKey as GeneratedKey, generate_simple_self_signed};
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    // 写入文件并设置修改时间，避免文件系统时间精度导致检测不到变化
+    fn write(path: &Path, content: &str, modified: SystemTime) {
+        fs::write(path, content).unwrap();
+        File::options().write(true).open(path).unwrap().set_times(FileTimes::new().set_modified(modified)).unwrap();
+    }
+
+    fn served_cert(resolver: &CertResolver) -> Vec<u8> {
+        resolver.certified_key.read().unwrap().cert[0].to_vec()
+    }
+
+    #[test]
+    fn test_reload() {
+        let dir = std::env::temp_dir().join(format!("bubo-tls-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (cert_path, key_path) = (dir.join("cert.pem"), dir.join("key.pem"));
+        let GeneratedKey { cert: old_cert, key_pair: old_key } = generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+        let GeneratedKey { cert: new_cert, key_pair: new_key } = generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+        let now = SystemTime::now();
+        write(&cert_path, &old_cert.pem(), now);
+        write(&key_path, &old_key.serialize_pem(), now);
+
+        let resolver = CertResolver::new(&cert_path, &key_path).unwrap();
+        assert!(!resolver.reload_if_changed().unwrap());
+        assert_eq!(served_cert(&resolver), old_cert.der().to_vec());
+
+        // 只写入了新证书，私钥还是旧的
+        write(&cert_path, &new_cert.pem(), now + Duration::from_secs(1));
+        assert!(resolver.reload_if_changed().unwrap_err().to_string().contains("does not match"));
+        assert_eq!(served_cert(&resolver), old_cert.der().to_vec());
+
+        write(&key_path, &new_key.serialize_pem(), now + Duration::from_secs(2));
+        assert!(resolver.reload_if_changed().unwrap());
+        assert_eq!(served_cert(&resolver), new_cert.der().to_vec());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reload_loop_shutdown() {
+        let dir = std::env::temp_dir().join(format!("bubo-tls-shutdown-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (cert_path, key_path) = (dir.join("cert.pem"), dir.join("key.pem"));
+        let GeneratedKey { cert, key_pair } = generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+        fs::write(&cert_path, cert.pem()).unwrap();
+        fs::write(&key_path, key_pair.serialize_pem()).unwrap();
+        let resolver = Arc::new(CertResolver::new(&cert_path, &key_path).unwrap());
+
+        let shutdown = Shutdown::new();
+        let task = tokio::spawn(reload_loop(resolver.clone(), Duration::from_millis(10), shutdown.clone()));
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(!task.is_finished());
+        shutdown.trigger(Duration::from_secs(1));
+        tokio::time::timeout(Duration::from_secs(1), task).await.unwrap().unwrap();
+        // 任务结束后释放证书解析器
+        assert_eq!(Arc::strong_count(&resolver), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,20 +1,23 @@
 use std::collections::HashSet;
 
+use schemars::JsonSchema;
 use serde::Serialize;
 use serde_with::{serde_as, DisplayFromStr};
 
 use crate::controllers::middlewares::auth::AuthUser;
 
 #[serde_as]
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct AuthUserResponse {
     #[serde_as(as = "DisplayFromStr")]
+    #[schemars(with = "crate::openapi::Id")]
     pub id: i64,
     pub username: String,
     pub nick_name: String,
     pub is_admin: bool,
     pub roles: HashSet<String>,
     pub permissions: HashSet<String>,
+    pub locale: Option<String>,
 }
 
 impl AuthUserResponse {
@@ -26,6 +29,26 @@ impl AuthUserResponse {
             is_admin: value.is_admin, 
             roles: value.roles, 
             permissions: value.permissions, 
+            locale: value.locale,
         }
     }
-}
\ No newline at end of file
+}
+
+///
+/// 登录和刷新令牌的响应
+///
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TokenResponse {
+    pub status: bool,
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    // access token 有效期（秒）
+    pub expires_in: i64,
+}
+
+impl TokenResponse {
+    pub fn new((access_token, refresh_token, token_type, expires_in): (String, String, &'static str, i64)) -> Self {
+        Self { status: true, access_token, refresh_token, token_type: token_type.to_owned(), expires_in }
+    }
+}
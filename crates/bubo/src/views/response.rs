@@ -0,0 +1,130 @@
+use axum::{response::{IntoResponse, Response}, Json};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::database::Page;
+
+///
+/// 成功响应 `{"status": true, "data": T}`，没有数据时为 `{"status": true}`
+///
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ApiResponse<T = ()> {
+    pub status: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "T")]
+    pub data: Option<T>,
+}
+
+impl<T> ApiResponse<T> {
+    pub fn ok(data: T) -> Self {
+        Self { status: true, data: Some(data) }
+    }
+}
+
+impl ApiResponse {
+    pub fn empty() -> Self {
+        Self { status: true, data: None }
+    }
+}
+
+impl<T: Serialize> IntoResponse for ApiResponse<T> {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+///
+/// 分页响应，data 为当前页数据
+///
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PageResponse<T> {
+    pub status: bool,
+    pub data: Vec<T>,
+    // 当前页，从 1 开始
+    pub page: u64,
+    pub page_size: u64,
+    // 总条数
+    pub total: u64,
+    // 总页数
+    pub num_pages: u64,
+}
+
+impl<T> PageResponse<T> {
+    pub fn new(page: Page<T>) -> Self {
+        Self { status: true, data: page.items, page: page.page, page_size: page.page_size, total: page.total, num_pages: page.num_pages }
+    }
+}
+
+impl<T> From<Page<T>> for PageResponse<T> {
+    fn from(page: Page<T>) -> Self {
+        Self::new(page)
+    }
+}
+
+impl<T: Serialize> IntoResponse for PageResponse<T> {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+///
+/// 错误响应，status 为 false
+///
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ErrorResponse {
+    pub status: bool,
+    pub error_code: usize,
+    pub error_message: String,
+}
+
+impl ErrorResponse {
+    pub fn new(error_code: impl Into<usize>, error_message: impl Into<String>) -> Self {
+        Self { status: false, error_code: error_code.into(), error_message: error_message.into() }
+    }
+}
+
+///
+/// RFC 7807 错误响应，server.error_mode 为 problem 时使用，Content-Type 为 `application/problem+json`
+///
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ProblemDetails {
+    // 固定为 about:blank，title 为状态码的标准描述
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    // 请求路径
+    pub instance: String,
+    pub error_code: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use schemars::schema_for;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_serialize() {
+        assert_eq!(serde_json::to_value(ApiResponse::ok(1)).unwrap(), json!({"status": true, "data": 1}));
+        assert_eq!(serde_json::to_value(ApiResponse::empty()).unwrap(), json!({"status": true}));
+
+        let page = Page { items: vec!["a"], page: 2, page_size: 1, total: 3, num_pages: 3 };
+        assert_eq!(serde_json::to_value(PageResponse::new(page)).unwrap(),
+            json!({"status": true, "data": ["a"], "page": 2, "page_size": 1, "total": 3, "num_pages": 3}));
+        assert_eq!(serde_json::to_value(ErrorResponse::new(10001usize, "invalid")).unwrap(),
+            json!({"status": false, "error_code": 10001, "error_message": "invalid"}));
+    }
+
+    #[test]
+    fn test_schema() {
+        let schema: Value = serde_json::to_value(schema_for!(ApiResponse<String>)).unwrap();
+        assert_eq!(schema["required"], json!(["data", "status"]));
+        assert_eq!(schema["properties"]["data"]["type"], "string");
+    }
+}
@@ -0,0 +1,503 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use fred::{prelude::{KeysInterface, ListInterface, RedisPool, SortedSetsInterface}, types::LMoveDirection};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, error, info, warn};
+
+use crate::{config::WorkerConfig, server::AppState, utils::{error::BuboResult, prometheus, snowflake, time::current_timestamp_ms}};
+
+///
+/// 后台任务处理器
+///
+#[async_trait]
+pub trait Worker: Send + Sync + 'static {
+    type Args: Serialize + DeserializeOwned + Send + 'static;
+
+    ///
+    /// 任务名称，同一应用内唯一
+    ///
+    fn name() -> &'static str;
+
+    ///
+    /// 最大重试次数，None 时使用配置 worker.max_retries
+    ///
+    fn max_retries(&self) -> Option<u32> {
+        None
+    }
+
+    async fn perform(&self, args: Self::Args) -> BuboResult<()>;
+
+    ///
+    /// 放入队列异步执行，返回任务id
+    ///
+    async fn perform_later(state: &AppState, args: Self::Args) -> BuboResult<i64>
+    where
+        Self: Sized,
+    {
+        enqueue::<Self>(state, args).await
+    }
+}
+
+///
+/// 队列中的任务
+///
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: i64,
+    pub worker: String,
+    pub args: Value,
+    // 已执行次数
+    pub attempts: u32,
+    pub enqueued_at: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+///
+/// 放入队列异步执行，返回任务id
+///
+pub async fn enqueue<W: Worker>(state: &AppState, args: W::Args) -> BuboResult<i64> {
+    push(&state.redis, state.app_name, W::name(), serde_json::to_value(args)?).await
+}
+
+async fn push(redis: &RedisPool, app_name: &str, worker: &str, args: Value) -> BuboResult<i64> {
+    let job = Job {
+        id: snowflake::new_id(),
+        worker: worker.to_owned(),
+        args,
+        attempts: 0,
+        enqueued_at: current_timestamp_ms(),
+        error: None,
+    };
+    redis.rpush::<(), _, _>(queue_key(app_name), serde_json::to_string(&job)?).await?;
+    debug!("enqueue job {} {}", job.worker, job.id);
+    Ok(job.id)
+}
+
+pub fn queue_key(app_name: &str) -> String {
+    format!("{}:worker:queue", app_name)
+}
+
+pub fn retry_key(app_name: &str) -> String {
+    format!("{}:worker:retry", app_name)
+}
+
+pub fn dead_key(app_name: &str) -> String {
+    format!("{}:worker:dead", app_name)
+}
+
+///
+/// 任务循环正在执行的任务，执行完成后删除
+///
+pub fn processing_key(app_name: &str, consumer: &str) -> String {
+    format!("{}:worker:processing:{}", app_name, consumer)
+}
+
+///
+/// 任务循环的心跳时间，用于发现已经退出的实例
+///
+pub fn consumers_key(app_name: &str) -> String {
+    format!("{}:worker:consumers", app_name)
+}
+
+#[async_trait]
+trait JobHandler: Send + Sync {
+    fn max_retries(&self) -> Option<u32>;
+
+    ///
+    /// 参数无法反序列化时返回外层错误，这类任务重试也不会成功
+    ///
+    async fn handle(&self, args: Value) -> Result<BuboResult<()>, serde_json::Error>;
+}
+
+struct Handler<W>(W);
+
+#[async_trait]
+impl<W: Worker> JobHandler for Handler<W> {
+    fn max_retries(&self) -> Option<u32> {
+        self.0.max_retries()
+    }
+
+    async fn handle(&self, args: Value) -> Result<BuboResult<()>, serde_json::Error> {
+        let args = serde_json::from_value(args)?;
+        Ok(self.0.perform(args).await)
+    }
+}
+
+///
+/// 任务调度，通过 Hooks::workers 注册处理器
+///
+#[derive(Default, Clone)]
+pub struct Processor {
+    handlers: HashMap<&'static str, Arc<dyn JobHandler>>,
+}
+
+impl Processor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<W: Worker>(&mut self, worker: W) {
+        if self.handlers.insert(W::name(), Arc::new(Handler(worker))).is_some() {
+            warn!("worker {} registered more than once", W::name());
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+
+    ///
+    /// 启动 concurrency 个任务循环，停机开始后不再取新任务，在停机截止时间前等待执行中的任务完成，
+    /// 超时未完成的任务留在执行列表中，由其他实例在心跳超时后重新执行
+    ///
+    pub async fn run(self, state: AppState) {
+        let config = state.config.worker.clone();
+        if !config.enable || self.is_empty() {
+            return;
+        }
+        info!("worker started, concurrency: {}", config.concurrency);
+        let processor = Arc::new(self);
+        let instance = snowflake::new_id();
+        let consumers: Vec<String> = (0..config.concurrency).map(|i| format!("{instance}-{i}")).collect();
+        let heartbeat = tokio::spawn(heartbeat_loop(state.redis.clone(), state.app_name, consumers.clone(), config.clone()));
+        let mut tasks = Vec::with_capacity(config.concurrency + 1);
+        tasks.push(tokio::spawn(requeue_loop(state.clone(), config.clone())));
+        for consumer in &consumers {
+            tasks.push(tokio::spawn(processor.clone().work_loop(state.clone(), config.clone(), consumer.clone())));
+        }
+
+        let deadline = state.shutdown.wait().await;
+        let mut finished = true;
+        for task in tasks {
+            if tokio::time::timeout_at(deadline, task).await.is_err() {
+                finished = false;
+                break;
+            }
+        }
+        heartbeat.abort();
+        if finished {
+            if let Err(e) = state.redis.zrem::<(), _, _>(consumers_key(state.app_name), consumers).await {
+                prometheus::record_redis_error(&e);
+            }
+            info!("worker stopped");
+        } else {
+            warn!("worker stop timeout, unfinished jobs will be requeued after {}s", config.stale_timeout);
+        }
+    }
+
+    async fn work_loop(self: Arc<Self>, state: AppState, config: WorkerConfig, consumer: String) {
+        let poll_interval = Duration::from_millis(config.poll_interval);
+        while !state.shutdown.is_shutting_down() {
+            match self.work_once(&state.redis, state.app_name, &config, &consumer).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep(poll_interval) => {},
+                        _ = state.shutdown.wait() => {},
+                    }
+                }
+                Err(e) => {
+                    error!("worker fetch job error: {}", e);
+                    tokio::time::sleep(poll_interval).await;
+                }
+            }
+        }
+    }
+
+    ///
+    /// 取出一个任务移到执行列表，处理完成（成功、安排重试或进入死信队列）后从执行列表删除，
+    /// 队列为空时返回 false
+    ///
+    async fn work_once(&self, redis: &RedisPool, app_name: &str, config: &WorkerConfig, consumer: &str) -> BuboResult<bool> {
+        let processing = processing_key(app_name, consumer);
+        let raw: Option<String> = redis.lmove(queue_key(app_name), &processing, LMoveDirection::Left, LMoveDirection::Right).await?;
+        let Some(raw) = raw else {
+            return Ok(false);
+        };
+        self.process(redis, app_name, config, &raw).await;
+        if let Err(e) = redis.lrem::<(), _, _>(&processing, 1, raw).await {
+            prometheus::record_redis_error(&e);
+            error!("worker ack job error: {}", e);
+        }
+        Ok(true)
+    }
+
+    async fn process(&self, redis: &RedisPool, app_name: &str, config: &WorkerConfig, raw: &str) {
+        let mut job: Job = match serde_json::from_str(raw) {
+            Ok(job) => job,
+            Err(e) => {
+                error!("worker invalid job: {}, {}", e, raw);
+                let _ = push_dead(redis, app_name, config, raw.to_owned()).await;
+                return;
+            }
+        };
+        let Some(handler) = self.handlers.get(job.worker.as_str()).cloned() else {
+            warn!("worker {} not registered, job {} moved to dead queue", job.worker, job.id);
+            job.error = Some("worker not registered".to_owned());
+            let _ = push_job_dead(redis, app_name, config, &job).await;
+            return;
+        };
+
+        job.attempts += 1;
+        // 在单独的任务中执行，perform 中的 panic 只影响当前任务
+        let args = job.args.clone();
+        let max_retries = handler.max_retries().unwrap_or(config.max_retries);
+        let error = match tokio::spawn(async move { handler.handle(args).await }).await {
+            Ok(Ok(Ok(()))) => {
+                debug!("job {} {} done", job.worker, job.id);
+                return;
+            }
+            Ok(Ok(Err(e))) => e.to_string(),
+            Ok(Err(e)) => {
+                error!("job {} {} invalid args, moved to dead queue: {}", job.worker, job.id, e);
+                job.error = Some(format!("invalid args: {e}"));
+                if let Err(e) = push_job_dead(redis, app_name, config, &job).await {
+                    error!("job {} {} reschedule error: {}", job.worker, job.id, e);
+                }
+                return;
+            }
+            Err(e) => e.to_string(),
+        };
+
+        let outcome = if job.attempts > max_retries {
+            error!("job {} {} failed after {} attempts: {}", job.worker, job.id, job.attempts, error);
+            job.error = Some(error);
+            push_job_dead(redis, app_name, config, &job).await
+        } else {
+            let delay = backoff(config.retry_base_delay, job.attempts);
+            warn!("job {} {} failed, retry in {}s: {}", job.worker, job.id, delay, error);
+            job.error = Some(error);
+            schedule_retry(redis, app_name, &job, delay).await
+        };
+        if let Err(e) = outcome {
+            error!("job {} {} reschedule error: {}", job.worker, job.id, e);
+        }
+    }
+}
+
+///
+/// 指数退避，base * 2^(attempts-1)，最长 1 小时
+///
+fn backoff(base: u64, attempts: u32) -> u64 {
+    base.saturating_mul(1u64 << attempts.saturating_sub(1).min(20)).min(3600)
+}
+
+async fn schedule_retry(redis: &RedisPool, app_name: &str, job: &Job, delay_secs: u64) -> BuboResult<()> {
+    let at = current_timestamp_ms() + delay_secs as i64 * 1000;
+    redis.zadd::<(), _, _>(retry_key(app_name), None, None, false, false, (at as f64, serde_json::to_string(job)?)).await?;
+    Ok(())
+}
+
+async fn push_job_dead(redis: &RedisPool, app_name: &str, config: &WorkerConfig, job: &Job) -> BuboResult<()> {
+    push_dead(redis, app_name, config, serde_json::to_string(job)?).await
+}
+
+async fn push_dead(redis: &RedisPool, app_name: &str, config: &WorkerConfig, raw: String) -> BuboResult<()> {
+    let key = dead_key(app_name);
+    redis.lpush::<(), _, _>(&key, raw).await?;
+    redis.ltrim::<(), _>(&key, 0, config.dead_max_len as i64 - 1).await?;
+    Ok(())
+}
+
+///
+/// 定时更新本实例各任务循环的心跳，执行时间很长的任务也不会被当作已退出
+///
+async fn heartbeat_loop(redis: RedisPool, app_name: &'static str, consumers: Vec<String>, config: WorkerConfig) {
+    let key = consumers_key(app_name);
+    let interval = Duration::from_secs((config.stale_timeout / 3).max(1));
+    loop {
+        let now = current_timestamp_ms() as f64;
+        let members: Vec<(f64, String)> = consumers.iter().map(|consumer| (now, consumer.clone())).collect();
+        if let Err(e) = redis.zadd::<(), _, _>(&key, None, None, false, false, members).await {
+            prometheus::record_redis_error(&e);
+            error!("worker heartbeat error: {}", e);
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+///
+/// 把到期的重试任务和心跳超时的实例中执行到一半的任务放回队列
+///
+async fn requeue_loop(state: AppState, config: WorkerConfig) {
+    let poll_interval = Duration::from_millis(config.poll_interval);
+    while !state.shutdown.is_shutting_down() {
+        if let Err(e) = requeue_due(&state.redis, state.app_name).await {
+            error!("worker requeue retry jobs error: {}", e);
+        }
+        let stale_before = current_timestamp_ms() - config.stale_timeout as i64 * 1000;
+        if let Err(e) = requeue_stale(&state.redis, state.app_name, stale_before).await {
+            error!("worker requeue stale jobs error: {}", e);
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(poll_interval) => {},
+            _ = state.shutdown.wait() => {},
+        }
+    }
+}
+
+async fn requeue_due(redis: &RedisPool, app_name: &str) -> BuboResult<()> {
+    let retry_key = retry_key(app_name);
+    let queue_key = queue_key(app_name);
+    let due: Vec<String> = redis.zrangebyscore(&retry_key, "-inf", current_timestamp_ms(), false, Some((0, 100))).await?;
+    for raw in due {
+        // 多实例同时处理时只有删除成功的实例放回队列
+        if redis.zrem::<i64, _, _>(&retry_key, raw.as_str()).await? == 1 {
+            redis.rpush::<(), _, _>(&queue_key, raw).await?;
+        }
+    }
+    Ok(())
+}
+
+///
+/// 心跳早于 stale_before 的任务循环，执行列表中的任务放回队列头部
+///
+async fn requeue_stale(redis: &RedisPool, app_name: &str, stale_before: i64) -> BuboResult<()> {
+    let consumers_key = consumers_key(app_name);
+    let queue_key = queue_key(app_name);
+    let stale: Vec<String> = redis.zrangebyscore(&consumers_key, "-inf", stale_before, false, Some((0, 100))).await?;
+    for consumer in stale {
+        if redis.zrem::<i64, _, _>(&consumers_key, consumer.as_str()).await? != 1 {
+            continue;
+        }
+        let processing = processing_key(app_name, &consumer);
+        let mut count = 0;
+        while redis.lmove::<Option<String>, _, _>(&processing, &queue_key, LMoveDirection::Right, LMoveDirection::Left).await?.is_some() {
+            count += 1;
+        }
+        if count > 0 {
+            warn!("worker {} heartbeat timeout, {} jobs requeued", consumer, count);
+        }
+    }
+    Ok(())
+}
+
+///
+/// 队列长度统计
+///
+pub async fn stats(redis: &RedisPool, app_name: &str) -> BuboResult<(u64, u64, u64)> {
+    let queued = redis.llen(queue_key(app_name)).await?;
+    let retry = redis.zcard(retry_key(app_name)).await?;
+    let dead = redis.llen(dead_key(app_name)).await?;
+    Ok((queued, retry, dead))
+}
+
+///
+/// 清空死信队列
+///
+pub async fn clear_dead(redis: &RedisPool, app_name: &str) -> BuboResult<()> {
+    redis.del::<(), _>(dead_key(app_name)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{testing::MockRedis, utils::error::{BuboError, SystemErrorCode}};
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const APP: &str = "bubo-test";
+
+    #[derive(Serialize, Deserialize)]
+    struct Args {
+        mode: String,
+    }
+
+    struct TestWorker;
+
+    #[async_trait]
+    impl Worker for TestWorker {
+        type Args = Args;
+
+        fn name() -> &'static str {
+            "test"
+        }
+
+        async fn perform(&self, args: Args) -> BuboResult<()> {
+            match args.mode.as_str() {
+                "ok" => Ok(()),
+                "panic" => panic!("boom"),
+                _ => Err(BuboError::system_error(SystemErrorCode::InternalServerError, "failed")),
+            }
+        }
+    }
+
+    fn jobs(raw: Vec<String>) -> Vec<Job> {
+        raw.iter().map(|raw| serde_json::from_str(raw).unwrap()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_process() {
+        let mock = Arc::new(MockRedis::default());
+        let redis = mock.pool().await;
+        let config = WorkerConfig { max_retries: 1, retry_base_delay: 0, ..Default::default() };
+        let mut processor = Processor::new();
+        processor.register(TestWorker);
+        let work_once = || processor.work_once(&redis, APP, &config, "c1");
+
+        push(&redis, APP, "test", json!({"mode": "ok"})).await.unwrap();
+        assert!(work_once().await.unwrap());
+        assert!(!work_once().await.unwrap());
+        assert!(mock.list(&processing_key(APP, "c1")).is_empty());
+
+        // 失败后重试，超过最大重试次数进入死信队列
+        let id = push(&redis, APP, "test", json!({"mode": "fail"})).await.unwrap();
+        work_once().await.unwrap();
+        let retry = mock.zset(&retry_key(APP));
+        assert_eq!(retry.len(), 1);
+        assert_eq!(jobs(vec![retry[0].1.clone()])[0].attempts, 1);
+        assert!(mock.list(&processing_key(APP, "c1")).is_empty());
+        requeue_due(&redis, APP).await.unwrap();
+        assert!(mock.zset(&retry_key(APP)).is_empty());
+        work_once().await.unwrap();
+        let dead = jobs(mock.list(&dead_key(APP)));
+        assert_eq!((dead[0].id, dead[0].attempts, dead[0].error.as_deref()), (id, 2, Some("failed")));
+
+        // 参数错误直接进入死信队列
+        push(&redis, APP, "test", json!({"other": 1})).await.unwrap();
+        work_once().await.unwrap();
+        let dead = jobs(mock.list(&dead_key(APP)));
+        assert_eq!(dead.len(), 2);
+        assert!(dead[0].error.as_deref().unwrap().starts_with("invalid args"));
+        assert!(mock.zset(&retry_key(APP)).is_empty());
+
+        // panic 作为失败处理，任务循环继续执行
+        push(&redis, APP, "test", json!({"mode": "panic"})).await.unwrap();
+        work_once().await.unwrap();
+        let retry = jobs(mock.zset(&retry_key(APP)).into_iter().map(|(_, raw)| raw).collect());
+        assert!(retry[0].error.as_deref().unwrap().contains("panicked"));
+        push(&redis, APP, "test", json!({"mode": "ok"})).await.unwrap();
+        assert!(work_once().await.unwrap());
+        assert!(mock.list(&queue_key(APP)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_requeue_stale() {
+        let mock = Arc::new(MockRedis::default());
+        let redis = mock.pool().await;
+        push(&redis, APP, "test", json!({"mode": "ok"})).await.unwrap();
+        // c1 取出任务后实例退出，c2 仍有心跳
+        let raw: Option<String> = redis.lmove(queue_key(APP), processing_key(APP, "c1"), LMoveDirection::Left, LMoveDirection::Right).await.unwrap();
+        let now = current_timestamp_ms();
+        redis.zadd::<(), _, _>(consumers_key(APP), None, None, false, false, vec![((now - 120_000) as f64, "c1"), (now as f64, "c2")]).await.unwrap();
+
+        requeue_stale(&redis, APP, now - 60_000).await.unwrap();
+        assert_eq!(mock.list(&queue_key(APP)), vec![raw.unwrap()]);
+        assert!(mock.list(&processing_key(APP, "c1")).is_empty());
+        assert_eq!(mock.zset(&consumers_key(APP)), vec![(now as f64, "c2".to_owned())]);
+    }
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(5, 1), 5);
+        assert_eq!(backoff(5, 2), 10);
+        assert_eq!(backoff(5, 4), 40);
+        assert_eq!(backoff(5, 100), 3600);
+    }
+}